@@ -0,0 +1,326 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{error::ConfigError, models::ProxyConfiguration, storage};
+
+pub const BACKUP_DIR_NAME: &str = "backups";
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupInfo {
+    pub id: String,
+    pub path: PathBuf,
+    pub created: String,
+    pub size: u64
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    Unchanged,
+    Added,
+    Removed
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub kind: DiffKind,
+    pub text: String
+}
+
+pub fn backup_dir(config_path: &Path) -> PathBuf {
+    config_path.with_file_name(BACKUP_DIR_NAME)
+}
+
+fn config_file_name(config_path: &Path) -> String {
+    config_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "proxy_config.toml".into())
+}
+
+// Backups are named proxy_config.toml.<UTC timestamp>.bak so sorting by name sorts by age.
+pub fn snapshot(config_path: &Path, max_backups: usize) -> Result<Option<BackupInfo>, ConfigError> {
+    if max_backups == 0 || !config_path.is_file() {
+        return Ok(None);
+    }
+
+    let dir = backup_dir(config_path);
+    fs::create_dir_all(&dir).map_err(|e| ConfigError::io(&dir, e))?;
+
+    // Two saves within the same millisecond get consecutive timestamps instead of a suffix,
+    // which would break the ordering by name.
+    let mut time = SystemTime::now();
+    let mut id = format!("{}.{}.bak", config_file_name(config_path), file_timestamp(time));
+    while dir.join(&id).exists() {
+        time += Duration::from_millis(1);
+        id = format!("{}.{}.bak", config_file_name(config_path), file_timestamp(time));
+    }
+
+    // The configuration can hold plaintext secrets, so the copy is private whatever the
+    // permissions of the original are.
+    let backup_path = dir.join(&id);
+    let contents = fs::read(config_path).map_err(|e| ConfigError::io(config_path, e))?;
+    storage::write_private(&backup_path, &contents).map_err(|e| ConfigError::io(&backup_path, e))?;
+
+    prune(config_path, max_backups)?;
+
+    Ok(list(config_path)?.into_iter().find(|backup| backup.id == id))
+}
+
+pub fn prune(config_path: &Path, max_backups: usize) -> Result<(), ConfigError> {
+    for backup in list(config_path)?.into_iter().skip(max_backups) {
+        fs::remove_file(&backup.path).map_err(|e| ConfigError::io(&backup.path, e))?;
+    }
+    Ok(())
+}
+
+// Newest first.
+pub fn list(config_path: &Path) -> Result<Vec<BackupInfo>, ConfigError> {
+    let dir = backup_dir(config_path);
+
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let prefix = format!("{}.", config_file_name(config_path));
+    let mut backups = Vec::new();
+
+    for entry in fs::read_dir(&dir).map_err(|e| ConfigError::io(&dir, e))? {
+        let entry = entry.map_err(|e| ConfigError::io(&dir, e))?;
+        let id = entry.file_name().to_string_lossy().into_owned();
+
+        if !id.starts_with(&prefix) || !id.ends_with(".bak") {
+            continue;
+        }
+
+        let metadata = entry.metadata().map_err(|e| ConfigError::io(&entry.path(), e))?;
+        let created = metadata
+            .modified()
+            .map(display_timestamp)
+            .unwrap_or_default();
+
+        backups.push(BackupInfo {
+            id,
+            path: entry.path(),
+            created,
+            size: metadata.len()
+        });
+    }
+
+    backups.sort_by(|a, b| b.id.cmp(&a.id));
+    Ok(backups)
+}
+
+fn find(config_path: &Path, id: &str) -> Result<BackupInfo, ConfigError> {
+    list(config_path)?
+        .into_iter()
+        .find(|backup| backup.id == id)
+        .ok_or_else(|| ConfigError::not_found(&backup_dir(config_path).join(id)))
+}
+
+pub fn read(config_path: &Path, id: &str) -> Result<String, ConfigError> {
+    let backup = find(config_path, id)?;
+    fs::read_to_string(&backup.path).map_err(|e| ConfigError::io(&backup.path, e))
+}
+
+// Lines prefixed Removed only exist in the backup, Added ones only in the current file.
+pub fn diff(config_path: &Path, id: &str) -> Result<Vec<DiffLine>, ConfigError> {
+    let backup = read(config_path, id)?;
+    let current = fs::read_to_string(config_path).map_err(|e| ConfigError::io(config_path, e))?;
+    Ok(diff_lines(&backup, &current))
+}
+
+// The current file is backed up first, so a restore can itself be undone.
+pub fn restore(config_path: &Path, id: &str, max_backups: usize) -> Result<(), ConfigError> {
+    let backup = find(config_path, id)?;
+    let contents = fs::read_to_string(&backup.path).map_err(|e| ConfigError::io(&backup.path, e))?;
+
+    toml::from_str::<ProxyConfiguration>(&contents).map_err(|e| ConfigError::parse(&backup.path, &contents, e))?;
+
+    snapshot(config_path, max_backups.max(1))?;
+    storage::write_atomic(config_path, contents.as_bytes()).map_err(|e| ConfigError::io(config_path, e))
+}
+
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // lengths[i][j] is the longest common subsequence of old[i..] and new[j..].
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(DiffLine { kind: DiffKind::Unchanged, text: old[i].into() });
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            lines.push(DiffLine { kind: DiffKind::Removed, text: old[i].into() });
+            i += 1;
+        } else {
+            lines.push(DiffLine { kind: DiffKind::Added, text: new[j].into() });
+            j += 1;
+        }
+    }
+
+    lines
+}
+
+fn civil_from_unix(time: SystemTime) -> (i64, u32, u32, u32, u32, u32, u32) {
+    let duration = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = duration.as_secs() as i64;
+    let days = secs.div_euclid(86_400);
+    let seconds_of_day = secs.rem_euclid(86_400) as u32;
+
+    // Howard Hinnant's days-to-civil algorithm.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        seconds_of_day % 3_600 / 60,
+        seconds_of_day % 60,
+        duration.subsec_millis()
+    )
+}
+
+fn file_timestamp(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second, millis) = civil_from_unix(time);
+    format!("{:04}{:02}{:02}T{:02}{:02}{:02}.{:03}Z", year, month, day, hour, minute, second, millis)
+}
+
+pub fn display_timestamp(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second, _) = civil_from_unix(time);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", year, month, day, hour, minute, second)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("arc2proxy-gui-backups-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn at(secs: u64, millis: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn formats_known_timestamps() {
+        assert_eq!(display_timestamp(UNIX_EPOCH), "1970-01-01 00:00:00 UTC");
+        assert_eq!(display_timestamp(at(1_709_251_199, 0)), "2024-02-29 23:59:59 UTC");
+        assert_eq!(display_timestamp(at(1_709_251_200, 0)), "2024-03-01 00:00:00 UTC");
+        assert_eq!(display_timestamp(at(951_782_400, 0)), "2000-02-29 00:00:00 UTC");
+        assert_eq!(display_timestamp(at(4_107_542_400, 0)), "2100-03-01 00:00:00 UTC");
+        assert_eq!(file_timestamp(at(1_709_251_199, 7)), "20240229T235959.007Z");
+    }
+
+    #[test]
+    fn diffs_changed_lines() {
+        let kinds = |lines: Vec<DiffLine>| {
+            lines.into_iter().map(|line| (line.kind, line.text)).collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            kinds(diff_lines("a = 1\nb = 2\nc = 3\n", "a = 1\nb = 20\nc = 3\nd = 4\n")),
+            vec![
+                (DiffKind::Unchanged, "a = 1".to_string()),
+                (DiffKind::Removed, "b = 2".to_string()),
+                (DiffKind::Added, "b = 20".to_string()),
+                (DiffKind::Unchanged, "c = 3".to_string()),
+                (DiffKind::Added, "d = 4".to_string()),
+            ]
+        );
+        assert!(diff_lines("a = 1\n", "a = 1\n").iter().all(|line| line.kind == DiffKind::Unchanged));
+        assert_eq!(diff_lines("", "a = 1").len(), 1);
+    }
+
+    #[test]
+    fn keeps_only_the_newest_backups() {
+        let dir = test_dir("prune");
+        let config_path = dir.join("proxy_config.toml");
+        fs::write(&config_path, "listening_port_http = 80\n").unwrap();
+
+        fs::create_dir_all(backup_dir(&config_path)).unwrap();
+        for timestamp in ["20240101T000000.000Z", "20240301T000000.000Z", "20240201T000000.000Z"] {
+            fs::write(backup_dir(&config_path).join(format!("proxy_config.toml.{}.bak", timestamp)), "").unwrap();
+        }
+        fs::write(backup_dir(&config_path).join("other.toml.20230101T000000.000Z.bak"), "").unwrap();
+
+        prune(&config_path, 2).unwrap();
+
+        let ids: Vec<String> = list(&config_path).unwrap().into_iter().map(|backup| backup.id).collect();
+        assert_eq!(ids, vec!["proxy_config.toml.20240301T000000.000Z.bak", "proxy_config.toml.20240201T000000.000Z.bak"]);
+        assert!(backup_dir(&config_path).join("other.toml.20230101T000000.000Z.bak").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn snapshots_stay_within_max_backups() {
+        let dir = test_dir("snapshot");
+        let config_path = dir.join("proxy_config.toml");
+
+        for port in 0..5 {
+            fs::write(&config_path, format!("listening_port_http = {}\n", 8000 + port)).unwrap();
+            assert!(snapshot(&config_path, 3).unwrap().is_some());
+        }
+
+        let backups = list(&config_path).unwrap();
+        assert_eq!(backups.len(), 3);
+        assert_eq!(read(&config_path, &backups[0].id).unwrap(), "listening_port_http = 8004\n");
+        assert_eq!(snapshot(&config_path, 0).unwrap(), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn snapshots_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = test_dir("permissions");
+        let config_path = dir.join("proxy_config.toml");
+        fs::write(&config_path, "api_key = \"hunter2\"\n").unwrap();
+        fs::set_permissions(&config_path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let backup = snapshot(&config_path, 3).unwrap().unwrap();
+
+        assert_eq!(fs::metadata(&backup.path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::metadata(&config_path).unwrap().permissions().mode() & 0o777, 0o644);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
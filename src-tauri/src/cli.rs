@@ -0,0 +1,475 @@
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    backups,
+    certificates::CertificateStatus,
+    error::ConfigError,
+    export::{self, ExportFormat},
+    location::{self, ConfigLocator},
+    models::{ProxyConfiguration, ProxyRuleInner},
+    preferences,
+    rules,
+    schema,
+    secrets,
+    settings,
+    tls_policy,
+    validation::Severity,
+};
+
+pub const JSON_ARG: &str = "--json";
+
+const USAGE: &str = "\
+Usage: arc2proxy-gui [--config PATH] [--json] <command>
+
+Without a command the GUI starts.
+
+Commands:
+  get [KEY]             Print all settings, or the value of KEY
+  set KEY VALUE         Change a setting, an empty VALUE clears it
+  secret set KEY VALUE [--encrypt]
+                        Change a secret, - reads it from stdin. VALUE may be
+                        env:NAME or file:PATH, resolved when the proxy starts
+  secret rotate         Replace the API key with a new random one and print it
+  rules list            List the proxy rules
+  rules add JSON        Add a proxy rule given as JSON, - reads it from stdin
+  rules remove DOMAIN   Remove the proxy rule for DOMAIN
+  validate              Check the configuration, exits with 1 when it has errors
+  schema                Print the JSON Schema of the configuration file
+  tls                   Check that every HTTPS backend supports proxy_min_tls_version,
+                        exits with 1 when one does not or cannot be reached
+  export FORMAT         Print the proxy rules as nginx, haproxy or caddy configuration
+  certificates [DAYS]   Show the certificate of every domain, exits with 1 when one
+                        is missing or expires within DAYS (default 30)
+  certificates import DOMAIN CHAIN KEY
+                        Use the PEM files CHAIN and KEY for DOMAIN instead of
+                        Let's Encrypt, after checking that they match
+  certificates remove DOMAIN
+                        Go back to Let's Encrypt for DOMAIN
+  backup                Back up the configuration file now
+  backup list           List the backups, newest first
+  restore ID            Restore the backup with the given ID
+  help                  Show this help";
+
+// Only these words switch to the CLI. Anything else starts the GUI, launchers pass their
+// own arguments, e.g. `-psn_0_12345` on macOS.
+const COMMANDS: [&str; 14] = [
+    "help", "--help", "-h", "get", "set", "secret", "rules", "validate", "schema", "tls", "export",
+    "certificates", "backup", "restore",
+];
+
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+enum Output {
+    Human,
+    Json
+}
+
+impl Output {
+    // Write errors are ignored, the reader of a pipe (e.g. `head`) may be gone already.
+    fn print<T: Serialize>(&self, value: &T, human: impl FnOnce() -> String) {
+        let text = match self {
+            Output::Human => human(),
+            Output::Json => serde_json::to_string_pretty(value).unwrap_or_default()
+        };
+
+        if !text.is_empty() {
+            let _ = writeln!(io::stdout(), "{}", text);
+        }
+    }
+
+    fn error(&self, error: &ConfigError) {
+        match self {
+            // Display already lists the issues of an Invalid error.
+            Output::Human => eprintln!("error: {}", error),
+            Output::Json => eprintln!("{}", serde_json::to_string_pretty(error).unwrap_or_default())
+        }
+    }
+}
+
+// Splits the global options from the command words. `--config` is left to the locator.
+fn command_words(args: &[String]) -> (Vec<&str>, bool) {
+    let mut words = Vec::new();
+    let mut json = false;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == location::CONFIG_ARG {
+            args.next();
+        } else if arg.starts_with("--config=") {
+            continue;
+        } else if arg == JSON_ARG {
+            json = true;
+        } else {
+            words.push(arg.as_str());
+        }
+    }
+
+    (words, json)
+}
+
+// Runs a subcommand and returns its exit code, or None when there is none and the GUI
+// should start instead.
+pub fn run(args: Vec<String>) -> Option<i32> {
+    let (words, json) = command_words(&args);
+
+    if !words.first().is_some_and(|word| COMMANDS.contains(word)) {
+        return None;
+    }
+
+    let output = if json { Output::Json } else { Output::Human };
+    let locator = ConfigLocator::new(location::config_path_from_args(args.iter().cloned()), preferences::default_preferences_path());
+
+    let result = match words.as_slice() {
+        ["help"] | ["--help"] | ["-h"] => {
+            let _ = writeln!(io::stdout(), "{}", USAGE);
+            Ok(0)
+        }
+        ["get"] => get_all(&locator, &output),
+        ["get", key] => get(&locator, &output, key),
+        ["set", key, value] => set(&locator, &output, key, value),
+        ["secret", "set", key, value] => set_secret(&locator, &output, key, value, false),
+        ["secret", "set", key, value, "--encrypt"] => set_secret(&locator, &output, key, value, true),
+        ["secret", "rotate"] => rotate_api_key(&locator, &output),
+        ["rules", "list"] => list_rules(&locator, &output),
+        ["rules", "add", rule] => add_rule(&locator, &output, rule),
+        ["rules", "remove", domain] => remove_rule(&locator, &output, domain),
+        ["validate"] => validate(&locator, &output),
+        ["schema"] => {
+            let _ = writeln!(io::stdout(), "{}", serde_json::to_string_pretty(schema::schema()).unwrap_or_default());
+            Ok(0)
+        }
+        ["tls"] => check_tls(&locator, &output),
+        ["export", format] => export(&locator, &output, format),
+        ["certificates"] => list_certificates(&locator, &output, None),
+        ["certificates", "import", domain, chain, key] => import_certificate(&locator, &output, domain, chain, key),
+        ["certificates", "remove", domain] => remove_certificate(&locator, &output, domain),
+        ["certificates", days] => list_certificates(&locator, &output, Some(days)),
+        ["backup"] => backup(&locator, &output),
+        ["backup", "list"] => list_backups(&locator, &output),
+        ["restore", id] => restore(&locator, &output, id),
+        _ => {
+            eprintln!("{}", USAGE);
+            Ok(EXIT_USAGE)
+        }
+    };
+
+    Some(result.unwrap_or_else(|error| {
+        output.error(&error);
+        EXIT_ERROR
+    }))
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::Null => "(not set)".into(),
+        Value::String(text) => text.clone(),
+        other => other.to_string()
+    }
+}
+
+// Secrets are never printed, only whether they are set or which reference they use.
+fn read_masked(locator: &ConfigLocator) -> Result<ProxyConfiguration, ConfigError> {
+    Ok(secrets::mask(&crate::_read_configuration(&locator.path()?)?))
+}
+
+fn get_all(locator: &ConfigLocator, output: &Output) -> Result<i32, ConfigError> {
+    let model = read_masked(locator)?;
+
+    let mut values = serde_json::Map::new();
+    for setting in settings::SETTINGS.iter() {
+        values.insert(setting.name.into(), settings::get_value(&model, setting.name)?);
+    }
+
+    output.print(&values, || {
+        settings::SETTINGS
+            .iter()
+            .map(|setting| match (&values[setting.name], setting.default) {
+                (Value::Null, Some(default)) => format!("{} = (not set, defaults to {})", setting.name, default),
+                (value, _) => format!("{} = {}", setting.name, display_value(value))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    });
+    Ok(0)
+}
+
+fn get(locator: &ConfigLocator, output: &Output, key: &str) -> Result<i32, ConfigError> {
+    let value = settings::get_value(&read_masked(locator)?, key)?;
+    output.print(&value, || display_value(&value));
+    Ok(0)
+}
+
+fn set(locator: &ConfigLocator, output: &Output, key: &str, value: &str) -> Result<i32, ConfigError> {
+    crate::_save_value(locator, key, value)?;
+    let model = crate::_read_configuration(&locator.path()?)?;
+    let value = settings::get_value(&model, key)?;
+    output.print(&value, || format!("{} = {}", key, display_value(&value)));
+    Ok(0)
+}
+
+fn set_secret(locator: &ConfigLocator, output: &Output, key: &str, value: &str, encrypt: bool) -> Result<i32, ConfigError> {
+    let value = if value == "-" {
+        let mut value = String::new();
+        io::stdin()
+            .read_to_string(&mut value)
+            .map_err(|e| ConfigError::validation(key, e.to_string()))?;
+        value
+    } else {
+        value.to_string()
+    };
+
+    let model = secrets::mask(&crate::_set_secret(locator, key, &value, encrypt)?);
+    let value = settings::get_value(&model, key)?;
+    output.print(&value, || format!("{} = {}", key, display_value(&value)));
+    Ok(0)
+}
+
+fn rotate_api_key(locator: &ConfigLocator, output: &Output) -> Result<i32, ConfigError> {
+    let (api_key, _) = crate::_rotate_api_key(locator)?;
+    output.print(&api_key, || api_key.clone());
+    Ok(0)
+}
+
+fn describe_rule(rule: &ProxyRuleInner) -> String {
+    let target = match &rule.routing_rules {
+        Some(routing_rules) => format!("{:?} routing over {} location(s)", routing_rules.routing_method, routing_rules.routing_locations.len()),
+        None => {
+            let addr = rule.forward_addr.as_ref().or(rule.forward_ipv4.as_ref()).or(rule.forward_ipv6.as_ref()).cloned().unwrap_or_default();
+            match rule.forward_port_https.or(rule.forward_port_http) {
+                Some(port) => format!("{}:{}", addr, port),
+                None => addr
+            }
+        }
+    };
+
+    format!("{} -> {} ({:?})", rule.domain, target, rule.rule_type)
+}
+
+fn list_rules(locator: &ConfigLocator, output: &Output) -> Result<i32, ConfigError> {
+    let model = crate::_read_configuration(&locator.path()?)?;
+    output.print(&model.proxy_rules, || model.proxy_rules.iter().map(describe_rule).collect::<Vec<_>>().join("\n"));
+    Ok(0)
+}
+
+fn add_rule(locator: &ConfigLocator, output: &Output, rule: &str) -> Result<i32, ConfigError> {
+    let json = if rule == "-" {
+        let mut json = String::new();
+        io::stdin()
+            .read_to_string(&mut json)
+            .map_err(|e| ConfigError::validation("rule", e.to_string()))?;
+        json
+    } else {
+        rule.to_string()
+    };
+
+    let rule: ProxyRuleInner = serde_json::from_str(&json).map_err(|e| ConfigError::validation("rule", e.to_string()))?;
+    let domain = rule.domain.clone();
+    let model = crate::_edit_configuration(locator, |model| rules::add_rule(model, rule))?;
+
+    output.print(&model.proxy_rules, || format!("Added {}.", domain));
+    Ok(0)
+}
+
+fn remove_rule(locator: &ConfigLocator, output: &Output, domain: &str) -> Result<i32, ConfigError> {
+    let model = crate::_edit_configuration(locator, |model| rules::delete_rule(model, domain).map(|_| ()))?;
+    output.print(&model.proxy_rules, || format!("Removed {}.", domain));
+    Ok(0)
+}
+
+fn validate(locator: &ConfigLocator, output: &Output) -> Result<i32, ConfigError> {
+    let report = crate::_validate_file(&locator.path()?)?;
+
+    output.print(&report, || {
+        if report.issues.is_empty() {
+            return "The configuration is valid.".into();
+        }
+
+        report
+            .issues
+            .iter()
+            .map(|issue| {
+                let severity = if issue.severity == Severity::Error { "error" } else { "warning" };
+                format!("{}: {}: {}", severity, issue.field, issue.message)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    });
+
+    Ok(if report.has_errors() { EXIT_ERROR } else { 0 })
+}
+
+fn check_tls(locator: &ConfigLocator, output: &Output) -> Result<i32, ConfigError> {
+    let checks = tls_policy::check_backends(&crate::_read_configuration(&locator.path()?)?);
+
+    output.print(&checks, || {
+        checks
+            .iter()
+            .map(|check| {
+                let backend = match check.location {
+                    Some(index) => format!("{} location #{} ({})", check.domain, index + 1, check.target),
+                    None => format!("{} ({})", check.domain, check.target)
+                };
+                let outcome = match (&check.error, &check.version) {
+                    (Some(error), _) => format!("error: {}", error),
+                    (None, version) => format!("{} {}", version.as_deref().unwrap_or("?"), check.cipher.as_deref().unwrap_or_default())
+                };
+                let mut line = format!("{}: {}", backend, outcome);
+                if let Some(warning) = &check.warning {
+                    line.push_str(&format!("\n  warning: {}", warning));
+                }
+                if let Some(certificate_error) = &check.certificate_error {
+                    line.push_str(&format!("\n  certificate: {}", certificate_error));
+                }
+                line
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    });
+
+    let failed = checks.iter().any(|check| check.error.is_some() || check.meets_minimum == Some(false));
+    Ok(if failed { EXIT_ERROR } else { 0 })
+}
+
+// The configuration goes to stdout and the warnings to stderr, so the output can be
+// redirected straight into a file.
+fn export(locator: &ConfigLocator, output: &Output, format: &str) -> Result<i32, ConfigError> {
+    let format = ExportFormat::parse(format)
+        .ok_or_else(|| ConfigError::validation("format", format!("Unknown format \"{}\", use nginx, haproxy or caddy.", format)))?;
+    let export = export::export(&crate::_read_configuration(&locator.path()?)?, format);
+
+    if let Output::Human = output {
+        for warning in &export.warnings {
+            match &warning.domain {
+                Some(domain) => eprintln!("warning: {}: {}", domain, warning.message),
+                None => eprintln!("warning: {}", warning.message)
+            }
+        }
+    }
+
+    output.print(&export, || export.text.trim_end().to_string());
+    Ok(0)
+}
+
+fn list_certificates(locator: &ConfigLocator, output: &Output, days: Option<&str>) -> Result<i32, ConfigError> {
+    let days = days
+        .map(|days| days.parse::<u32>().map_err(|e| ConfigError::validation("days", format!("\"{}\" {}", days, e))))
+        .transpose()?;
+    let inventory = crate::_certificate_inventory(locator, days)?;
+
+    output.print(&inventory, || {
+        let mut lines = vec![match &inventory.directory {
+            Some(directory) => format!("Certificates in {}", directory.display()),
+            None => "No certificate directory found.".into()
+        }];
+
+        for domain in &inventory.domains {
+            lines.push(match (&domain.certificate, domain.status) {
+                (Some(certificate), status) => format!(
+                    "{}: {:?}, expires {} ({} days), {}, {}",
+                    domain.domain, status, certificate.not_after, certificate.days_left, certificate.key_type, certificate.issuer
+                ),
+                (None, _) => format!("{}: {:?}", domain.domain, CertificateStatus::Missing)
+            });
+        }
+
+        for unreadable in &inventory.unreadable {
+            lines.push(format!("unreadable: {}: {}", unreadable.path.display(), unreadable.message));
+        }
+
+        lines.join("\n")
+    });
+
+    Ok(if inventory.needs_attention() { EXIT_ERROR } else { 0 })
+}
+
+fn import_certificate(locator: &ConfigLocator, output: &Output, domain: &str, chain: &str, key: &str) -> Result<i32, ConfigError> {
+    let read = |path: &str| fs::read_to_string(path).map_err(|e| ConfigError::io(Path::new(path), e));
+    let domain_dir = crate::_import_certificate(locator, domain, &read(chain)?, &read(key)?)?;
+    output.print(&domain_dir, || format!("Installed the certificate for {} in {}.", domain, domain_dir.display()));
+    Ok(0)
+}
+
+fn remove_certificate(locator: &ConfigLocator, output: &Output, domain: &str) -> Result<i32, ConfigError> {
+    crate::_remove_certificate(locator, domain)?;
+    output.print(&domain, || format!("{} uses Let's Encrypt again.", domain));
+    Ok(0)
+}
+
+fn backup(locator: &ConfigLocator, output: &Output) -> Result<i32, ConfigError> {
+    let path_buf = locator.path()?;
+    let backup = backups::snapshot(&path_buf, locator.preferences().load().max_backups().max(1))?;
+    output.print(&backup, || match &backup {
+        Some(backup) => format!("Backed up to {}.", backup.path.display()),
+        None => String::new()
+    });
+    Ok(0)
+}
+
+fn list_backups(locator: &ConfigLocator, output: &Output) -> Result<i32, ConfigError> {
+    let backups = backups::list(&locator.path()?)?;
+    output.print(&backups, || {
+        backups
+            .iter()
+            .map(|backup| format!("{}  {}  {} bytes", backup.id, backup.created, backup.size))
+            .collect::<Vec<_>>()
+            .join("\n")
+    });
+    Ok(0)
+}
+
+fn restore(locator: &ConfigLocator, output: &Output, id: &str) -> Result<i32, ConfigError> {
+    crate::_restore_backup(locator, id)?;
+    output.print(&id, || format!("Restored {}.", id));
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn starts_the_gui_for_launcher_arguments() {
+        assert_eq!(run(args(&[])), None);
+        assert_eq!(run(args(&["-psn_0_12345"])), None);
+        assert_eq!(run(args(&["--config", "/tmp/proxy_config.toml"])), None);
+        assert_eq!(run(args(&["--json", "--some-webview-flag"])), None);
+    }
+
+    #[test]
+    fn prints_usage_for_known_commands_with_wrong_arguments() {
+        assert_eq!(run(args(&["help"])), Some(0));
+        assert_eq!(run(args(&["rules", "rename", "a.com"])), Some(EXIT_USAGE));
+    }
+
+    #[test]
+    fn get_masks_secrets() {
+        let dir = std::env::temp_dir().join(format!("arc2proxy-gui-cli-get-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(location::CONFIG_FILE_NAME);
+        fs::write(&path, "api_key = \"hunter2\"\nazure_table_storage_key = \"env:AZURE_KEY\"\nlistening_port_http = 8080\nproxy_rules = []\n").unwrap();
+
+        let locator = ConfigLocator::new(Some(path), None);
+        let model = read_masked(&locator).unwrap();
+        assert_eq!(settings::get_value(&model, "api_key").unwrap(), Value::from(secrets::MASK));
+        assert_eq!(settings::get_value(&model, "azure_table_storage_key").unwrap(), Value::from("env:AZURE_KEY"));
+        assert_eq!(settings::get_value(&model, "listening_port_http").unwrap(), Value::from(8080));
+
+        let config = args(&["--config", dir.join(location::CONFIG_FILE_NAME).to_str().unwrap(), "--json"]);
+        assert_eq!(run([config.clone(), args(&["get", "api_key"])].concat()), Some(0));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,272 @@
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, TableLike, Value};
+
+use crate::{error::ConfigError, models::ProxyConfiguration};
+
+// Renders `model` on top of the original file contents. Only keys whose value actually
+// changed are rewritten, so comments, key order and keys this version of the GUI does
+// not know about survive the round trip. An original that is not valid TOML has nothing
+// worth keeping, so it is replaced by the serialized model.
+pub fn render(original: &str, model: &ProxyConfiguration) -> Result<String, ConfigError> {
+    let new_string = toml::to_string(model)?;
+
+    let mut target = match original.parse::<DocumentMut>() {
+        Ok(document) => document,
+        Err(_) => return Ok(new_string)
+    };
+
+    let new_document = new_string
+        .parse::<DocumentMut>()
+        .map_err(|e| ConfigError::Serialize { message: e.to_string() })?;
+
+    // What the original file looked like to the model. Keys present here but missing from
+    // the new model were cleared on purpose, anything else missing is an unknown key.
+    let previous_document = toml::from_str::<ProxyConfiguration>(original)
+        .ok()
+        .and_then(|previous| toml::to_string(&previous).ok())
+        .and_then(|previous| previous.parse::<DocumentMut>().ok());
+
+    let in_document_order = is_in_document_order(target.as_table());
+
+    merge_table(
+        target.as_table_mut(),
+        previous_document.as_ref().map(|document| document.as_table() as &dyn TableLike),
+        new_document.as_table()
+    );
+
+    // toml_edit prints tables by their parsed position, so moved or added tables need new
+    // positions. Files with a hand-made table order are left alone.
+    if in_document_order {
+        renumber_tables(target.as_table_mut(), &mut 0);
+    }
+
+    Ok(target.to_string())
+}
+
+fn table_positions(table: &Table, positions: &mut Vec<usize>) {
+    if let Some(position) = table.position() {
+        positions.push(position);
+    }
+
+    for (_, item) in table.iter() {
+        match item {
+            Item::Table(table) => table_positions(table, positions),
+            Item::ArrayOfTables(array) => array.iter().for_each(|table| table_positions(table, positions)),
+            _ => {}
+        }
+    }
+}
+
+fn is_in_document_order(table: &Table) -> bool {
+    let mut positions = Vec::new();
+    table_positions(table, &mut positions);
+    positions.windows(2).all(|pair| pair[0] <= pair[1])
+}
+
+fn renumber_tables(table: &mut Table, next: &mut usize) {
+    table.set_position(*next);
+    *next += 1;
+
+    for (_, item) in table.iter_mut() {
+        match item {
+            Item::Table(table) => renumber_tables(table, next),
+            Item::ArrayOfTables(array) => array.iter_mut().for_each(|table| renumber_tables(table, next)),
+            _ => {}
+        }
+    }
+}
+
+fn merge_table(target: &mut dyn TableLike, previous: Option<&dyn TableLike>, new: &dyn TableLike) {
+    for (key, new_item) in new.iter() {
+        match target.get_mut(key) {
+            Some(existing) => merge_item(existing, previous.and_then(|previous| previous.get(key)), new_item),
+            None => {
+                target.insert(key, new_item.clone());
+            }
+        }
+    }
+
+    let cleared: Vec<String> = target
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !new.contains_key(key) && previous.is_some_and(|previous| previous.contains_key(key)))
+        .collect();
+
+    for key in cleared {
+        target.remove(&key);
+    }
+}
+
+fn merge_item(target: &mut Item, previous: Option<&Item>, new: &Item) {
+    if let (Some(target_table), Some(new_table)) = (target.as_table_like_mut(), new.as_table_like()) {
+        merge_table(target_table, previous.and_then(Item::as_table_like), new_table);
+        return;
+    }
+
+    if let (Some(target_array), Some(new_array)) = (target.as_array_of_tables_mut(), new.as_array_of_tables()) {
+        merge_array_of_tables(target_array, previous.and_then(Item::as_array_of_tables), new_array);
+        return;
+    }
+
+    match (target.as_value_mut(), new.as_value()) {
+        (Some(target_value), Some(new_value)) => {
+            if !same_value(target_value, new_value) {
+                let decor = target_value.decor().clone();
+                *target_value = new_value.clone();
+                *target_value.decor_mut() = decor;
+            }
+        }
+        _ => *target = new.clone()
+    }
+}
+
+// Tables are matched by their `domain` key when they have one, so reordering proxy rules
+// moves their comments along with them. Anything else is matched by position.
+fn merge_array_of_tables(target: &mut ArrayOfTables, previous: Option<&ArrayOfTables>, new: &ArrayOfTables) {
+    let mut existing: Vec<Option<(Table, Option<&Table>)>> = target
+        .iter()
+        .enumerate()
+        .map(|(index, table)| Some((table.clone(), previous.and_then(|previous| previous.get(index)))))
+        .collect();
+
+    let mut merged = ArrayOfTables::new();
+
+    for (index, new_table) in new.iter().enumerate() {
+        let position = match identity(new_table) {
+            Some(id) => existing
+                .iter()
+                .position(|entry| entry.as_ref().is_some_and(|(table, _)| identity(table).as_deref() == Some(id.as_str()))),
+            None => existing.get(index).and_then(|entry| entry.as_ref()).map(|_| index)
+        };
+
+        match position.and_then(|position| existing[position].take()) {
+            Some((mut table, previous_table)) => {
+                merge_table(&mut table, previous_table.map(|table| table as &dyn TableLike), new_table);
+                merged.push(table);
+            }
+            None => merged.push(new_table.clone())
+        }
+    }
+
+    *target = merged;
+}
+
+fn identity(table: &Table) -> Option<String> {
+    table.get("domain").and_then(Item::as_str).map(|domain| domain.to_lowercase())
+}
+
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => a.value() == b.value(),
+        (Value::Integer(a), Value::Integer(b)) => a.value() == b.value(),
+        (Value::Float(a), Value::Float(b)) => a.value() == b.value(),
+        (Value::Boolean(a), Value::Boolean(b)) => a.value() == b.value(),
+        (Value::Datetime(a), Value::Datetime(b)) => a.value() == b.value(),
+        (Value::Array(a), Value::Array(b)) => a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same_value(a, b)),
+        (Value::InlineTable(a), Value::InlineTable(b)) => {
+            a.len() == b.len() && a.iter().all(|(key, a)| b.get(key).is_some_and(|b| same_value(a, b)))
+        }
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGINAL: &str = r#"# Written by hand, do not reformat.
+listening_port_http = 80 # plain HTTP
+listening_port_https = 443
+added_by_a_newer_proxy = "kept"
+logging_level = "info"
+
+# The main site.
+[[proxy_rules]]
+domain = "a.com"
+max_age_seconds = 60
+rule_type = "Whitelist"
+enable_logging = false
+ignore_query_string = false
+enable_sql_injection_protection = false
+enable_compression = false
+enable_minification = false
+enable_webp_transformation = false
+forward_ipv4 = "10.0.0.1" # app server
+forward_port_http = 8080
+
+# Static files.
+[[proxy_rules]]
+domain = "b.com"
+max_age_seconds = 3600
+rule_type = "Whitelist"
+enable_logging = true
+ignore_query_string = true
+enable_sql_injection_protection = false
+enable_compression = false
+enable_minification = false
+enable_webp_transformation = false
+forward_ipv4 = "10.0.0.2"
+forward_port_http = 8081
+"#;
+
+    fn model() -> ProxyConfiguration {
+        toml::from_str(ORIGINAL).unwrap()
+    }
+
+    fn changed_lines(before: &str, after: &str) -> Vec<(String, String)> {
+        assert_eq!(before.lines().count(), after.lines().count(), "{}", after);
+        before
+            .lines()
+            .zip(after.lines())
+            .filter(|(before, after)| before != after)
+            .map(|(before, after)| (before.to_string(), after.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn unchanged_model_renders_the_original() {
+        assert_eq!(render(ORIGINAL, &model()).unwrap(), ORIGINAL);
+    }
+
+    #[test]
+    fn changes_only_the_edited_line() {
+        let mut model = model();
+        model.listening_port_http = Some(8000);
+        model.proxy_rules[1].max_age_seconds = 60;
+
+        let rendered = render(ORIGINAL, &model).unwrap();
+        assert_eq!(changed_lines(ORIGINAL, &rendered), vec![
+            ("listening_port_http = 80 # plain HTTP".to_string(), "listening_port_http = 8000 # plain HTTP".to_string()),
+            ("max_age_seconds = 3600".to_string(), "max_age_seconds = 60".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn cleared_value_is_removed_and_unknown_key_kept() {
+        let mut model = model();
+        model.logging_level = None;
+
+        let rendered = render(ORIGINAL, &model).unwrap();
+        assert!(!rendered.contains("logging_level"));
+        assert!(rendered.contains("added_by_a_newer_proxy = \"kept\""));
+        assert_eq!(rendered.lines().count(), ORIGINAL.lines().count() - 1);
+    }
+
+    #[test]
+    fn reordered_rules_keep_their_comments() {
+        let mut model = model();
+        model.proxy_rules.swap(0, 1);
+
+        let rendered = render(ORIGINAL, &model).unwrap();
+        let static_files = rendered.find("# Static files.").unwrap();
+        let main_site = rendered.find("# The main site.").unwrap();
+        assert!(static_files < main_site);
+        assert!(rendered[static_files..main_site].contains("domain = \"b.com\""));
+        assert!(rendered[main_site..].contains("forward_ipv4 = \"10.0.0.1\" # app server"));
+    }
+
+    #[test]
+    fn unparsable_original_is_written_from_the_model() {
+        let rendered = render("listening_port_http = [80\n# broken", &model()).unwrap();
+        assert_eq!(rendered, toml::to_string(&model()).unwrap());
+    }
+}
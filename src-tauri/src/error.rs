@@ -0,0 +1,241 @@
+use std::{fmt, io, path::Path};
+
+use crate::validation::{ValidationIssue, ValidationReport};
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind")]
+pub enum ConfigError {
+    NotFound {
+        path: String
+    },
+    Io {
+        path: String,
+        message: String
+    },
+    Parse {
+        path: String,
+        message: String,
+        line: Option<usize>,
+        column: Option<usize>,
+        key: Option<String>
+    },
+    Validation {
+        field: String,
+        message: String
+    },
+    Invalid {
+        issues: Vec<ValidationIssue>
+    },
+    Serialize {
+        message: String
+    }
+}
+
+impl ConfigError {
+    pub fn not_found(path: &Path) -> Self {
+        ConfigError::NotFound {
+            path: path.display().to_string()
+        }
+    }
+
+    pub fn io(path: &Path, error: io::Error) -> Self {
+        if error.kind() == io::ErrorKind::NotFound {
+            return ConfigError::not_found(path);
+        }
+
+        ConfigError::Io {
+            path: path.display().to_string(),
+            message: error.to_string()
+        }
+    }
+
+    pub fn parse(path: &Path, source: &str, error: toml::de::Error) -> Self {
+        let (line, column) = match error.span() {
+            Some(span) => {
+                let (line, column) = line_column(source, span.start);
+                (Some(line), Some(column))
+            }
+            None => (None, None)
+        };
+
+        let key = backticked_key(error.message()).or_else(|| {
+            error.span().and_then(|span| key_at(source, span.start))
+        });
+
+        ConfigError::Parse {
+            path: path.display().to_string(),
+            message: error.message().to_string(),
+            line,
+            column,
+            key
+        }
+    }
+
+    pub fn validation(field: &str, message: impl Into<String>) -> Self {
+        ConfigError::Validation {
+            field: field.into(),
+            message: message.into()
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::NotFound { path } => {
+                write!(f, "Configuration file {} was not found.", path)
+            }
+            ConfigError::Io { path, message } => {
+                write!(f, "Could not access {}: {}", path, message)
+            }
+            ConfigError::Parse { path, message, line, column, key } => {
+                write!(f, "Could not parse {}", path)?;
+                if let (Some(line), Some(column)) = (line, column) {
+                    write!(f, " at line {}, column {}", line, column)?;
+                }
+                if let Some(key) = key {
+                    write!(f, " (key `{}`)", key)?;
+                }
+                write!(f, ": {}", message)
+            }
+            ConfigError::Validation { field, message } => {
+                write!(f, "Invalid value for `{}`: {}", field, message)
+            }
+            ConfigError::Invalid { issues } => {
+                write!(f, "Configuration has {} problem(s)", issues.len())?;
+                for issue in issues {
+                    write!(f, "\n  {}: {}", issue.field, issue.message)?;
+                }
+                Ok(())
+            }
+            ConfigError::Serialize { message } => {
+                write!(f, "Could not serialize configuration: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// Warnings are dropped, only errors prevent a configuration from being used.
+impl From<ValidationReport> for ConfigError {
+    fn from(report: ValidationReport) -> Self {
+        ConfigError::Invalid {
+            issues: report.errors().cloned().collect()
+        }
+    }
+}
+
+impl From<toml::ser::Error> for ConfigError {
+    fn from(error: toml::ser::Error) -> Self {
+        ConfigError::Serialize {
+            message: error.to_string()
+        }
+    }
+}
+
+// Both line and column are 1-based, as shown by editors.
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(source.len());
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+// serde reports missing/unknown fields as "missing field `name`".
+fn backticked_key(message: &str) -> Option<String> {
+    let start = message.find("field `").or_else(|| message.find("key `"))?;
+    let start = start + message[start..].find('`')? + 1;
+    let end = start + message[start..].find('`')?;
+    Some(message[start..end].to_string())
+}
+
+fn key_at(source: &str, offset: usize) -> Option<String> {
+    let offset = offset.min(source.len());
+    let line_start = source[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = source[line_start..].find('\n').map(|i| line_start + i).unwrap_or(source.len());
+    let line = source[line_start..line_end].trim();
+
+    if line.starts_with('[') {
+        let key = line.trim_start_matches('[').trim_end_matches(']').trim();
+        return if key.is_empty() { None } else { Some(key.to_string()) };
+    }
+
+    let (key, _) = line.split_once('=')?;
+    let key = key.trim().trim_matches('"');
+    if key.is_empty() { None } else { Some(key.to_string()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ProxyConfiguration;
+
+    const RULE: &str = r#"
+[[proxy_rules]]
+domain = "example.com"
+max_age_seconds = 60
+rule_type = "Proxy"
+enable_logging = true
+ignore_query_string = false
+enable_sql_injection_protection = true
+enable_compression = true
+enable_minification = false
+enable_webp_transformation = false
+"#;
+
+    // The configuration itself accepts unknown keys and leaves them to the schema check, so
+    // serde's "unknown field" message is produced with a struct that rejects them.
+    #[derive(Debug, serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Strict {
+        #[allow(dead_code)]
+        listening_port_http: u16
+    }
+
+    fn parse_error<T: serde::de::DeserializeOwned + fmt::Debug>(source: &str) -> ConfigError {
+        let error = toml::from_str::<T>(source).unwrap_err();
+        ConfigError::parse(Path::new("proxy_config.toml"), source, error)
+    }
+
+    fn location(error: &ConfigError) -> (Option<usize>, Option<usize>, Option<&str>) {
+        match error {
+            ConfigError::Parse { line, column, key, .. } => (*line, *column, key.as_deref()),
+            other => panic!("expected a parse error, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn bad_value_points_at_the_value() {
+        let error = parse_error::<ProxyConfiguration>("proxy_rules = []\nlistening_port_http = \"eighty\"\n");
+        assert_eq!(location(&error), (Some(2), Some(23), Some("listening_port_http")));
+
+        let error = parse_error::<ProxyConfiguration>(&RULE.replace("max_age_seconds = 60", "max_age_seconds = -1"));
+        assert_eq!(location(&error), (Some(4), Some(19), Some("max_age_seconds")));
+        assert_eq!(
+            error.to_string(),
+            "Could not parse proxy_config.toml at line 4, column 19 (key `max_age_seconds`): invalid value: integer `-1`, expected u64"
+        );
+    }
+
+    #[test]
+    fn missing_key_is_named_from_the_message() {
+        let error = parse_error::<ProxyConfiguration>("listening_port_http = 80\n");
+        assert_eq!(location(&error), (Some(1), Some(1), Some("proxy_rules")));
+    }
+
+    #[test]
+    fn unknown_key_is_named_from_the_message() {
+        let error = parse_error::<Strict>("listening_port_http = 80\nlistening_prot_https = 443\n");
+        assert_eq!(location(&error), (Some(2), Some(1), Some("listening_prot_https")));
+    }
+
+    #[test]
+    fn table_header_is_used_as_the_key() {
+        let error = parse_error::<ProxyConfiguration>("proxy_rules = []\n[listening\n");
+        assert_eq!(location(&error), (Some(2), Some(11), Some("listening")));
+    }
+}
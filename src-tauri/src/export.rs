@@ -0,0 +1,869 @@
+use std::collections::HashSet;
+
+use crate::models::{ProxyConfiguration, ProxyRuleInner, RoutingLocation, RoutingMethod, RuleType};
+
+const NGINX_CERTIFICATE_DIR: &str = "/etc/nginx/certs";
+const HAPROXY_CERTIFICATE_DIR: &str = "/etc/haproxy/certs";
+const SYSTEM_CA_FILE: &str = "/etc/ssl/certs/ca-certificates.crt";
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Nginx,
+    HAProxy,
+    Caddy
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<ExportFormat> {
+        match name.to_lowercase().as_str() {
+            "nginx" => Some(ExportFormat::Nginx),
+            "haproxy" => Some(ExportFormat::HAProxy),
+            "caddy" | "caddyfile" => Some(ExportFormat::Caddy),
+            _ => None
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Nginx => "nginx",
+            ExportFormat::HAProxy => "HAProxy",
+            ExportFormat::Caddy => "Caddy"
+        }
+    }
+}
+
+// Something the exported configuration does differently from arc2proxy. `domain` is None
+// for settings that are not tied to one rule.
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportWarning {
+    pub domain: Option<String>,
+    pub message: String
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Export {
+    pub format: ExportFormat,
+    pub text: String,
+    pub warnings: Vec<ExportWarning>
+}
+
+struct Server {
+    // Bracketed when it is an IPv6 address, so `host:port` is always valid.
+    host: String,
+    port: u16,
+    backup: bool
+}
+
+// Where one proxy rule forwards to, in the order the servers should be tried.
+struct Backend<'a> {
+    rule: &'a ProxyRuleInner,
+    // Identifier safe to use as an nginx upstream or HAProxy backend name.
+    name: String,
+    // None for rules with a single forward target.
+    method: Option<RoutingMethod>,
+    https: bool,
+    servers: Vec<Server>,
+    // Health check path and interval in seconds.
+    health_check: Option<(String, u32)>
+}
+
+struct Warnings(Vec<ExportWarning>);
+
+impl Warnings {
+    fn global(&mut self, message: impl Into<String>) {
+        self.0.push(ExportWarning { domain: None, message: message.into() });
+    }
+
+    fn rule(&mut self, rule: &ProxyRuleInner, message: impl Into<String>) {
+        self.0.push(ExportWarning { domain: Some(rule.domain.clone()), message: message.into() });
+    }
+}
+
+fn host_of(forward_addr: &Option<String>, forward_ipv4: &Option<String>, forward_ipv6: &Option<String>) -> Option<String> {
+    forward_addr
+        .clone()
+        .or_else(|| forward_ipv4.clone())
+        .or_else(|| forward_ipv6.as_ref().map(|ipv6| format!("[{}]", ipv6)))
+}
+
+fn backend_name(domain: &str) -> String {
+    domain.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
+
+// Domains like a-b.com and a_b.com map to the same name, later ones get a numeric suffix.
+fn make_names_unique(backends: &mut [Backend]) {
+    let mut taken = HashSet::new();
+
+    for backend in backends {
+        let base = backend.name.clone();
+        let mut suffix = 2;
+        while !taken.insert(backend.name.clone()) {
+            backend.name = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+    }
+}
+
+// Priority locations are tried primary first, then by ascending priority.
+fn location_order(location: &RoutingLocation) -> (bool, u16) {
+    (location.primary != Some(true), location.priority.unwrap_or(u16::MAX))
+}
+
+fn backend_of<'a>(config: &ProxyConfiguration, rule: &'a ProxyRuleInner, warnings: &mut Warnings) -> Option<Backend<'a>> {
+    let mut backend = Backend {
+        rule,
+        name: backend_name(&rule.domain),
+        method: None,
+        https: false,
+        servers: Vec::new(),
+        health_check: None
+    };
+
+    match &rule.routing_rules {
+        Some(routing_rule) => {
+            let mut locations: Vec<(usize, &RoutingLocation)> = routing_rule.routing_locations.iter().enumerate().collect();
+            if routing_rule.routing_method == RoutingMethod::Priority {
+                locations.sort_by_key(|(_, location)| location_order(location));
+            }
+
+            for (index, location) in locations {
+                let host = host_of(&location.forward_addr, &location.forward_ipv4, &location.forward_ipv6);
+                let port = if routing_rule.https_only { location.forward_port_https } else { location.forward_port_http };
+
+                match (host, port) {
+                    (Some(host), Some(port)) => backend.servers.push(Server {
+                        host,
+                        port,
+                        backup: routing_rule.routing_method == RoutingMethod::Priority && !backend.servers.is_empty()
+                    }),
+                    _ => warnings.rule(rule, format!("Routing location #{} has no address or port and is left out.", index + 1))
+                }
+            }
+
+            // RoutingMethod derefs to itself, so `.clone()` never resolves on it.
+            backend.method = Some(Clone::clone(&routing_rule.routing_method));
+            backend.https = routing_rule.https_only;
+            backend.health_check = routing_rule.enable_health_checks.then(|| {
+                (routing_rule.health_check_path.clone().unwrap_or_else(|| "/".into()), routing_rule.health_check_interval)
+            });
+        }
+        None => {
+            let Some(host) = host_of(&rule.forward_addr, &rule.forward_ipv4, &rule.forward_ipv6) else {
+                warnings.rule(rule, "The rule has no forward address and is left out.");
+                return None;
+            };
+
+            let (https, port) = match (rule.forward_port_http, rule.forward_port_https) {
+                (Some(http), Some(_)) => {
+                    warnings.rule(rule, format!("HTTPS requests are forwarded to the HTTP port {} as well.", http));
+                    (false, http)
+                }
+                (Some(http), None) => (false, http),
+                (None, Some(https)) => (true, https),
+                // arc2proxy forwards to its own listening ports, the export only has one port.
+                (None, None) => {
+                    let http = config.listening_port_http.unwrap_or(80);
+                    warnings.rule(rule, format!("No forward port is set, requests are forwarded to the HTTP listening port {}.", http));
+                    (false, http)
+                }
+            };
+
+            backend.https = https;
+            backend.servers.push(Server { host, port, backup: false });
+        }
+    }
+
+    if backend.servers.is_empty() {
+        warnings.rule(rule, "The rule has no usable routing locations and is left out.");
+        return None;
+    }
+
+    Some(backend)
+}
+
+// Everything arc2proxy does per rule that the fallback proxies are not given.
+fn warn_unexported(rule: &ProxyRuleInner, format: ExportFormat, warnings: &mut Warnings) {
+    let name = format.name();
+
+    if let Some(path_rules) = rule.path_rules.as_ref().filter(|path_rules| !path_rules.is_empty()) {
+        warnings.rule(rule, format!("{} path rule(s) are not exported, every path is forwarded.", path_rules.len()));
+    }
+
+    if rule.paths.as_ref().is_some_and(|paths| !paths.is_empty()) {
+        warnings.rule(rule, "The paths list is not exported, every path is forwarded.");
+    } else if rule.rule_type == RuleType::Blacklist {
+        warnings.rule(rule, "The rule blocks every path, the export forwards them instead.");
+    }
+
+    if rule.disallowed_user_agents.as_ref().is_some_and(|agents| !agents.is_empty()) {
+        warnings.rule(rule, "Disallowed user agents are not exported.");
+    }
+
+    if rule.max_age_seconds > 0 {
+        warnings.rule(rule, format!("Responses are not cached by {}.", name));
+    }
+
+    if rule.enable_sql_injection_protection {
+        warnings.rule(rule, format!("SQL injection protection has no {} equivalent.", name));
+    }
+
+    if rule.enable_compression && rule.compression_flags.as_ref().is_some_and(|flags| !flags.is_empty()) {
+        warnings.rule(rule, format!("Compression flags are not exported, {} compresses with gzip.", name));
+    }
+
+    if rule.enable_minification {
+        warnings.rule(rule, format!("Minification has no {} equivalent.", name));
+    }
+
+    if rule.enable_webp_transformation {
+        warnings.rule(rule, format!("WebP transformation has no {} equivalent.", name));
+    }
+
+    if format == ExportFormat::HAProxy && !rule.enable_logging {
+        warnings.rule(rule, "HAProxy logs every request of the frontend, logging cannot be turned off for this rule.");
+    }
+}
+
+fn listen_address(config: &ProxyConfiguration) -> String {
+    let address = config.listening_address.clone().unwrap_or_else(|| "0.0.0.0".into());
+    if address.contains(':') { format!("[{}]", address) } else { address }
+}
+
+fn nginx(config: &ProxyConfiguration, backends: &[Backend], warnings: &mut Warnings) -> Vec<String> {
+    let address = listen_address(config);
+    let timeout = config.proxy_timeout.unwrap_or(45);
+    let mut lines = vec![format!(
+        "# Certificates are expected in {}/<domain>.crt and {}/<domain>.key.",
+        NGINX_CERTIFICATE_DIR, NGINX_CERTIFICATE_DIR
+    )];
+
+    warnings.global(format!("Certificates are not exported, put them into {} before switching over.", NGINX_CERTIFICATE_DIR));
+
+    for backend in backends {
+        let rule = backend.rule;
+        let scheme = if backend.https { "https" } else { "http" };
+
+        let target = match &backend.method {
+            Some(method) => {
+                lines.push(String::new());
+                lines.push(format!("upstream {} {{", backend.name));
+
+                match method {
+                    RoutingMethod::Performance => {
+                        warnings.rule(rule, "Performance routing is approximated with least_conn.");
+                        lines.push("    least_conn;".into());
+                    }
+                    RoutingMethod::Priority if backend.servers.iter().filter(|server| server.backup).count() > 1 => {
+                        warnings.rule(rule, "nginx spreads requests over all backup servers, their priorities are lost.");
+                    }
+                    _ => {}
+                }
+
+                let passive_check = match &backend.health_check {
+                    Some((path, interval)) => {
+                        warnings.rule(
+                            rule,
+                            format!("nginx has no active health checks, failed requests take a server out for {}s instead of checking {}.", interval, path)
+                        );
+                        format!(" max_fails=3 fail_timeout={}s", interval)
+                    }
+                    None => String::new()
+                };
+
+                for server in &backend.servers {
+                    let backup = if server.backup { " backup" } else { "" };
+                    lines.push(format!("    server {}:{}{}{};", server.host, server.port, passive_check, backup));
+                }
+                lines.push("}".into());
+
+                format!("{}://{}", scheme, backend.name)
+            }
+            None => format!("{}://{}:{}", scheme, backend.servers[0].host, backend.servers[0].port)
+        };
+
+        lines.push(String::new());
+        lines.push("server {".into());
+        lines.push(format!("    listen {}:{};", address, config.listening_port_http.unwrap_or(80)));
+        lines.push(format!("    listen {}:{} ssl;", address, config.listening_port_https.unwrap_or(443)));
+        lines.push(format!("    server_name {};", rule.domain));
+        lines.push(format!("    ssl_certificate {}/{}.crt;", NGINX_CERTIFICATE_DIR, rule.domain));
+        lines.push(format!("    ssl_certificate_key {}/{}.key;", NGINX_CERTIFICATE_DIR, rule.domain));
+        if rule.enable_compression {
+            lines.push("    gzip on;".into());
+        }
+        if !rule.enable_logging {
+            lines.push("    access_log off;".into());
+        }
+        lines.push(String::new());
+        lines.push("    location / {".into());
+        lines.push(format!("        proxy_pass {};", target));
+        lines.push("        proxy_set_header Host $host;".into());
+        lines.push("        proxy_set_header X-Forwarded-For $proxy_add_x_forwarded_for;".into());
+        lines.push("        proxy_set_header X-Forwarded-Proto $scheme;".into());
+        lines.push(format!("        proxy_read_timeout {}s;", timeout));
+        if backend.https {
+            lines.push("        proxy_ssl_server_name on;".into());
+            lines.push("        proxy_ssl_name $host;".into());
+            lines.push("        proxy_ssl_verify on;".into());
+            lines.push(format!("        proxy_ssl_trusted_certificate {};", SYSTEM_CA_FILE));
+        }
+        lines.push("    }".into());
+        lines.push("}".into());
+    }
+
+    lines
+}
+
+fn haproxy(config: &ProxyConfiguration, backends: &[Backend], warnings: &mut Warnings) -> Vec<String> {
+    let address = listen_address(config);
+    let timeout = config.proxy_timeout.unwrap_or(45);
+
+    warnings.global(format!(
+        "Certificates are not exported, put them into {} as PEM files with the key appended before switching over.",
+        HAPROXY_CERTIFICATE_DIR
+    ));
+
+    let mut lines = vec![
+        format!("# Certificates are expected in {} as PEM files with the key appended.", HAPROXY_CERTIFICATE_DIR),
+        String::new(),
+        "defaults".into(),
+        "    mode http".into(),
+        "    option httplog".into(),
+        "    option forwardfor".into(),
+        "    timeout connect 5s".into(),
+        format!("    timeout client {}s", timeout),
+        format!("    timeout server {}s", timeout),
+        String::new(),
+        "frontend arc2proxy".into(),
+        format!("    bind {}:{}", address, config.listening_port_http.unwrap_or(80)),
+        format!("    bind {}:{} ssl crt {}/", address, config.listening_port_https.unwrap_or(443), HAPROXY_CERTIFICATE_DIR),
+        "    http-request set-header X-Forwarded-Proto https if { ssl_fc }".into(),
+    ];
+
+    for backend in backends {
+        lines.push(format!("    use_backend {} if {{ hdr(host),field(1,:) -i {} }}", backend.name, backend.rule.domain));
+    }
+
+    for backend in backends {
+        let rule = backend.rule;
+
+        lines.push(String::new());
+        lines.push(format!("backend {}", backend.name));
+
+        // Backup servers are tried one at a time in the order they are listed, which is
+        // the priority order.
+        match backend.method {
+            Some(RoutingMethod::Performance) => {
+                warnings.rule(rule, "Performance routing is approximated with leastconn.");
+                lines.push("    balance leastconn".into());
+            }
+            Some(RoutingMethod::Weighted) => lines.push("    balance roundrobin".into()),
+            _ => {}
+        }
+
+        if rule.enable_compression {
+            lines.push("    compression algo gzip".into());
+        }
+
+        let check = match &backend.health_check {
+            Some((path, interval)) => {
+                lines.push(format!("    option httpchk GET {}", path));
+                lines.push(format!("    http-check send hdr Host {}", rule.domain));
+                format!(" check inter {}s", interval)
+            }
+            None => String::new()
+        };
+
+        let tls = if backend.https {
+            format!(" ssl verify required ca-file {} sni str({}){}", SYSTEM_CA_FILE, rule.domain, if check.is_empty() { String::new() } else { format!(" check-sni {}", rule.domain) })
+        } else {
+            String::new()
+        };
+
+        for (index, server) in backend.servers.iter().enumerate() {
+            let backup = if server.backup { " backup" } else { "" };
+            lines.push(format!("    server s{} {}:{}{}{}{}", index + 1, server.host, server.port, tls, check, backup));
+        }
+    }
+
+    lines
+}
+
+fn caddy(config: &ProxyConfiguration, backends: &[Backend], warnings: &mut Warnings) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut global = Vec::new();
+
+    if let Some(port) = config.listening_port_http.filter(|port| *port != 80) {
+        global.push(format!("    http_port {}", port));
+    }
+    if let Some(port) = config.listening_port_https.filter(|port| *port != 443) {
+        global.push(format!("    https_port {}", port));
+    }
+    if let Some(address) = config.listening_address.as_ref().filter(|address| address.as_str() != "0.0.0.0") {
+        global.push(format!("    default_bind {}", address));
+    }
+    if let Some(email) = &config.lets_encrypt_contact_email {
+        global.push(format!("    email {}", email));
+    }
+    if let Some(timeout) = config.proxy_timeout {
+        warnings.global(format!("Caddy waits for backends without a time limit, proxy_timeout ({}s) is not exported.", timeout));
+    }
+
+    if !global.is_empty() {
+        lines.push("{".into());
+        lines.append(&mut global);
+        lines.push("}".into());
+    }
+
+    for backend in backends {
+        let rule = backend.rule;
+        let scheme = if backend.https { "https://" } else { "" };
+        let upstreams: Vec<String> = backend.servers.iter().map(|server| format!("{}{}:{}", scheme, server.host, server.port)).collect();
+
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.push(format!("{} {{", rule.domain));
+        if rule.enable_compression {
+            lines.push("    encode gzip".into());
+        }
+        if rule.enable_logging {
+            lines.push("    log".into());
+        }
+
+        let mut options = Vec::new();
+        match backend.method {
+            Some(RoutingMethod::Priority) => options.push("lb_policy first".to_string()),
+            Some(RoutingMethod::Weighted) => options.push("lb_policy round_robin".into()),
+            Some(RoutingMethod::Performance) => {
+                warnings.rule(rule, "Performance routing is approximated with least_conn.");
+                options.push("lb_policy least_conn".into());
+            }
+            None => {}
+        }
+        if let Some((path, interval)) = &backend.health_check {
+            options.push(format!("health_uri {}", path));
+            options.push(format!("health_interval {}s", interval));
+        }
+
+        if options.is_empty() && !backend.https {
+            lines.push(format!("    reverse_proxy {}", upstreams.join(" ")));
+        } else {
+            lines.push(format!("    reverse_proxy {} {{", upstreams.join(" ")));
+            for option in options {
+                lines.push(format!("        {}", option));
+            }
+            if backend.https {
+                lines.push("        transport http {".into());
+                lines.push(format!("            tls_server_name {}", rule.domain));
+                lines.push("        }".into());
+            }
+            lines.push("    }".into());
+        }
+        lines.push("}".into());
+    }
+
+    lines
+}
+
+// Renders the proxy rules for another reverse proxy to take over when arc2proxy is down.
+// Only forwarding is carried over: domains, targets, routing and health checks.
+pub fn export(config: &ProxyConfiguration, format: ExportFormat) -> Export {
+    let mut warnings = Warnings(Vec::new());
+
+    let mut backends: Vec<Backend> = config
+        .proxy_rules
+        .iter()
+        .filter_map(|rule| {
+            let backend = backend_of(config, rule, &mut warnings)?;
+            warn_unexported(rule, format, &mut warnings);
+            Some(backend)
+        })
+        .collect();
+    make_names_unique(&mut backends);
+
+    if config.add_rate_limiting.unwrap_or(true) {
+        warnings.global(format!("Rate limiting is not exported to {}.", format.name()));
+    }
+
+    if config.add_sql_injection_protection.unwrap_or(false) {
+        warnings.global(format!("SQL injection protection has no {} equivalent.", format.name()));
+    }
+
+    let lines = match format {
+        ExportFormat::Nginx => nginx(config, &backends, &mut warnings),
+        ExportFormat::HAProxy => haproxy(config, &backends, &mut warnings),
+        ExportFormat::Caddy => caddy(config, &backends, &mut warnings)
+    };
+
+    let mut text = lines.join("\n");
+    text.push('\n');
+
+    // General warnings first, then the ones of each rule in rule order.
+    let mut warnings = warnings.0;
+    warnings.sort_by_key(|warning| {
+        warning
+            .domain
+            .as_ref()
+            .and_then(|domain| config.proxy_rules.iter().position(|rule| &rule.domain == domain))
+    });
+
+    Export { format, text, warnings }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{MatchType, PathRule, UserAgentRule};
+
+    // A plain rule, an https_only Priority routing rule with health checks and a rule forwarding to HTTPS.
+    const CONFIG: &str = r#"
+listening_port_http = 8080
+add_rate_limiting = false
+
+[[proxy_rules]]
+domain = "plain.com"
+max_age_seconds = 0
+rule_type = "Whitelist"
+enable_logging = true
+ignore_query_string = false
+enable_sql_injection_protection = false
+enable_compression = true
+enable_minification = false
+enable_webp_transformation = false
+forward_ipv4 = "127.0.0.1"
+forward_port_http = 3000
+
+[[proxy_rules]]
+domain = "routed.com"
+max_age_seconds = 0
+rule_type = "Whitelist"
+enable_logging = true
+ignore_query_string = false
+enable_sql_injection_protection = false
+enable_compression = false
+enable_minification = false
+enable_webp_transformation = false
+
+[proxy_rules.routing_rules]
+routing_method = "Priority"
+https_only = true
+enable_health_checks = true
+health_check_interval = 10
+health_check_path = "/health"
+
+[[proxy_rules.routing_rules.routing_locations]]
+priority = 1
+forward_ipv6 = "fd00::2"
+forward_port_https = 8443
+
+[[proxy_rules.routing_rules.routing_locations]]
+primary = true
+forward_addr = "app.internal"
+forward_port_https = 8443
+
+[[proxy_rules.routing_rules.routing_locations]]
+priority = 2
+forward_ipv4 = "10.0.0.3"
+forward_port_https = 8443
+
+[[proxy_rules]]
+domain = "secure.com"
+max_age_seconds = 0
+rule_type = "Whitelist"
+enable_logging = true
+ignore_query_string = false
+enable_sql_injection_protection = false
+enable_compression = false
+enable_minification = false
+enable_webp_transformation = false
+forward_addr = "backend.internal"
+forward_port_https = 8443
+"#;
+
+    const NGINX: &str = r#"# Certificates are expected in /etc/nginx/certs/<domain>.crt and /etc/nginx/certs/<domain>.key.
+
+server {
+    listen 0.0.0.0:8080;
+    listen 0.0.0.0:443 ssl;
+    server_name plain.com;
+    ssl_certificate /etc/nginx/certs/plain.com.crt;
+    ssl_certificate_key /etc/nginx/certs/plain.com.key;
+    gzip on;
+
+    location / {
+        proxy_pass http://127.0.0.1:3000;
+        proxy_set_header Host $host;
+        proxy_set_header X-Forwarded-For $proxy_add_x_forwarded_for;
+        proxy_set_header X-Forwarded-Proto $scheme;
+        proxy_read_timeout 45s;
+    }
+}
+
+upstream routed_com {
+    server app.internal:8443 max_fails=3 fail_timeout=10s;
+    server [fd00::2]:8443 max_fails=3 fail_timeout=10s backup;
+    server 10.0.0.3:8443 max_fails=3 fail_timeout=10s backup;
+}
+
+server {
+    listen 0.0.0.0:8080;
+    listen 0.0.0.0:443 ssl;
+    server_name routed.com;
+    ssl_certificate /etc/nginx/certs/routed.com.crt;
+    ssl_certificate_key /etc/nginx/certs/routed.com.key;
+
+    location / {
+        proxy_pass https://routed_com;
+        proxy_set_header Host $host;
+        proxy_set_header X-Forwarded-For $proxy_add_x_forwarded_for;
+        proxy_set_header X-Forwarded-Proto $scheme;
+        proxy_read_timeout 45s;
+        proxy_ssl_server_name on;
+        proxy_ssl_name $host;
+        proxy_ssl_verify on;
+        proxy_ssl_trusted_certificate /etc/ssl/certs/ca-certificates.crt;
+    }
+}
+
+server {
+    listen 0.0.0.0:8080;
+    listen 0.0.0.0:443 ssl;
+    server_name secure.com;
+    ssl_certificate /etc/nginx/certs/secure.com.crt;
+    ssl_certificate_key /etc/nginx/certs/secure.com.key;
+
+    location / {
+        proxy_pass https://backend.internal:8443;
+        proxy_set_header Host $host;
+        proxy_set_header X-Forwarded-For $proxy_add_x_forwarded_for;
+        proxy_set_header X-Forwarded-Proto $scheme;
+        proxy_read_timeout 45s;
+        proxy_ssl_server_name on;
+        proxy_ssl_name $host;
+        proxy_ssl_verify on;
+        proxy_ssl_trusted_certificate /etc/ssl/certs/ca-certificates.crt;
+    }
+}
+"#;
+
+    const HAPROXY: &str = r#"# Certificates are expected in /etc/haproxy/certs as PEM files with the key appended.
+
+defaults
+    mode http
+    option httplog
+    option forwardfor
+    timeout connect 5s
+    timeout client 45s
+    timeout server 45s
+
+frontend arc2proxy
+    bind 0.0.0.0:8080
+    bind 0.0.0.0:443 ssl crt /etc/haproxy/certs/
+    http-request set-header X-Forwarded-Proto https if { ssl_fc }
+    use_backend plain_com if { hdr(host),field(1,:) -i plain.com }
+    use_backend routed_com if { hdr(host),field(1,:) -i routed.com }
+    use_backend secure_com if { hdr(host),field(1,:) -i secure.com }
+
+backend plain_com
+    compression algo gzip
+    server s1 127.0.0.1:3000
+
+backend routed_com
+    option httpchk GET /health
+    http-check send hdr Host routed.com
+    server s1 app.internal:8443 ssl verify required ca-file /etc/ssl/certs/ca-certificates.crt sni str(routed.com) check-sni routed.com check inter 10s
+    server s2 [fd00::2]:8443 ssl verify required ca-file /etc/ssl/certs/ca-certificates.crt sni str(routed.com) check-sni routed.com check inter 10s backup
+    server s3 10.0.0.3:8443 ssl verify required ca-file /etc/ssl/certs/ca-certificates.crt sni str(routed.com) check-sni routed.com check inter 10s backup
+
+backend secure_com
+    server s1 backend.internal:8443 ssl verify required ca-file /etc/ssl/certs/ca-certificates.crt sni str(secure.com)
+"#;
+
+    const CADDY: &str = r#"{
+    http_port 8080
+}
+
+plain.com {
+    encode gzip
+    log
+    reverse_proxy 127.0.0.1:3000
+}
+
+routed.com {
+    log
+    reverse_proxy https://app.internal:8443 https://[fd00::2]:8443 https://10.0.0.3:8443 {
+        lb_policy first
+        health_uri /health
+        health_interval 10s
+        transport http {
+            tls_server_name routed.com
+        }
+    }
+}
+
+secure.com {
+    log
+    reverse_proxy https://backend.internal:8443 {
+        transport http {
+            tls_server_name secure.com
+        }
+    }
+}
+"#;
+
+    fn export_of(format: ExportFormat) -> Export {
+        export(&toml::from_str(CONFIG).unwrap(), format)
+    }
+
+    fn messages(export: &Export) -> Vec<(Option<&str>, &str)> {
+        export.warnings.iter().map(|warning| (warning.domain.as_deref(), warning.message.as_str())).collect()
+    }
+
+    #[test]
+    fn exports_nginx() {
+        let export = export_of(ExportFormat::Nginx);
+        assert_eq!(export.text, NGINX);
+        assert_eq!(messages(&export), vec![
+            (None, "Certificates are not exported, put them into /etc/nginx/certs before switching over."),
+            (Some("routed.com"), "nginx spreads requests over all backup servers, their priorities are lost."),
+            (Some("routed.com"), "nginx has no active health checks, failed requests take a server out for 10s instead of checking /health."),
+        ]);
+    }
+
+    #[test]
+    fn exports_haproxy() {
+        let export = export_of(ExportFormat::HAProxy);
+        assert_eq!(export.text, HAPROXY);
+        assert_eq!(messages(&export), vec![(None, "Certificates are not exported, put them into /etc/haproxy/certs as PEM files with the key appended before switching over.")]);
+    }
+
+    #[test]
+    fn exports_caddy() {
+        let export = export_of(ExportFormat::Caddy);
+        assert_eq!(export.text, CADDY);
+        assert_eq!(messages(&export), Vec::new());
+    }
+
+    // One rule per warning, based on plain.com so each differs by the setting that is warned about.
+    fn warning_config() -> ProxyConfiguration {
+        let mut config: ProxyConfiguration = toml::from_str(CONFIG).unwrap();
+        config.proxy_timeout = Some(30);
+        config.add_rate_limiting = None;
+        config.add_sql_injection_protection = Some(true);
+
+        let plain = config.proxy_rules[0].clone();
+        let routed = config.proxy_rules[1].clone();
+        let rule = |domain: &str, edit: &dyn Fn(&mut ProxyRuleInner)| {
+            let mut rule = ProxyRuleInner { domain: domain.into(), ..plain.clone() };
+            edit(&mut rule);
+            rule
+        };
+
+        config.proxy_rules = vec![
+            rule("features.com", &|rule| {
+                rule.max_age_seconds = 60;
+                rule.enable_logging = false;
+                rule.enable_sql_injection_protection = true;
+                rule.compression_flags = Some("gzip=6".into());
+                rule.enable_minification = true;
+                rule.enable_webp_transformation = true;
+                rule.path_rules = Some(vec![PathRule {
+                    max_age_seconds: 0,
+                    path: "/admin".into(),
+                    match_type: MatchType::StartsWith,
+                    rule_type: RuleType::Blacklist
+                }]);
+                rule.disallowed_user_agents = Some(vec![UserAgentRule { user_agent: "bot".into(), match_type: MatchType::Contains }]);
+            }),
+            rule("paths.com", &|rule| rule.paths = Some(vec!["/api".into()])),
+            rule("blocked.com", &|rule| rule.rule_type = RuleType::Blacklist),
+            rule("no-address.com", &|rule| rule.forward_ipv4 = None),
+            rule("no-port.com", &|rule| rule.forward_port_http = None),
+            rule("both-ports.com", &|rule| rule.forward_port_https = Some(3443)),
+            ProxyRuleInner { domain: "performance.com".into(), ..routed.clone() },
+            ProxyRuleInner { domain: "unusable.com".into(), ..routed },
+        ];
+
+        let routing_rule = config.proxy_rules[6].routing_rules.as_mut().unwrap();
+        routing_rule.routing_method = RoutingMethod::Performance;
+        routing_rule.enable_health_checks = false;
+        routing_rule.routing_locations[2].forward_port_https = None;
+
+        for location in &mut config.proxy_rules[7].routing_rules.as_mut().unwrap().routing_locations {
+            location.forward_port_https = None;
+        }
+
+        config
+    }
+
+    #[test]
+    fn warns_about_everything_that_is_not_exported() {
+        let export = export(&warning_config(), ExportFormat::Nginx);
+
+        assert_eq!(messages(&export), vec![
+            (None, "Rate limiting is not exported to nginx."),
+            (None, "SQL injection protection has no nginx equivalent."),
+            (None, "Certificates are not exported, put them into /etc/nginx/certs before switching over."),
+            (Some("features.com"), "1 path rule(s) are not exported, every path is forwarded."),
+            (Some("features.com"), "Disallowed user agents are not exported."),
+            (Some("features.com"), "Responses are not cached by nginx."),
+            (Some("features.com"), "SQL injection protection has no nginx equivalent."),
+            (Some("features.com"), "Compression flags are not exported, nginx compresses with gzip."),
+            (Some("features.com"), "Minification has no nginx equivalent."),
+            (Some("features.com"), "WebP transformation has no nginx equivalent."),
+            (Some("paths.com"), "The paths list is not exported, every path is forwarded."),
+            (Some("blocked.com"), "The rule blocks every path, the export forwards them instead."),
+            (Some("no-address.com"), "The rule has no forward address and is left out."),
+            (Some("no-port.com"), "No forward port is set, requests are forwarded to the HTTP listening port 8080."),
+            (Some("both-ports.com"), "HTTPS requests are forwarded to the HTTP port 3000 as well."),
+            (Some("performance.com"), "Routing location #3 has no address or port and is left out."),
+            (Some("performance.com"), "Performance routing is approximated with least_conn."),
+            (Some("unusable.com"), "Routing location #2 has no address or port and is left out."),
+            (Some("unusable.com"), "Routing location #1 has no address or port and is left out."),
+            (Some("unusable.com"), "Routing location #3 has no address or port and is left out."),
+            (Some("unusable.com"), "The rule has no usable routing locations and is left out."),
+        ]);
+
+        for domain in ["no-address.com", "unusable.com"] {
+            assert!(!export.text.contains(domain), "{} should be left out", domain);
+        }
+        assert!(export.text.contains("upstream performance_com {\n    least_conn;\n    server [fd00::2]:8443;\n    server app.internal:8443;\n}"));
+        assert!(export.text.contains("server_name both-ports.com;"));
+        assert!(export.text.contains("server_name no-port.com;"));
+        assert!(export.text.contains("proxy_pass http://127.0.0.1:8080;"));
+        assert!(!export.text.contains(":3443"));
+    }
+
+    #[test]
+    fn backend_names_are_unique() {
+        let mut config: ProxyConfiguration = toml::from_str(CONFIG).unwrap();
+        config.proxy_rules.truncate(1);
+        for domain in ["plain-com", "plain_com", "plain.com.2"] {
+            config.proxy_rules.push(ProxyRuleInner { domain: domain.into(), ..config.proxy_rules[0].clone() });
+        }
+
+        let export = export(&config, ExportFormat::HAProxy);
+        let backends: Vec<&str> = export.text.lines().filter_map(|line| line.strip_prefix("backend ")).collect();
+        assert_eq!(backends, vec!["plain_com", "plain_com_2", "plain_com_3", "plain_com_2_2"]);
+        assert!(export.text.contains("use_backend plain_com_3 if { hdr(host),field(1,:) -i plain_com }"));
+    }
+
+    #[test]
+    fn warns_about_what_each_format_does_differently() {
+        let haproxy = export(&warning_config(), ExportFormat::HAProxy);
+        let haproxy = messages(&haproxy);
+        assert!(haproxy.contains(&(Some("features.com"), "HAProxy logs every request of the frontend, logging cannot be turned off for this rule.")));
+        assert!(haproxy.contains(&(Some("performance.com"), "Performance routing is approximated with leastconn.")));
+        assert!(haproxy.contains(&(Some("features.com"), "Minification has no HAProxy equivalent.")));
+
+        let caddy = export(&warning_config(), ExportFormat::Caddy);
+        let caddy = messages(&caddy);
+        assert!(caddy.contains(&(None, "Caddy waits for backends without a time limit, proxy_timeout (30s) is not exported.")));
+        assert!(caddy.contains(&(Some("performance.com"), "Performance routing is approximated with least_conn.")));
+        assert!(!caddy.iter().any(|(_, message)| message.starts_with("Certificates are not exported")));
+    }
+}
@@ -0,0 +1,340 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    error::ConfigError,
+    models::ProxyConfiguration,
+    rules,
+};
+
+// The string form is a comma separated list, e.g. `gzip=6,brotli,mime=text/html,mime=text/css`
+// for compression and `html,css,js` for minification. Formatting writes the same form back.
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    Gzip,
+    Brotli,
+    Zstd
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 3] = [Self::Gzip, Self::Brotli, Self::Zstd];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Brotli => "brotli",
+            Self::Zstd => "zstd"
+        }
+    }
+
+    pub fn levels(self) -> (u8, u8) {
+        match self {
+            Self::Gzip => (1, 9),
+            Self::Brotli => (0, 11),
+            Self::Zstd => (1, 22)
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlgorithmFlag {
+    pub algorithm: Algorithm,
+    // None leaves the level to the proxy.
+    pub level: Option<u8>
+}
+
+// Algorithms are kept in the order they are listed, the MIME types limit what gets compressed.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompressionFlags {
+    pub algorithms: Vec<AlgorithmFlag>,
+    pub mime_types: Vec<String>
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MinificationFlags {
+    pub html: bool,
+    pub css: bool,
+    pub js: bool
+}
+
+// What the GUI offers as checkboxes. Other MIME types can still be typed in.
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlagOptions {
+    pub algorithms: Vec<AlgorithmOption>,
+    pub mime_types: &'static [&'static str],
+    pub minification: &'static [&'static str]
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlgorithmOption {
+    pub name: &'static str,
+    pub min_level: u8,
+    pub max_level: u8
+}
+
+// The compression and minification settings of one proxy rule.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleFlags {
+    pub enable_compression: bool,
+    pub compression: CompressionFlags,
+    pub enable_minification: bool,
+    pub minification: MinificationFlags
+}
+
+pub const COMMON_MIME_TYPES: [&str; 8] = [
+    "text/html",
+    "text/css",
+    "text/plain",
+    "text/javascript",
+    "application/javascript",
+    "application/json",
+    "application/xml",
+    "image/svg+xml",
+];
+
+pub const MINIFICATION_TARGETS: [&str; 3] = ["html", "css", "js"];
+
+pub fn options() -> FlagOptions {
+    FlagOptions {
+        algorithms: Algorithm::ALL
+            .iter()
+            .map(|algorithm| {
+                let (min_level, max_level) = algorithm.levels();
+                AlgorithmOption { name: algorithm.as_str(), min_level, max_level }
+            })
+            .collect(),
+        mime_types: &COMMON_MIME_TYPES,
+        minification: &MINIFICATION_TARGETS
+    }
+}
+
+fn tokens(raw: &str) -> impl Iterator<Item = &str> {
+    raw.split(',').map(str::trim).filter(|token| !token.is_empty())
+}
+
+// `type/subtype` with the token characters of RFC 6838, the subtype may be `*`.
+fn is_mime_type(value: &str) -> bool {
+    let is_token = |part: &str| {
+        !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || "!#$&^_.+-".contains(c))
+    };
+
+    match value.split_once('/') {
+        Some((kind, subtype)) => is_token(kind) && (subtype == "*" || is_token(subtype)),
+        None => false
+    }
+}
+
+fn parse_level(algorithm: Algorithm, raw: &str) -> Result<u8, String> {
+    let (min, max) = algorithm.levels();
+    raw.parse::<u8>()
+        .ok()
+        .filter(|level| (min..=max).contains(level))
+        .ok_or_else(|| format!("{} level must be a number between {} and {}, not \"{}\".", algorithm.as_str(), min, max, raw))
+}
+
+impl CompressionFlags {
+    pub fn is_empty(&self) -> bool {
+        self.algorithms.is_empty() && self.mime_types.is_empty()
+    }
+}
+
+impl FromStr for CompressionFlags {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let mut flags = CompressionFlags::default();
+
+        for token in tokens(raw) {
+            let (name, value) = match token.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim())),
+                None => (token, None)
+            };
+
+            if name == "mime" {
+                let mime_type = value.unwrap_or_default().to_ascii_lowercase();
+                if !is_mime_type(&mime_type) {
+                    return Err(format!("\"{}\" is not a MIME type like text/html.", value.unwrap_or_default()));
+                }
+                if flags.mime_types.contains(&mime_type) {
+                    return Err(format!("MIME type {} is listed twice.", mime_type));
+                }
+                flags.mime_types.push(mime_type);
+                continue;
+            }
+
+            let algorithm = Algorithm::ALL
+                .into_iter()
+                .find(|algorithm| algorithm.as_str() == name)
+                .ok_or_else(|| format!("Unknown compression flag \"{}\", expected gzip, brotli, zstd or mime=TYPE.", token))?;
+
+            if flags.algorithms.iter().any(|flag| flag.algorithm == algorithm) {
+                return Err(format!("{} is listed twice.", algorithm.as_str()));
+            }
+
+            let level = value.map(|value| parse_level(algorithm, value)).transpose()?;
+            flags.algorithms.push(AlgorithmFlag { algorithm, level });
+        }
+
+        Ok(flags)
+    }
+}
+
+impl fmt::Display for CompressionFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let algorithms = self.algorithms.iter().map(|flag| match flag.level {
+            Some(level) => format!("{}={}", flag.algorithm.as_str(), level),
+            None => flag.algorithm.as_str().to_string()
+        });
+        let mime_types = self.mime_types.iter().map(|mime_type| format!("mime={}", mime_type));
+
+        f.write_str(&algorithms.chain(mime_types).collect::<Vec<_>>().join(","))
+    }
+}
+
+impl MinificationFlags {
+    pub fn is_empty(&self) -> bool {
+        !(self.html || self.css || self.js)
+    }
+}
+
+impl FromStr for MinificationFlags {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let mut flags = MinificationFlags::default();
+
+        for token in tokens(raw) {
+            let target = match token {
+                "html" => &mut flags.html,
+                "css" => &mut flags.css,
+                "js" => &mut flags.js,
+                _ => return Err(format!("Unknown minification flag \"{}\", expected html, css or js.", token))
+            };
+
+            if *target {
+                return Err(format!("{} is listed twice.", token));
+            }
+            *target = true;
+        }
+
+        Ok(flags)
+    }
+}
+
+impl fmt::Display for MinificationFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let enabled = [self.html, self.css, self.js];
+        let targets: Vec<_> = MINIFICATION_TARGETS
+            .iter()
+            .zip(enabled)
+            .filter_map(|(target, enabled)| enabled.then_some(*target))
+            .collect();
+
+        f.write_str(&targets.join(","))
+    }
+}
+
+// Formats the flags and parses them again, so flags built by the GUI get the same checks as
+// flags read from the file. Empty flags clear the setting.
+pub fn format_compression(flags: &CompressionFlags, field: &str) -> Result<Option<String>, ConfigError> {
+    let text = flags.to_string();
+    text.parse::<CompressionFlags>().map_err(|e| ConfigError::validation(field, e))?;
+    Ok((!flags.is_empty()).then_some(text))
+}
+
+fn format_minification(flags: &MinificationFlags) -> Option<String> {
+    (!flags.is_empty()).then(|| flags.to_string())
+}
+
+pub fn parse_compression(raw: Option<&str>, field: &str) -> Result<CompressionFlags, ConfigError> {
+    raw.unwrap_or_default().parse().map_err(|e| ConfigError::validation(field, e))
+}
+
+pub fn rule_flags_of(config: &ProxyConfiguration, domain: &str) -> Result<RuleFlags, ConfigError> {
+    let index = rules::find_rule_index(config, domain)?;
+    let rule = &config.proxy_rules[index];
+    let prefix = format!("proxy_rules[{}]", index);
+
+    Ok(RuleFlags {
+        enable_compression: rule.enable_compression,
+        compression: parse_compression(rule.compression_flags.as_deref(), &format!("{}.compression_flags", prefix))?,
+        enable_minification: rule.enable_minification,
+        minification: rule
+            .minification_flags
+            .as_deref()
+            .unwrap_or_default()
+            .parse()
+            .map_err(|e| ConfigError::validation(&format!("{}.minification_flags", prefix), e))?
+    })
+}
+
+pub fn set_rule_flags(config: &mut ProxyConfiguration, domain: &str, flags: RuleFlags) -> Result<(), ConfigError> {
+    let index = rules::find_rule_index(config, domain)?;
+    let field = format!("proxy_rules[{}].compression_flags", index);
+
+    rules::edit_rule(config, domain, |rule| {
+        rule.enable_compression = flags.enable_compression;
+        rule.compression_flags = format_compression(&flags.compression, &field)?;
+        rule.enable_minification = flags.enable_minification;
+        rule.minification_flags = format_minification(&flags.minification);
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_compression_flags() {
+        let flags: CompressionFlags = " gzip=6, brotli ,mime=Text/HTML,mime=image/svg+xml,mime=text/*".parse().unwrap();
+        assert_eq!(flags.algorithms, vec![
+            AlgorithmFlag { algorithm: Algorithm::Gzip, level: Some(6) },
+            AlgorithmFlag { algorithm: Algorithm::Brotli, level: None },
+        ]);
+        assert_eq!(flags.mime_types, vec!["text/html", "image/svg+xml", "text/*"]);
+        assert_eq!(flags.to_string(), "gzip=6,brotli,mime=text/html,mime=image/svg+xml,mime=text/*");
+        assert_eq!(flags.to_string().parse::<CompressionFlags>(), Ok(flags));
+
+        assert!("".parse::<CompressionFlags>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_unknown_or_invalid_compression_flags() {
+        assert_eq!(
+            "gzip,deflate".parse::<CompressionFlags>(),
+            Err("Unknown compression flag \"deflate\", expected gzip, brotli, zstd or mime=TYPE.".to_string())
+        );
+        assert_eq!(
+            "gzip=10".parse::<CompressionFlags>(),
+            Err("gzip level must be a number between 1 and 9, not \"10\".".to_string())
+        );
+        assert!("zstd=22".parse::<CompressionFlags>().is_ok());
+        assert!("brotli=fast".parse::<CompressionFlags>().is_err());
+        assert!("gzip,gzip=5".parse::<CompressionFlags>().is_err());
+        assert!("mime=html".parse::<CompressionFlags>().is_err());
+        assert!("mime=text/html,mime=text/html".parse::<CompressionFlags>().is_err());
+    }
+
+    #[test]
+    fn round_trips_minification_flags() {
+        let flags: MinificationFlags = "js, html".parse().unwrap();
+        assert_eq!(flags, MinificationFlags { html: true, css: false, js: true });
+        assert_eq!(flags.to_string(), "html,js");
+        assert_eq!(flags.to_string().parse::<MinificationFlags>(), Ok(flags));
+
+        assert_eq!(
+            "html,svg".parse::<MinificationFlags>(),
+            Err("Unknown minification flag \"svg\", expected html, css or js.".to_string())
+        );
+        assert!("css,css".parse::<MinificationFlags>().is_err());
+    }
+}
@@ -1,171 +1,852 @@
-use std::{env, fs, io::{Seek, Write}, path::PathBuf};
+use std::{fs, path::{Path, PathBuf}, time::SystemTime};
 
-use models::ProxyConfiguration;
+use backups::{BackupInfo, DiffLine};
+use certificates::CertificateInventory;
+use error::ConfigError;
+use export::{Export, ExportFormat};
+use flags::{CompressionFlags, FlagOptions, RuleFlags};
+use location::{ConfigLocation, ConfigLocator};
+use logs::{LogFilter, LogTail};
+use models::{PathRule, ProxyConfiguration, ProxyRuleInner, RoutingLocation, RoutingRule};
+use nginx::NginxImport;
+use path_rules::PathRuleConflict;
+use preferences::Preferences;
+use probe::ProbeResult;
+use routing::RoutingOptions;
+use session::{EditSession, FieldChange, SessionState};
+use settings::Setting;
+use simulator::{SimulatedRequest, Simulation};
+use supervisor::{ProxyStatus, Supervisor};
+use tauri::{Emitter, Manager, State};
+use tls_policy::TlsCheck;
+use validation::ValidationReport;
 
+pub mod backups;
+pub mod certificates;
+pub mod cli;
+pub mod document;
+pub mod error;
+pub mod export;
+pub mod flags;
+pub mod location;
+pub mod logs;
 pub mod models;
+pub mod nginx;
+pub mod path_rules;
+pub mod preferences;
+pub mod probe;
+pub mod routing;
+pub mod rules;
+pub mod schema;
+pub mod secrets;
+pub mod session;
+pub mod settings;
+pub mod simulator;
+pub mod storage;
+pub mod supervisor;
+pub mod tls_policy;
+pub mod validation;
 
-fn _get_config_path() -> Option<PathBuf> {
-    let mut dir = env::current_exe().unwrap();
-    dir.pop();
-    dir.push("proxy_config.toml");
-    if dir.is_file() {
-        Some(dir)
+fn _read_configuration(path: &Path) -> Result<ProxyConfiguration, ConfigError> {
+    let toml_string = fs::read_to_string(path).map_err(|e| ConfigError::io(path, e))?;
+    toml::from_str::<ProxyConfiguration>(&toml_string).map_err(|e| ConfigError::parse(path, &toml_string, e))
+}
+
+fn _write_configuration(locator: &ConfigLocator, path: &Path, model: &ProxyConfiguration) -> Result<(), ConfigError> {
+    let original = fs::read_to_string(path).map_err(|e| ConfigError::io(path, e))?;
+    let new_string = document::render(&original, model)?;
+
+    if new_string == original {
+        return Ok(());
     }
-    else {
-        None
+
+    backups::snapshot(path, locator.preferences().load().max_backups())?;
+    storage::write_atomic(path, new_string.as_bytes()).map_err(|e| ConfigError::io(path, e))?;
+    _refresh_runtime_copy(locator, path, model)
+}
+
+// Shared by the GUI and the CLI, so a restore reaches a running proxy's copy either way.
+fn _restore_backup(locator: &ConfigLocator, id: &str) -> Result<PathBuf, ConfigError> {
+    let path_buf = locator.path()?;
+    backups::restore(&path_buf, id, locator.preferences().load().max_backups())?;
+    _refresh_runtime_copy(locator, &path_buf, &_read_configuration(&path_buf)?)?;
+    Ok(path_buf)
+}
+
+// A proxy started with resolved secrets reads a copy, which has to follow every write.
+fn _refresh_runtime_copy(locator: &ConfigLocator, path: &Path, model: &ProxyConfiguration) -> Result<(), ConfigError> {
+    match secrets::store(locator) {
+        Ok(store) => store.refresh_runtime_copy(path, model),
+        Err(_) => Ok(())
     }
 }
 
+// Checks the file against the schema before it is deserialized, so every value of the wrong
+// type is reported with its field instead of only the first one as a parse error.
+fn _validate_file(path: &Path) -> Result<ValidationReport, ConfigError> {
+    let toml_string = fs::read_to_string(path).map_err(|e| ConfigError::io(path, e))?;
+    let mut report = schema::check_toml(&toml_string).map_err(|e| ConfigError::parse(path, &toml_string, e))?;
+
+    if !report.has_errors() {
+        let model = toml::from_str::<ProxyConfiguration>(&toml_string).map_err(|e| ConfigError::parse(path, &toml_string, e))?;
+        report.extend_new_fields(model.validate());
+    }
+
+    Ok(report)
+}
+
+// Loads the configuration, applies `edit` and writes it back only when `edit` succeeds.
+fn _edit_configuration<F>(locator: &ConfigLocator, edit: F) -> Result<ProxyConfiguration, ConfigError>
+where
+    F: FnOnce(&mut ProxyConfiguration) -> Result<(), ConfigError>
+{
+    let path_buf = locator.path()?;
+    let mut model = _read_configuration(&path_buf)?;
+    edit(&mut model)?;
+    _write_configuration(locator, &path_buf, &model)?;
+    Ok(model)
+}
+
+// Rejects only errors the new value introduces, so a broken file can be fixed one value at a time.
+fn _save_value(locator: &ConfigLocator, setting_name: &str, setting_value: &str) -> Result<(), ConfigError> {
+    let path_buf = locator.path()?;
+    let mut model = _read_configuration(&path_buf)?;
+    let report_before = model.validate();
+
+    settings::set_value(&mut model, setting_name, setting_value)?;
+
+    let report = model.validate().new_errors_since(&report_before);
+    if report.has_errors() {
+        return Err(report.into());
+    }
+
+    _write_configuration(locator, &path_buf, &model)?;
+
+    Ok(())
+}
+
+fn _set_secret(locator: &ConfigLocator, setting_name: &str, setting_value: &str, encrypt: bool) -> Result<ProxyConfiguration, ConfigError> {
+    let store = secrets::store(locator)?;
+    _edit_configuration(locator, |model| secrets::set_secret(model, setting_name, setting_value, encrypt, &store))
+}
+
+fn _rotate_api_key(locator: &ConfigLocator) -> Result<(String, ProxyConfiguration), ConfigError> {
+    let store = secrets::store(locator)?;
+    let mut api_key = String::new();
+    let model = _edit_configuration(locator, |model| {
+        api_key = secrets::rotate_api_key(model, &store)?;
+        Ok(())
+    })?;
+    Ok((api_key, model))
+}
+
+// Secrets are written straight away instead of being staged. The edit session takes the new
+// values too, so they are not seen as someone else's change when it is committed.
+fn _sync_session_secrets(sessions: &SessionState, model: &ProxyConfiguration) {
+    let _ = sessions.with_session(|session| {
+        secrets::copy_secrets(model, &mut session.base);
+        secrets::copy_secrets(model, &mut session.staged);
+        Ok(())
+    });
+}
+
+#[tauri::command]
+fn get_configuration_location(locator: State<'_, ConfigLocator>) -> Result<ConfigLocation, ConfigError> {
+    locator.resolve()
+}
+
+#[tauri::command]
+fn get_configuration_candidates(locator: State<'_, ConfigLocator>) -> Vec<ConfigLocation> {
+    locator.candidates()
+}
+
 #[tauri::command]
-fn get_configuration() -> ProxyConfiguration {
-    if let Some(path_buf) = _get_config_path() {
-        let toml_string = fs::read_to_string(path_buf).unwrap();
-        let model = toml::from_str::<ProxyConfiguration>(&toml_string).unwrap();
-        model
-    } else {
-        panic!("proxy_config.toml File was not found.")
+fn open_configuration(locator: State<'_, ConfigLocator>, path: PathBuf, remember: bool) -> Result<ConfigLocation, ConfigError> {
+    _read_configuration(&path)?;
+    locator.select(path, remember)
+}
+
+#[tauri::command]
+fn forget_configuration(locator: State<'_, ConfigLocator>) -> Result<ConfigLocation, ConfigError> {
+    locator.forget()?;
+    locator.resolve()
+}
+
+#[tauri::command]
+fn get_configuration(locator: State<'_, ConfigLocator>) -> Result<ProxyConfiguration, ConfigError> {
+    let path_buf = locator.path()?;
+    _read_configuration(&path_buf).map(|model| secrets::mask(&model))
+}
+
+#[tauri::command]
+fn validate_configuration(
+    locator: State<'_, ConfigLocator>,
+    configuration: Option<ProxyConfiguration>
+) -> Result<ValidationReport, ConfigError> {
+    match configuration {
+        Some(configuration) => Ok(configuration.validate()),
+        None => _validate_file(&locator.path()?)
     }
 }
 
 #[tauri::command]
-fn save_value(setting_name: &str, setting_value: &str) -> bool {
-    if let Some(path_buf) = _get_config_path() {
-        let toml_string = fs::read_to_string(path_buf).unwrap();
-        let mut model = toml::from_str::<ProxyConfiguration>(&toml_string).unwrap();
-
-        if setting_name == "listening_address" {
-            model.listening_address = {
-                if setting_value.len() == 0 {
-                    None
-                } else {
-                    Some(setting_value.into())
-                }
-            };
-        } else if setting_name == "listening_port_http" {
-            model.listening_port_http = {
-                if setting_value.len() == 0 {
-                    None
-                } else {
-                    Some(setting_value.parse::<u16>().unwrap())
-                }
-            };
-        } else if setting_name == "listening_port_https" {
-            model.listening_port_https = {
-                if setting_value.len() == 0 {
-                    None
-                } else {
-                    Some(setting_value.parse::<u16>().unwrap())
-                }
-            };
-        } else if setting_name == "logging_level" {
-            model.logging_level = {
-                if setting_value.len() == 0 {
-                    None
-                } else {
-                    Some(setting_value.into())
-                }
-            };
-        } else if setting_name == "add_caching" {
-            model.add_caching = {
-                if setting_value.len() == 0 {
-                    None
-                } else {
-                    Some(setting_value.parse::<bool>().unwrap())
-                }
-            };
-        }  else if setting_name == "add_rate_limiting" {
-            model.add_rate_limiting = {
-                if setting_value.len() == 0 {
-                    None
-                } else {
-                    Some(setting_value.parse::<bool>().unwrap())
-                }
-            };
-        } else if setting_name == "add_logging" {
-            model.add_logging = {
-                if setting_value.len() == 0 {
-                    None
-                } else {
-                    Some(setting_value.parse::<bool>().unwrap())
-                }
-            };
-        } else if setting_name == "disable_default_body_limit" {
-            model.disable_default_body_limit = {
-                if setting_value.len() == 0 {
-                    None
-                } else {
-                    Some(setting_value.parse::<bool>().unwrap())
-                }
-            };
-        } else if setting_name == "add_sql_injection_protection" {
-            model.add_sql_injection_protection = {
-                if setting_value.len() == 0 {
-                    None
-                } else {
-                    Some(setting_value.parse::<bool>().unwrap())
-                }
-            };
-        } else if setting_name == "recv_buffer_size" {
-            model.recv_buffer_size = {
-                if setting_value.len() == 0 {
-                    None
-                } else {
-                    Some(setting_value.parse::<usize>().unwrap())
-                }
-            };
-        } else if setting_name == "send_buffer_size" {
-            model.send_buffer_size = {
-                if setting_value.len() == 0 {
-                    None
-                } else {
-                    Some(setting_value.parse::<usize>().unwrap())
-                }
-            };
-        } else if setting_name == "ip_ttl" {
-            model.ip_ttl = {
-                if setting_value.len() == 0 {
-                    None
-                } else {
-                    Some(setting_value.parse::<u32>().unwrap())
-                }
-            };
-        } else if setting_name == "tcp_keep_alive_seconds" {
-            model.tcp_keep_alive_seconds = {
-                if setting_value.len() == 0 {
-                    None
-                } else {
-                    Some(setting_value.parse::<u64>().unwrap())
-                }
-            };
-        } else if setting_name == "max_backlog" {
-            model.max_backlog = {
-                if setting_value.len() == 0 {
-                    None
-                } else {
-                    Some(setting_value.parse::<i32>().unwrap())
-                }
-            };
-        }
+fn get_configuration_schema() -> serde_json::Value {
+    schema::schema().clone()
+}
+
+#[tauri::command]
+fn get_settings() -> Vec<Setting> {
+    settings::SETTINGS.to_vec()
+}
+
+#[tauri::command]
+fn save_value(locator: State<'_, ConfigLocator>, setting_name: &str, setting_value: &str) -> Result<(), ConfigError> {
+    _save_value(&locator, setting_name, setting_value)
+}
+
+#[tauri::command]
+fn set_secret(
+    locator: State<'_, ConfigLocator>,
+    sessions: State<'_, SessionState>,
+    setting_name: &str,
+    setting_value: &str,
+    encrypt: bool
+) -> Result<ProxyConfiguration, ConfigError> {
+    let model = _set_secret(&locator, setting_name, setting_value, encrypt)?;
+    _sync_session_secrets(&sessions, &model);
+    Ok(secrets::mask(&model))
+}
+
+// The new key is returned once so it can be handed to the API clients.
+#[tauri::command]
+fn rotate_api_key(locator: State<'_, ConfigLocator>, sessions: State<'_, SessionState>) -> Result<String, ConfigError> {
+    let (api_key, model) = _rotate_api_key(&locator)?;
+    _sync_session_secrets(&sessions, &model);
+    Ok(api_key)
+}
+
+#[tauri::command]
+fn add_proxy_rule(locator: State<'_, ConfigLocator>, rule: ProxyRuleInner) -> Result<Vec<ProxyRuleInner>, ConfigError> {
+    let model = _edit_configuration(&locator, |model| rules::add_rule(model, rule))?;
+    Ok(model.proxy_rules)
+}
+
+#[tauri::command]
+fn update_proxy_rule(locator: State<'_, ConfigLocator>, domain: &str, rule: ProxyRuleInner) -> Result<Vec<ProxyRuleInner>, ConfigError> {
+    let model = _edit_configuration(&locator, |model| rules::update_rule(model, domain, rule))?;
+    Ok(model.proxy_rules)
+}
+
+#[tauri::command]
+fn delete_proxy_rule(locator: State<'_, ConfigLocator>, domain: &str) -> Result<Vec<ProxyRuleInner>, ConfigError> {
+    let model = _edit_configuration(&locator, |model| rules::delete_rule(model, domain).map(|_| ()))?;
+    Ok(model.proxy_rules)
+}
+
+#[tauri::command]
+fn duplicate_proxy_rule(locator: State<'_, ConfigLocator>, domain: &str, new_domain: &str) -> Result<Vec<ProxyRuleInner>, ConfigError> {
+    let model = _edit_configuration(&locator, |model| rules::duplicate_rule(model, domain, new_domain))?;
+    Ok(model.proxy_rules)
+}
+
+#[tauri::command]
+fn reorder_proxy_rules(locator: State<'_, ConfigLocator>, domains: Vec<String>) -> Result<Vec<ProxyRuleInner>, ConfigError> {
+    let model = _edit_configuration(&locator, |model| rules::reorder_rules(model, &domains))?;
+    Ok(model.proxy_rules)
+}
+
+#[tauri::command]
+fn import_nginx(text: &str) -> Result<NginxImport, ConfigError> {
+    nginx::import(text)
+}
+
+// Adds several rules in one write, none of them are added when one is rejected.
+#[tauri::command]
+fn add_proxy_rules(locator: State<'_, ConfigLocator>, rules: Vec<ProxyRuleInner>) -> Result<Vec<ProxyRuleInner>, ConfigError> {
+    let model = _edit_configuration(&locator, |model| rules.into_iter().try_for_each(|rule| rules::add_rule(model, rule)))?;
+    Ok(model.proxy_rules)
+}
+
+#[tauri::command]
+fn export_configuration(locator: State<'_, ConfigLocator>, format: ExportFormat) -> Result<Export, ConfigError> {
+    let model = _read_configuration(&locator.path()?)?;
+    Ok(export::export(&model, format))
+}
+
+#[tauri::command]
+fn begin_session(locator: State<'_, ConfigLocator>, sessions: State<'_, SessionState>) -> Result<ProxyConfiguration, ConfigError> {
+    let path_buf = locator.path()?;
+    let model = _read_configuration(&path_buf)?;
+    let masked = secrets::mask(&model);
+    sessions.set(EditSession::new(path_buf, model));
+    Ok(masked)
+}
+
+#[tauri::command]
+fn stage_value(sessions: State<'_, SessionState>, setting_name: &str, setting_value: &str) -> Result<Vec<FieldChange>, ConfigError> {
+    sessions.with_session(|session| {
+        settings::set_value(&mut session.staged, setting_name, setting_value)?;
+        session.changes().map(secrets::mask_changes)
+    })
+}
+
+#[tauri::command]
+fn stage_configuration(sessions: State<'_, SessionState>, mut configuration: ProxyConfiguration) -> Result<Vec<FieldChange>, ConfigError> {
+    sessions.with_session(|session| {
+        secrets::unmask(&mut configuration, &session.staged);
+        session.staged = configuration;
+        session.changes().map(secrets::mask_changes)
+    })
+}
+
+#[tauri::command]
+fn preview_changes(sessions: State<'_, SessionState>) -> Result<Vec<FieldChange>, ConfigError> {
+    sessions.with_session(|session| session.changes().map(secrets::mask_changes))
+}
+
+#[tauri::command]
+fn commit_changes(locator: State<'_, ConfigLocator>, sessions: State<'_, SessionState>) -> Result<ProxyConfiguration, ConfigError> {
+    let session = sessions.get().ok_or_else(|| ConfigError::validation("session", "No edit session is active."))?;
+
+    session.check_unchanged(&_read_configuration(&session.path)?)?;
+
+    let report = session.staged.validate().new_errors_since(&session.base.validate());
+    if report.has_errors() {
+        return Err(report.into());
+    }
+
+    _write_configuration(&locator, &session.path, &session.staged)?;
+    sessions.take();
+    Ok(secrets::mask(&session.staged))
+}
+
+#[tauri::command]
+fn discard_changes(sessions: State<'_, SessionState>) -> Option<ProxyConfiguration> {
+    sessions.take().map(|session| secrets::mask(&session.base))
+}
+
+#[tauri::command]
+fn get_preferences(locator: State<'_, ConfigLocator>) -> Preferences {
+    locator.preferences().load()
+}
+
+#[tauri::command]
+fn set_max_backups(locator: State<'_, ConfigLocator>, max_backups: usize) -> Result<Preferences, ConfigError> {
+    let preferences = locator.preferences().update(|preferences| preferences.max_backups = Some(max_backups))?;
+    backups::prune(&locator.path()?, max_backups)?;
+    Ok(preferences)
+}
+
+#[tauri::command]
+fn list_backups(locator: State<'_, ConfigLocator>) -> Result<Vec<BackupInfo>, ConfigError> {
+    backups::list(&locator.path()?)
+}
+
+#[tauri::command]
+fn diff_backup(locator: State<'_, ConfigLocator>, id: &str) -> Result<Vec<DiffLine>, ConfigError> {
+    backups::diff(&locator.path()?, id).map(secrets::mask_diff)
+}
+
+#[tauri::command]
+fn restore_backup(locator: State<'_, ConfigLocator>, id: &str) -> Result<Vec<BackupInfo>, ConfigError> {
+    backups::list(&_restore_backup(&locator, id)?)
+}
+
+#[tauri::command]
+fn get_path_rules(locator: State<'_, ConfigLocator>, domain: &str) -> Result<Vec<PathRule>, ConfigError> {
+    let model = _read_configuration(&locator.path()?)?;
+    Ok(path_rules::path_rules_of(&model, domain)?.to_vec())
+}
+
+#[tauri::command]
+fn add_path_rule(locator: State<'_, ConfigLocator>, domain: &str, path_rule: PathRule) -> Result<Vec<PathRule>, ConfigError> {
+    let model = _edit_configuration(&locator, |model| path_rules::add_path_rule(model, domain, path_rule))?;
+    Ok(path_rules::path_rules_of(&model, domain)?.to_vec())
+}
+
+#[tauri::command]
+fn update_path_rule(locator: State<'_, ConfigLocator>, domain: &str, index: usize, path_rule: PathRule) -> Result<Vec<PathRule>, ConfigError> {
+    let model = _edit_configuration(&locator, |model| path_rules::update_path_rule(model, domain, index, path_rule))?;
+    Ok(path_rules::path_rules_of(&model, domain)?.to_vec())
+}
+
+#[tauri::command]
+fn delete_path_rule(locator: State<'_, ConfigLocator>, domain: &str, index: usize) -> Result<Vec<PathRule>, ConfigError> {
+    let model = _edit_configuration(&locator, |model| path_rules::delete_path_rule(model, domain, index).map(|_| ()))?;
+    Ok(path_rules::path_rules_of(&model, domain)?.to_vec())
+}
+
+#[tauri::command]
+fn reorder_path_rules(locator: State<'_, ConfigLocator>, domain: &str, order: Vec<usize>) -> Result<Vec<PathRule>, ConfigError> {
+    let model = _edit_configuration(&locator, |model| path_rules::reorder_path_rules(model, domain, &order))?;
+    Ok(path_rules::path_rules_of(&model, domain)?.to_vec())
+}
+
+#[tauri::command]
+fn analyze_path_rules(locator: State<'_, ConfigLocator>, domain: &str) -> Result<Vec<PathRuleConflict>, ConfigError> {
+    let model = _read_configuration(&locator.path()?)?;
+    Ok(path_rules::analyze(path_rules::path_rules_of(&model, domain)?))
+}
+
+#[tauri::command]
+fn get_routing_rule(locator: State<'_, ConfigLocator>, domain: &str) -> Result<Option<RoutingRule>, ConfigError> {
+    let model = _read_configuration(&locator.path()?)?;
+    routing::routing_rule_of(&model, domain)
+}
+
+#[tauri::command]
+fn add_routing_location(locator: State<'_, ConfigLocator>, domain: &str, location: RoutingLocation) -> Result<Option<RoutingRule>, ConfigError> {
+    let model = _edit_configuration(&locator, |model| routing::add_location(model, domain, location))?;
+    routing::routing_rule_of(&model, domain)
+}
+
+#[tauri::command]
+fn update_routing_location(
+    locator: State<'_, ConfigLocator>,
+    domain: &str,
+    index: usize,
+    location: RoutingLocation
+) -> Result<Option<RoutingRule>, ConfigError> {
+    let model = _edit_configuration(&locator, |model| routing::update_location(model, domain, index, location))?;
+    routing::routing_rule_of(&model, domain)
+}
+
+#[tauri::command]
+fn delete_routing_location(locator: State<'_, ConfigLocator>, domain: &str, index: usize) -> Result<Option<RoutingRule>, ConfigError> {
+    let model = _edit_configuration(&locator, |model| routing::delete_location(model, domain, index).map(|_| ()))?;
+    routing::routing_rule_of(&model, domain)
+}
+
+#[tauri::command]
+fn reorder_routing_locations(locator: State<'_, ConfigLocator>, domain: &str, order: Vec<usize>) -> Result<Option<RoutingRule>, ConfigError> {
+    let model = _edit_configuration(&locator, |model| routing::reorder_locations(model, domain, &order))?;
+    routing::routing_rule_of(&model, domain)
+}
+
+#[tauri::command]
+fn update_routing_options(locator: State<'_, ConfigLocator>, domain: &str, options: RoutingOptions) -> Result<Option<RoutingRule>, ConfigError> {
+    let model = _edit_configuration(&locator, |model| routing::update_options(model, domain, options))?;
+    routing::routing_rule_of(&model, domain)
+}
+
+#[tauri::command]
+fn get_flag_options() -> FlagOptions {
+    flags::options()
+}
+
+#[tauri::command]
+fn parse_compression_flags(flags: Option<String>) -> Result<CompressionFlags, ConfigError> {
+    flags::parse_compression(flags.as_deref(), "compression_flags")
+}
+
+// The global flags are staged like any other setting, this only builds the string.
+#[tauri::command]
+fn format_compression_flags(flags: CompressionFlags) -> Result<Option<String>, ConfigError> {
+    flags::format_compression(&flags, "compression_flags")
+}
+
+#[tauri::command]
+fn get_rule_flags(locator: State<'_, ConfigLocator>, domain: &str) -> Result<RuleFlags, ConfigError> {
+    let model = _read_configuration(&locator.path()?)?;
+    flags::rule_flags_of(&model, domain)
+}
+
+#[tauri::command]
+fn set_rule_flags(locator: State<'_, ConfigLocator>, domain: &str, flags: RuleFlags) -> Result<RuleFlags, ConfigError> {
+    let model = _edit_configuration(&locator, |model| flags::set_rule_flags(model, domain, flags))?;
+    flags::rule_flags_of(&model, domain)
+}
+
+// Probing waits on the network, so it runs off the main thread.
+#[tauri::command]
+async fn probe_routing_locations(locator: State<'_, ConfigLocator>, domain: String) -> Result<Vec<ProbeResult>, ConfigError> {
+    let model = _read_configuration(&locator.path()?)?;
+    probe::probe_routing_locations(&model, &domain)
+}
+
+// Handshakes wait on the network, so they run off the main thread.
+#[tauri::command]
+async fn check_backend_tls(locator: State<'_, ConfigLocator>) -> Result<Vec<TlsCheck>, ConfigError> {
+    let model = _read_configuration(&locator.path()?)?;
+    Ok(tls_policy::check_backends(&model))
+}
+
+#[tauri::command]
+fn simulate_request(
+    locator: State<'_, ConfigLocator>,
+    request: SimulatedRequest,
+    configuration: Option<ProxyConfiguration>
+) -> Result<Simulation, ConfigError> {
+    let model = match configuration {
+        Some(configuration) => configuration,
+        None => _read_configuration(&locator.path()?)?
+    };
+
+    Ok(simulator::simulate(&model, &request))
+}
+
+// A directory chosen in the preferences wins over the ones searched next to the configuration.
+fn _certificate_dir(locator: &ConfigLocator, path: &Path) -> Option<PathBuf> {
+    locator.preferences().load().certificate_dir.or_else(|| certificates::find_certificate_dir(path))
+}
+
+fn _certificate_inventory(locator: &ConfigLocator, warning_days: Option<u32>) -> Result<CertificateInventory, ConfigError> {
+    let path_buf = locator.path()?;
+    let model = _read_configuration(&path_buf)?;
+    let directory = _certificate_dir(locator, &path_buf);
+
+    Ok(certificates::inventory(
+        &model,
+        directory.as_deref(),
+        warning_days.unwrap_or(certificates::DEFAULT_WARNING_DAYS),
+        SystemTime::now()
+    ))
+}
+
+// The files are only written once the certificate checks out, and the rule is marked as using
+// a manual certificate so the proxy does not request one from Let's Encrypt.
+fn _import_certificate(locator: &ConfigLocator, domain: &str, chain: &str, private_key: &str) -> Result<PathBuf, ConfigError> {
+    let path_buf = locator.path()?;
+    rules::find_rule_index(&_read_configuration(&path_buf)?, domain)?;
+    certificates::check_manual_certificate(domain, chain, private_key, SystemTime::now())?;
+
+    let directory = _certificate_dir(locator, &path_buf).unwrap_or_else(|| certificates::default_certificate_dir(&path_buf));
+    let domain_dir = certificates::install_manual_certificate(&directory, domain, chain, private_key)?;
+
+    // Files of a rule that is not marked would be taken for Let's Encrypt's, so they go again.
+    let marked = _edit_configuration(locator, |model| rules::edit_rule(model, domain, |rule| {
+        rule.manual_certificate = Some(true);
+        Ok(())
+    }));
+    if let Err(e) = marked {
+        let _ = certificates::remove_manual_certificate(&directory, domain);
+        return Err(e);
+    }
+    Ok(domain_dir)
+}
+
+// Hands the domain back to Let's Encrypt. The files are only deleted when they were imported,
+// otherwise they are the ones Let's Encrypt issued.
+fn _remove_certificate(locator: &ConfigLocator, domain: &str) -> Result<(), ConfigError> {
+    let path_buf = locator.path()?;
+    let mut was_manual = false;
+    _edit_configuration(locator, |model| rules::edit_rule(model, domain, |rule| {
+        was_manual = rule.manual_certificate == Some(true);
+        rule.manual_certificate = None;
+        Ok(())
+    }))?;
+
+    match _certificate_dir(locator, &path_buf) {
+        Some(directory) if was_manual => certificates::remove_manual_certificate(&directory, domain),
+        _ => Ok(())
+    }
+}
+
+#[tauri::command]
+async fn import_certificate(
+    locator: State<'_, ConfigLocator>,
+    domain: String,
+    chain: String,
+    private_key: String
+) -> Result<CertificateInventory, ConfigError> {
+    _import_certificate(&locator, &domain, &chain, &private_key)?;
+    _certificate_inventory(&locator, None)
+}
+
+#[tauri::command]
+async fn remove_certificate(locator: State<'_, ConfigLocator>, domain: String) -> Result<CertificateInventory, ConfigError> {
+    _remove_certificate(&locator, &domain)?;
+    _certificate_inventory(&locator, None)
+}
 
-        let new_string = toml::to_string(&model).unwrap();
+// Reading every certificate touches the disk, so it runs off the main thread.
+#[tauri::command]
+async fn get_certificates(locator: State<'_, ConfigLocator>, warning_days: Option<u32>) -> Result<CertificateInventory, ConfigError> {
+    _certificate_inventory(&locator, warning_days)
+}
 
-        let mut toml_file = fs::OpenOptions::new().write(true).truncate(true).open(_get_config_path().unwrap()).unwrap();
+#[tauri::command]
+fn set_certificate_dir(locator: State<'_, ConfigLocator>, path: Option<PathBuf>) -> Result<Preferences, ConfigError> {
+    locator.preferences().update(|preferences| preferences.certificate_dir = path)
+}
 
-        toml_file.set_len(0).unwrap();
-        toml_file.rewind().unwrap();
-        toml_file.write_all(new_string.as_bytes()).expect("Failed to write");
+struct ProxyLaunch {
+    binary: PathBuf,
+    args: Vec<String>,
+    working_dir: PathBuf
+}
+
+// The proxy binary lives next to the configuration and runs from that directory. When secrets
+// are references or encrypted, it runs from the private runtime directory instead, with a copy
+// of the configuration that has them resolved, so they never end up next to the original.
+// Either way the file is passed with --config, so the proxy never has to search for it.
+fn _proxy_launch(locator: &ConfigLocator) -> Result<ProxyLaunch, ConfigError> {
+    let path_buf = locator.path()?;
+    let binary = supervisor::find_proxy_binary(&path_buf)?;
+    let model = _read_configuration(&path_buf)?;
 
-        true
-    } else {
-        false
+    let launch = |config_path: PathBuf, working_dir: PathBuf| ProxyLaunch {
+        binary: binary.clone(),
+        args: vec![location::CONFIG_ARG.to_string(), config_path.display().to_string()],
+        working_dir
+    };
+
+    if secrets::needs_resolving(&model) {
+        let store = secrets::store(locator)?;
+        store.write_runtime_copy(&path_buf, &model)?;
+        return Ok(launch(store.runtime_config_path(), store.runtime_dir()));
     }
+
+    if let Ok(store) = secrets::store(locator) {
+        store.remove_runtime_copy();
+    }
+
+    let working_dir = path_buf.parent().map(Path::to_path_buf).unwrap_or_default();
+    Ok(launch(path_buf, working_dir))
+}
+
+#[tauri::command]
+fn get_proxy_status(supervisor: State<'_, Supervisor>) -> ProxyStatus {
+    supervisor.status()
+}
+
+#[tauri::command]
+fn start_proxy(locator: State<'_, ConfigLocator>, supervisor: State<'_, Supervisor>) -> Result<ProxyStatus, ConfigError> {
+    let launch = _proxy_launch(&locator)?;
+    supervisor.start(&launch.binary, &launch.args, &launch.working_dir)
+}
+
+// Stopping waits for the process to exit, so these run off the main thread.
+#[tauri::command]
+async fn stop_proxy(locator: State<'_, ConfigLocator>, supervisor: State<'_, Supervisor>) -> Result<ProxyStatus, ConfigError> {
+    let status = supervisor.stop(supervisor::DEFAULT_STOP_TIMEOUT)?;
+    if let Ok(store) = secrets::store(&locator) {
+        store.remove_runtime_copy();
+    }
+    Ok(status)
+}
+
+#[tauri::command]
+async fn restart_proxy(locator: State<'_, ConfigLocator>, supervisor: State<'_, Supervisor>) -> Result<ProxyStatus, ConfigError> {
+    let launch = _proxy_launch(&locator)?;
+    supervisor.restart(&launch.binary, &launch.args, &launch.working_dir, supervisor::DEFAULT_STOP_TIMEOUT)
+}
+
+#[tauri::command]
+fn set_log_filter(logs: State<'_, LogTail>, filter: LogFilter) -> Result<(), ConfigError> {
+    logs.set_filter(&filter)
+}
+
+#[tauri::command]
+fn ack_log_batch(logs: State<'_, LogTail>, sequence: u64) {
+    logs.acknowledge(sequence);
+}
+
+#[tauri::command]
+fn get_log_file(logs: State<'_, LogTail>) -> Option<PathBuf> {
+    logs.file()
+}
+
+#[tauri::command]
+fn tail_log_file(logs: State<'_, LogTail>, path: PathBuf) -> Result<(), ConfigError> {
+    logs.tail_file(&path)
+}
+
+#[tauri::command]
+fn stop_log_file(logs: State<'_, LogTail>) {
+    logs.stop_file();
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
-        .invoke_handler(tauri::generate_handler![get_configuration, save_value])
+        .setup(|app| {
+            let preferences_path = app.path().app_config_dir().ok().map(|dir| dir.join(preferences::PREFERENCES_FILE_NAME));
+            app.manage(ConfigLocator::from_env(preferences_path));
+            app.manage(SessionState::default());
+
+            let logs = LogTail::default();
+            let handle = app.handle().clone();
+            logs.run_emitter(move |batch| {
+                let _ = handle.emit(logs::LOG_BATCH_EVENT, batch.clone());
+            });
+
+            let handle = app.handle().clone();
+            app.manage(Supervisor::new(move |status| {
+                let _ = handle.emit(supervisor::PROXY_STATUS_EVENT, status.clone());
+            }, logs.clone()));
+            app.manage(logs);
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            get_configuration_location,
+            get_configuration_candidates,
+            open_configuration,
+            forget_configuration,
+            get_configuration,
+            validate_configuration,
+            get_configuration_schema,
+            get_settings,
+            save_value,
+            set_secret,
+            rotate_api_key,
+            add_proxy_rule,
+            update_proxy_rule,
+            delete_proxy_rule,
+            duplicate_proxy_rule,
+            reorder_proxy_rules,
+            import_nginx,
+            add_proxy_rules,
+            export_configuration,
+            get_path_rules,
+            add_path_rule,
+            update_path_rule,
+            delete_path_rule,
+            reorder_path_rules,
+            analyze_path_rules,
+            get_routing_rule,
+            add_routing_location,
+            update_routing_location,
+            delete_routing_location,
+            reorder_routing_locations,
+            update_routing_options,
+            get_flag_options,
+            parse_compression_flags,
+            format_compression_flags,
+            get_rule_flags,
+            set_rule_flags,
+            probe_routing_locations,
+            check_backend_tls,
+            simulate_request,
+            get_certificates,
+            set_certificate_dir,
+            import_certificate,
+            remove_certificate,
+            begin_session,
+            stage_value,
+            stage_configuration,
+            preview_changes,
+            commit_changes,
+            discard_changes,
+            get_preferences,
+            set_max_backups,
+            list_backups,
+            diff_backup,
+            restore_backup,
+            get_proxy_status,
+            start_proxy,
+            stop_proxy,
+            restart_proxy,
+            set_log_filter,
+            ack_log_batch,
+            get_log_file,
+            tail_log_file,
+            stop_log_file
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+
+    const CONFIG: &str = r#"[[proxy_rules]]
+domain = "example.com"
+max_age_seconds = 0
+rule_type = "Whitelist"
+enable_logging = false
+ignore_query_string = false
+enable_sql_injection_protection = false
+enable_compression = false
+enable_minification = false
+enable_webp_transformation = false
+"#;
+
+    #[test]
+    fn removes_only_imported_certificate_files() {
+        let dir = std::env::temp_dir().join(format!("arc2proxy-gui-lib-remove-certificate-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let domain_dir = dir.join(certificates::CERTIFICATE_DIR_NAMES[0]).join("example.com");
+        fs::create_dir_all(&domain_dir).unwrap();
+        for file_name in [certificates::CHAIN_FILE_NAME, certificates::KEY_FILE_NAME] {
+            fs::write(domain_dir.join(file_name), "issued by Let's Encrypt").unwrap();
+        }
+
+        let path = dir.join(location::CONFIG_FILE_NAME);
+        fs::write(&path, CONFIG).unwrap();
+        let locator = ConfigLocator::new(Some(path.clone()), None);
+
+        _remove_certificate(&locator, "example.com").unwrap();
+        assert!(domain_dir.join(certificates::CHAIN_FILE_NAME).is_file());
+        assert!(domain_dir.join(certificates::KEY_FILE_NAME).is_file());
+
+        fs::write(&path, format!("{}manual_certificate = true\n", CONFIG)).unwrap();
+        _remove_certificate(&locator, "example.com").unwrap();
+        assert!(!domain_dir.exists());
+        assert_eq!(_read_configuration(&path).unwrap().proxy_rules[0].manual_certificate, None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn restore_refreshes_the_runtime_copy() {
+        let dir = std::env::temp_dir().join(format!("arc2proxy-gui-lib-restore-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join(location::CONFIG_FILE_NAME);
+        fs::write(&path, format!("listening_port_http = 8080\n{}", CONFIG)).unwrap();
+        let backup = backups::snapshot(&path, 3).unwrap().unwrap();
+        fs::write(&path, format!("listening_port_http = 9090\n{}", CONFIG)).unwrap();
+
+        let locator = ConfigLocator::new(Some(path.clone()), Some(dir.join("app").join(preferences::PREFERENCES_FILE_NAME)));
+        let store = secrets::store(&locator).unwrap();
+        store.write_runtime_copy(&path, &_read_configuration(&path).unwrap()).unwrap();
+
+        assert_eq!(_restore_backup(&locator, &backup.id).unwrap(), path);
+        let runtime = fs::read_to_string(store.runtime_config_path()).unwrap();
+        assert!(runtime.contains("listening_port_http = 8080"), "{}", runtime);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn proxy_is_given_the_private_runtime_copy() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("arc2proxy-gui-lib-launch-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(supervisor::PROXY_BINARY_NAMES[0]), "").unwrap();
+        fs::write(dir.join("api_key.txt"), "hunter2\n").unwrap();
+
+        let path = dir.join(location::CONFIG_FILE_NAME);
+        fs::write(&path, CONFIG).unwrap();
+        let locator = ConfigLocator::new(Some(path.clone()), Some(dir.join("app").join(preferences::PREFERENCES_FILE_NAME)));
+        let store = secrets::store(&locator).unwrap();
+
+        let launch = _proxy_launch(&locator).unwrap();
+        assert_eq!(launch.binary, dir.join(supervisor::PROXY_BINARY_NAMES[0]));
+        assert_eq!(launch.args, vec![location::CONFIG_ARG.to_string(), path.display().to_string()]);
+        assert_eq!(launch.working_dir, dir);
+
+        fs::write(&path, format!("api_key = \"file:{}\"\n{}", dir.join("api_key.txt").display(), CONFIG)).unwrap();
+        let launch = _proxy_launch(&locator).unwrap();
+        let runtime_path = store.runtime_config_path();
+        assert_eq!(launch.args, vec![location::CONFIG_ARG.to_string(), runtime_path.display().to_string()]);
+        assert_eq!(launch.working_dir, store.runtime_dir());
+        assert!(fs::read_to_string(&runtime_path).unwrap().contains("api_key = \"hunter2\""));
+        assert_eq!(fs::metadata(&runtime_path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        fs::write(&path, CONFIG).unwrap();
+        _proxy_launch(&locator).unwrap();
+        assert!(!runtime_path.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,356 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{error::ConfigError, preferences::PreferenceStore};
+
+pub const CONFIG_FILE_NAME: &str = "proxy_config.toml";
+pub const CONFIG_ENV_VAR: &str = "ARC2PROXY_CONFIG";
+pub const CONFIG_ARG: &str = "--config";
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+    Selected,
+    CommandLine,
+    Environment,
+    Preference,
+    ExeDirectory,
+    UserConfigDirectory,
+    SystemDirectory
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigLocation {
+    pub path: PathBuf,
+    pub source: ConfigSource,
+    pub exists: bool
+}
+
+impl ConfigLocation {
+    fn new(path: PathBuf, source: ConfigSource) -> Self {
+        let exists = path.is_file();
+        ConfigLocation { path, source, exists }
+    }
+}
+
+type EnvLookup = Box<dyn Fn(&str) -> Option<OsString> + Send + Sync>;
+
+// Decides which proxy_config.toml the GUI works on. A file opened at runtime wins,
+// then --config, then ARC2PROXY_CONFIG, then the saved preference, and finally the
+// first existing file in the search list.
+pub struct ConfigLocator {
+    cli_path: Option<PathBuf>,
+    preferences: PreferenceStore,
+    selected: Mutex<Option<PathBuf>>,
+    env: EnvLookup,
+    exe_path: Option<PathBuf>
+}
+
+impl ConfigLocator {
+    pub fn new(cli_path: Option<PathBuf>, preferences_path: Option<PathBuf>) -> Self {
+        ConfigLocator {
+            cli_path,
+            preferences: PreferenceStore::new(preferences_path),
+            selected: Mutex::new(None),
+            env: Box::new(|name| env::var_os(name)),
+            exe_path: env::current_exe().ok()
+        }
+    }
+
+    // Replaces the process environment and executable path, so tests do not depend on
+    // the machine they run on.
+    #[cfg(test)]
+    fn with_lookups<F>(mut self, env: F, exe_path: Option<PathBuf>) -> Self
+    where
+        F: Fn(&str) -> Option<OsString> + Send + Sync + 'static
+    {
+        self.env = Box::new(env);
+        self.exe_path = exe_path;
+        self
+    }
+
+    pub fn preferences(&self) -> &PreferenceStore {
+        &self.preferences
+    }
+
+    pub fn from_env(preferences_path: Option<PathBuf>) -> Self {
+        ConfigLocator::new(config_path_from_args(env::args().skip(1)), preferences_path)
+    }
+
+    // Explicitly configured locations, most important first.
+    fn explicit(&self) -> Vec<ConfigLocation> {
+        let mut explicit = Vec::new();
+
+        if let Some(path) = self.selected.lock().unwrap().clone() {
+            explicit.push(ConfigLocation::new(path, ConfigSource::Selected));
+        }
+        if let Some(path) = self.cli_path.clone() {
+            explicit.push(ConfigLocation::new(path, ConfigSource::CommandLine));
+        }
+        if let Some(path) = (self.env)(CONFIG_ENV_VAR).filter(|value| !value.is_empty()) {
+            explicit.push(ConfigLocation::new(PathBuf::from(path), ConfigSource::Environment));
+        }
+        if let Some(path) = self.preferences.load().config_path {
+            explicit.push(ConfigLocation::new(path, ConfigSource::Preference));
+        }
+
+        explicit
+    }
+
+    pub fn candidates(&self) -> Vec<ConfigLocation> {
+        let mut candidates = self.explicit();
+        candidates.extend(self.search_list());
+        candidates
+    }
+
+    fn search_list(&self) -> Vec<ConfigLocation> {
+        search_list(self.exe_path.as_deref(), &*self.env)
+    }
+
+    // An explicitly configured path that does not exist is an error rather than a reason
+    // to silently fall back to some other file.
+    pub fn resolve(&self) -> Result<ConfigLocation, ConfigError> {
+        if let Some(location) = self.explicit().into_iter().next() {
+            return if location.exists {
+                Ok(location)
+            } else {
+                Err(ConfigError::not_found(&location.path))
+            };
+        }
+
+        let search_list = self.search_list();
+
+        match search_list.iter().find(|location| location.exists) {
+            Some(location) => Ok(location.clone()),
+            None => Err(ConfigError::NotFound {
+                path: search_list
+                    .iter()
+                    .map(|location| location.path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+        }
+    }
+
+    pub fn path(&self) -> Result<PathBuf, ConfigError> {
+        self.resolve().map(|location| location.path)
+    }
+
+    pub fn select(&self, path: PathBuf, remember: bool) -> Result<ConfigLocation, ConfigError> {
+        if !path.is_file() {
+            return Err(ConfigError::not_found(&path));
+        }
+
+        let path = fs::canonicalize(&path).map_err(|e| ConfigError::io(&path, e))?;
+
+        if remember {
+            self.preferences.update(|preferences| preferences.config_path = Some(path.clone()))?;
+        }
+
+        *self.selected.lock().unwrap() = Some(path.clone());
+        Ok(ConfigLocation::new(path, ConfigSource::Selected))
+    }
+
+    pub fn forget(&self) -> Result<(), ConfigError> {
+        *self.selected.lock().unwrap() = None;
+        self.preferences.update(|preferences| preferences.config_path = None)?;
+        Ok(())
+    }
+}
+
+// Accepts both `--config path` and `--config=path`.
+pub fn config_path_from_args<I: IntoIterator<Item = String>>(args: I) -> Option<PathBuf> {
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == CONFIG_ARG {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+
+    None
+}
+
+pub fn search_list(exe_path: Option<&Path>, env: &dyn Fn(&str) -> Option<OsString>) -> Vec<ConfigLocation> {
+    let mut locations = Vec::new();
+
+    if let Some(dir) = exe_path.and_then(Path::parent) {
+        locations.push(ConfigLocation::new(dir.join(CONFIG_FILE_NAME), ConfigSource::ExeDirectory));
+    }
+
+    if let Some(dir) = user_config_dir_from(env) {
+        locations.push(ConfigLocation::new(dir.join("arc2proxy").join(CONFIG_FILE_NAME), ConfigSource::UserConfigDirectory));
+    }
+
+    if cfg!(unix) {
+        locations.push(ConfigLocation::new(Path::new("/etc/arc2proxy").join(CONFIG_FILE_NAME), ConfigSource::SystemDirectory));
+    }
+
+    locations
+}
+
+pub(crate) fn user_config_dir() -> Option<PathBuf> {
+    user_config_dir_from(&|name| env::var_os(name))
+}
+
+fn user_config_dir_from(env: &dyn Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    if cfg!(windows) {
+        return env("APPDATA").map(PathBuf::from);
+    }
+
+    env("XDG_CONFIG_HOME")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env("HOME").map(|home| PathBuf::from(home).join(".config")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, process};
+
+    use super::*;
+
+    struct Setup {
+        dir: PathBuf,
+        cli: bool,
+        env: bool,
+        preference: bool
+    }
+
+    impl Setup {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("arc2proxy-gui-location-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            for sub in ["bin", "xdg/arc2proxy"] {
+                fs::create_dir_all(dir.join(sub)).unwrap();
+            }
+            for file in ["selected.toml", "cli.toml", "env.toml", "preference.toml", "bin/proxy_config.toml", "xdg/arc2proxy/proxy_config.toml"] {
+                fs::write(dir.join(file), "proxy_rules = []\n").unwrap();
+            }
+            Setup { dir, cli: true, env: true, preference: true }
+        }
+
+        fn locator(&self) -> ConfigLocator {
+            let preferences_path = self.dir.join("preferences.json");
+            let preference = self.preference.then(|| self.dir.join("preference.toml"));
+            PreferenceStore::new(Some(preferences_path.clone()))
+                .update(|preferences| preferences.config_path = preference)
+                .unwrap();
+
+            let mut vars = HashMap::new();
+            vars.insert("XDG_CONFIG_HOME", self.dir.join("xdg").into_os_string());
+            if self.env {
+                vars.insert(CONFIG_ENV_VAR, self.dir.join("env.toml").into_os_string());
+            }
+
+            ConfigLocator::new(self.cli.then(|| self.dir.join("cli.toml")), Some(preferences_path))
+                .with_lookups(move |name| vars.get(name).cloned(), Some(self.dir.join("bin").join("arc2proxy-gui")))
+        }
+
+        fn resolved(&self) -> (ConfigSource, PathBuf) {
+            let location = self.locator().resolve().unwrap();
+            (location.source, location.path)
+        }
+    }
+
+    impl Drop for Setup {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn each_step_overrides_the_ones_after_it() {
+        let mut setup = Setup::new("precedence");
+        let dir = setup.dir.clone();
+
+        let locator = setup.locator();
+        locator.select(dir.join("selected.toml"), false).unwrap();
+        assert_eq!(locator.resolve().unwrap().source, ConfigSource::Selected);
+        let sources: Vec<ConfigSource> = locator.candidates().iter().take(5).map(|location| location.source).collect();
+        assert_eq!(sources, vec![
+            ConfigSource::Selected,
+            ConfigSource::CommandLine,
+            ConfigSource::Environment,
+            ConfigSource::Preference,
+            ConfigSource::ExeDirectory,
+        ]);
+
+        assert_eq!(setup.resolved(), (ConfigSource::CommandLine, dir.join("cli.toml")));
+        setup.cli = false;
+        assert_eq!(setup.resolved(), (ConfigSource::Environment, dir.join("env.toml")));
+        setup.env = false;
+        assert_eq!(setup.resolved(), (ConfigSource::Preference, dir.join("preference.toml")));
+        setup.preference = false;
+        assert_eq!(setup.resolved(), (ConfigSource::ExeDirectory, dir.join("bin").join(CONFIG_FILE_NAME)));
+    }
+
+    #[test]
+    fn search_list_skips_missing_files() {
+        let mut setup = Setup::new("search");
+        setup.cli = false;
+        setup.env = false;
+        setup.preference = false;
+        let dir = setup.dir.clone();
+
+        fs::remove_file(dir.join("bin").join(CONFIG_FILE_NAME)).unwrap();
+        assert_eq!(
+            setup.resolved(),
+            (ConfigSource::UserConfigDirectory, dir.join("xdg").join("arc2proxy").join(CONFIG_FILE_NAME))
+        );
+
+        fs::remove_file(dir.join("xdg").join("arc2proxy").join(CONFIG_FILE_NAME)).unwrap();
+        match setup.locator().resolve() {
+            Err(ConfigError::NotFound { path }) => {
+                assert!(path.starts_with(&dir.join("bin").join(CONFIG_FILE_NAME).display().to_string()), "{}", path);
+                assert!(path.contains(&dir.join("xdg").display().to_string()), "{}", path);
+            }
+            other => panic!("expected not found, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn missing_explicit_file_does_not_fall_back() {
+        let setup = Setup::new("explicit");
+        fs::remove_file(setup.dir.join("env.toml")).unwrap();
+
+        let locator = setup.locator();
+        assert_eq!(locator.resolve().unwrap().source, ConfigSource::CommandLine);
+
+        fs::remove_file(setup.dir.join("cli.toml")).unwrap();
+        assert_eq!(locator.resolve(), Err(ConfigError::not_found(&setup.dir.join("cli.toml"))));
+
+        let candidates = locator.candidates();
+        assert_eq!((candidates[0].source, candidates[0].exists), (ConfigSource::CommandLine, false));
+        assert_eq!((candidates[1].source, candidates[1].exists), (ConfigSource::Environment, false));
+    }
+
+    #[test]
+    fn empty_environment_variable_is_ignored() {
+        let setup = Setup::new("empty-env");
+        let locator = ConfigLocator::new(None, None).with_lookups(
+            |name| (name == CONFIG_ENV_VAR).then(OsString::new),
+            Some(setup.dir.join("bin").join("arc2proxy-gui"))
+        );
+        assert_eq!(locator.resolve().unwrap().source, ConfigSource::ExeDirectory);
+    }
+
+    #[test]
+    fn reads_config_argument() {
+        let args = |args: &[&str]| config_path_from_args(args.iter().map(|arg| arg.to_string()));
+
+        assert_eq!(args(&["--config", "a.toml"]), Some(PathBuf::from("a.toml")));
+        assert_eq!(args(&["validate", "--config=b.toml"]), Some(PathBuf::from("b.toml")));
+        assert_eq!(args(&["validate", "--config"]), None);
+        assert_eq!(args(&["validate"]), None);
+    }
+}
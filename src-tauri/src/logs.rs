@@ -0,0 +1,471 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::error::ConfigError;
+
+pub const LOG_BATCH_EVENT: &str = "log-batch";
+
+// Lines waiting to be sent. When the webview falls behind, the oldest lines are dropped
+// instead of growing without bound.
+const MAX_PENDING_LINES: usize = 5_000;
+const MAX_BATCH_LINES: usize = 500;
+const MAX_LINE_LENGTH: usize = 4_096;
+const BATCH_INTERVAL: Duration = Duration::from_millis(200);
+// A batch that is never acknowledged (e.g. the page was reloaded) stops blocking after this.
+const ACK_TIMEOUT: Duration = Duration::from_secs(5);
+const FILE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+// How much of an existing log file is shown when tailing starts.
+const FILE_BACKLOG_BYTES: u64 = 64 * 1024;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error
+}
+
+impl Level {
+    fn parse(word: &str) -> Option<Level> {
+        match word {
+            "TRACE" => Some(Level::Trace),
+            "DEBUG" => Some(Level::Debug),
+            "INFO" => Some(Level::Info),
+            "WARN" | "WARNING" => Some(Level::Warn),
+            "ERROR" | "FATAL" | "CRITICAL" => Some(Level::Error),
+            _ => None
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+    File
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogLine {
+    pub stream: LogStream,
+    pub level: Option<Level>,
+    pub timestamp: Option<String>,
+    pub text: String
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogBatch {
+    pub sequence: u64,
+    pub lines: Vec<LogLine>,
+    // Lines dropped since the previous batch because the frontend could not keep up.
+    pub dropped: usize
+}
+
+#[derive(serde::Deserialize)]
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    pub min_level: Option<Level>,
+    pub domain: Option<String>,
+    pub pattern: Option<String>
+}
+
+struct CompiledFilter {
+    min_level: Option<Level>,
+    domain: Option<String>,
+    pattern: Option<Regex>
+}
+
+impl CompiledFilter {
+    fn compile(filter: &LogFilter) -> Result<Self, ConfigError> {
+        let pattern = match filter.pattern.as_deref().filter(|pattern| !pattern.is_empty()) {
+            Some(pattern) => Some(
+                RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .size_limit(1 << 20)
+                    .build()
+                    .map_err(|e| ConfigError::validation("pattern", e.to_string()))?
+            ),
+            None => None
+        };
+
+        Ok(CompiledFilter {
+            min_level: filter.min_level,
+            domain: filter.domain.as_deref().map(str::trim).filter(|domain| !domain.is_empty()).map(str::to_lowercase),
+            pattern
+        })
+    }
+
+    fn matches(&self, line: &LogLine) -> bool {
+        if let (Some(min_level), Some(level)) = (self.min_level, line.level) {
+            if level < min_level {
+                return false;
+            }
+        }
+
+        if let Some(domain) = &self.domain {
+            if !line.text.to_lowercase().contains(domain.as_str()) {
+                return false;
+            }
+        }
+
+        self.pattern.as_ref().is_none_or(|pattern| pattern.is_match(&line.text))
+    }
+}
+
+fn timestamp_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"^\[?(\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?)").unwrap()
+    })
+}
+
+fn level_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\b(TRACE|DEBUG|INFO|WARNING|WARN|ERROR|FATAL|CRITICAL)\b").unwrap())
+}
+
+fn ansi_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\x1b\[[0-9;]*[A-Za-z]").unwrap())
+}
+
+// Understands the usual env_logger and tracing layouts, e.g.
+// `[2024-05-01T10:00:00Z INFO arc2proxy] ...` and `2024-05-01T10:00:00.123Z  WARN arc2proxy: ...`.
+// The level is only looked for near the start so an "ERROR" inside a message does not count.
+pub fn parse_line(stream: LogStream, raw: &str) -> LogLine {
+    let mut text = ansi_pattern().replace_all(raw.trim_end_matches(['\r', '\n']), "").into_owned();
+
+    if text.len() > MAX_LINE_LENGTH {
+        let mut end = MAX_LINE_LENGTH;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push('…');
+    }
+
+    let timestamp = timestamp_pattern()
+        .captures(&text)
+        .map(|captures| captures[1].to_string());
+
+    let mut head_end = text.len().min(64);
+    while !text.is_char_boundary(head_end) {
+        head_end -= 1;
+    }
+
+    let level = level_pattern()
+        .captures(&text[..head_end])
+        .and_then(|captures| Level::parse(&captures[1]));
+
+    LogLine { stream, level, timestamp, text }
+}
+
+struct Inner {
+    filter: CompiledFilter,
+    pending: VecDeque<LogLine>,
+    dropped: usize,
+    // Continuation lines such as backtraces have no level of their own and keep the one
+    // of the line before them.
+    last_level: [Option<Level>; 3],
+    sequence: u64,
+    awaiting_ack: Option<(u64, Instant)>,
+    // Bumped whenever a different file is tailed so the old tail thread stops.
+    file_generation: u64,
+    file: Option<PathBuf>
+}
+
+// Collects lines from the supervised proxy or a log file and hands them to the webview in
+// batches. Only one batch is in flight at a time: the next one is sent once the frontend
+// acknowledges the previous one.
+#[derive(Clone)]
+pub struct LogTail {
+    inner: Arc<Mutex<Inner>>
+}
+
+impl Default for LogTail {
+    fn default() -> Self {
+        LogTail {
+            inner: Arc::new(Mutex::new(Inner {
+                filter: CompiledFilter { min_level: None, domain: None, pattern: None },
+                pending: VecDeque::new(),
+                dropped: 0,
+                last_level: [None; 3],
+                sequence: 0,
+                awaiting_ack: None,
+                file_generation: 0,
+                file: None
+            }))
+        }
+    }
+}
+
+impl LogTail {
+    pub fn set_filter(&self, filter: &LogFilter) -> Result<(), ConfigError> {
+        let filter = CompiledFilter::compile(filter)?;
+        self.inner.lock().unwrap().filter = filter;
+        Ok(())
+    }
+
+    pub fn push(&self, stream: LogStream, raw: &str) {
+        let mut line = parse_line(stream, raw);
+        let mut inner = self.inner.lock().unwrap();
+
+        let slot = stream as usize;
+        match line.level {
+            Some(level) => inner.last_level[slot] = Some(level),
+            None => line.level = inner.last_level[slot]
+        }
+
+        if !inner.filter.matches(&line) {
+            return;
+        }
+
+        if inner.pending.len() == MAX_PENDING_LINES {
+            inner.pending.pop_front();
+            inner.dropped += 1;
+        }
+        inner.pending.push_back(line);
+    }
+
+    // The next batch to send, or None while the previous one is still unacknowledged.
+    pub fn take_batch(&self) -> Option<LogBatch> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if let Some((_, sent)) = inner.awaiting_ack {
+            if sent.elapsed() < ACK_TIMEOUT {
+                return None;
+            }
+        }
+
+        if inner.pending.is_empty() && inner.dropped == 0 {
+            return None;
+        }
+
+        let count = inner.pending.len().min(MAX_BATCH_LINES);
+        let lines: Vec<LogLine> = inner.pending.drain(..count).collect();
+
+        inner.sequence += 1;
+        inner.awaiting_ack = Some((inner.sequence, Instant::now()));
+
+        Some(LogBatch {
+            sequence: inner.sequence,
+            lines,
+            dropped: std::mem::take(&mut inner.dropped)
+        })
+    }
+
+    pub fn acknowledge(&self, sequence: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.awaiting_ack.is_some_and(|(pending, _)| pending == sequence) {
+            inner.awaiting_ack = None;
+        }
+    }
+
+    pub fn run_emitter<F: Fn(&LogBatch) + Send + 'static>(&self, sink: F) {
+        let tail = self.clone();
+
+        thread::spawn(move || loop {
+            thread::sleep(BATCH_INTERVAL);
+            if let Some(batch) = tail.take_batch() {
+                sink(&batch);
+            }
+        });
+    }
+
+    pub fn file(&self) -> Option<PathBuf> {
+        self.inner.lock().unwrap().file.clone()
+    }
+
+    // Follows `path` like `tail -f`, starting with the last few kilobytes. A file that
+    // shrinks is assumed to have been rotated and is read again from the start.
+    pub fn tail_file(&self, path: &Path) -> Result<(), ConfigError> {
+        let mut file = File::open(path).map_err(|e| ConfigError::io(path, e))?;
+        let length = file.metadata().map_err(|e| ConfigError::io(path, e))?.len();
+        let mut position = file
+            .seek(SeekFrom::Start(length.saturating_sub(FILE_BACKLOG_BYTES)))
+            .map_err(|e| ConfigError::io(path, e))?;
+
+        let generation = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.file_generation += 1;
+            inner.file = Some(path.to_path_buf());
+            inner.file_generation
+        };
+
+        let tail = self.clone();
+        let path = path.to_path_buf();
+
+        thread::spawn(move || {
+            let mut reader = BufReader::new(file);
+            let mut partial = Vec::new();
+
+            // The first line is most likely cut in half by the seek.
+            if position > 0 {
+                let _ = reader.read_until(b'\n', &mut partial);
+                partial.clear();
+            }
+
+            loop {
+                if tail.inner.lock().unwrap().file_generation != generation {
+                    return;
+                }
+
+                match reader.read_until(b'\n', &mut partial) {
+                    Ok(0) => {
+                        thread::sleep(FILE_POLL_INTERVAL);
+
+                        let length = std::fs::metadata(&path).map(|metadata| metadata.len()).unwrap_or(0);
+                        if length < position {
+                            match File::open(&path) {
+                                Ok(file) => reader = BufReader::new(file),
+                                Err(_) => continue
+                            }
+                            position = 0;
+                            partial.clear();
+                        }
+                    }
+                    Ok(read) => {
+                        position += read as u64;
+                        if partial.ends_with(b"\n") {
+                            tail.push(LogStream::File, &String::from_utf8_lossy(&partial));
+                            partial.clear();
+                        }
+                    }
+                    Err(_) => thread::sleep(FILE_POLL_INTERVAL)
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    pub fn stop_file(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.file_generation += 1;
+        inner.file = None;
+    }
+}
+
+// Feeds one output stream of the supervised proxy into `tail` until the process closes it.
+pub fn follow_stream<R: Read + Send + 'static>(tail: LogTail, stream: LogStream, source: R) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(source);
+        let mut line = Vec::new();
+
+        while let Ok(read) = reader.read_until(b'\n', &mut line) {
+            if read == 0 {
+                break;
+            }
+            tail.push(stream, &String::from_utf8_lossy(&line));
+            line.clear();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(batch: &LogBatch) -> Vec<&str> {
+        batch.lines.iter().map(|line| line.text.as_str()).collect()
+    }
+
+    #[test]
+    fn parses_level_and_timestamp_of_proxy_lines() {
+        let line = parse_line(LogStream::Stderr, "[2024-05-01T10:00:00Z INFO  arc2proxy] Listening on 0.0.0.0:80\n");
+        assert_eq!(line.level, Some(Level::Info));
+        assert_eq!(line.timestamp.as_deref(), Some("2024-05-01T10:00:00Z"));
+        assert_eq!(line.text, "[2024-05-01T10:00:00Z INFO  arc2proxy] Listening on 0.0.0.0:80");
+
+        let line = parse_line(LogStream::Stdout, "2024-05-01T10:00:00.123456Z  WARN arc2proxy::proxy: upstream a.com is slow\r\n");
+        assert_eq!(line.level, Some(Level::Warn));
+        assert_eq!(line.timestamp.as_deref(), Some("2024-05-01T10:00:00.123456Z"));
+
+        let line = parse_line(LogStream::Stdout, "\x1b[2m2024-05-01 10:00:00,5+02:00\x1b[0m \x1b[31mERROR\x1b[0m certificate renewal failed");
+        assert_eq!(line.level, Some(Level::Error));
+        assert_eq!(line.timestamp.as_deref(), Some("2024-05-01 10:00:00,5+02:00"));
+        assert_eq!(line.text, "2024-05-01 10:00:00,5+02:00 ERROR certificate renewal failed");
+
+        // A level word deep inside the message is not the level of the line.
+        let line = parse_line(LogStream::Stdout, "   at arc2proxy::handler (src/handler.rs:42) while logging the previous ERROR");
+        assert_eq!(line.level, None);
+        assert_eq!(line.timestamp, None);
+    }
+
+    #[test]
+    fn filters_by_level_domain_and_pattern() {
+        let tail = LogTail::default();
+        tail.set_filter(&LogFilter { min_level: Some(Level::Warn), domain: None, pattern: None }).unwrap();
+        tail.push(LogStream::Stdout, "INFO a.com served");
+        tail.push(LogStream::Stdout, "WARN a.com slow");
+        tail.push(LogStream::Stdout, "    continued warning");
+        tail.push(LogStream::Stdout, "ERROR B.com down");
+        assert_eq!(texts(&tail.take_batch().unwrap()), vec!["WARN a.com slow", "    continued warning", "ERROR B.com down"]);
+
+        let tail = LogTail::default();
+        tail.set_filter(&LogFilter { min_level: None, domain: Some(" b.COM ".into()), pattern: None }).unwrap();
+        tail.push(LogStream::Stdout, "INFO a.com served");
+        tail.push(LogStream::Stdout, "ERROR B.com down");
+        assert_eq!(texts(&tail.take_batch().unwrap()), vec!["ERROR B.com down"]);
+
+        let tail = LogTail::default();
+        tail.set_filter(&LogFilter { min_level: None, domain: None, pattern: Some(r"status=5\d\d".into()) }).unwrap();
+        tail.push(LogStream::Stdout, "INFO GET / STATUS=502");
+        tail.push(LogStream::Stdout, "INFO GET / status=200");
+        assert_eq!(texts(&tail.take_batch().unwrap()), vec!["INFO GET / STATUS=502"]);
+
+        let error = tail.set_filter(&LogFilter { min_level: None, domain: None, pattern: Some("(".into()) }).unwrap_err();
+        assert!(matches!(error, ConfigError::Validation { ref field, .. } if field == "pattern"));
+    }
+
+    #[test]
+    fn drops_the_oldest_lines_past_max_pending() {
+        let tail = LogTail::default();
+        for index in 0..MAX_PENDING_LINES + 3 {
+            tail.push(LogStream::Stdout, &format!("INFO line {}", index));
+        }
+
+        let batch = tail.take_batch().unwrap();
+        assert_eq!(batch.dropped, 3);
+        assert_eq!(batch.lines.len(), MAX_BATCH_LINES);
+        assert_eq!(batch.lines[0].text, "INFO line 3");
+    }
+
+    #[test]
+    fn sends_the_next_batch_only_after_acknowledgement() {
+        let tail = LogTail::default();
+        assert!(tail.take_batch().is_none());
+
+        tail.push(LogStream::Stdout, "INFO first");
+        let first = tail.take_batch().unwrap();
+        assert_eq!(first.sequence, 1);
+
+        tail.push(LogStream::Stdout, "INFO second");
+        assert!(tail.take_batch().is_none());
+
+        tail.acknowledge(first.sequence + 1);
+        assert!(tail.take_batch().is_none());
+
+        tail.acknowledge(first.sequence);
+        let second = tail.take_batch().unwrap();
+        assert_eq!(second.sequence, 2);
+        assert_eq!(texts(&second), vec!["INFO second"]);
+
+        tail.acknowledge(second.sequence);
+        assert!(tail.take_batch().is_none());
+    }
+}
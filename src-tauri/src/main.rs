@@ -2,5 +2,10 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    // Subcommands run headless, e.g. over SSH, without ever starting the webview.
+    if let Some(code) = arc2proxy_gui_lib::cli::run(std::env::args().skip(1).collect()) {
+        std::process::exit(code);
+    }
+
     arc2proxy_gui_lib::run()
 }
@@ -1,8 +1,13 @@
-use std::{net::IpAddr, ops::Deref, str::FromStr};
-use serde::Deserialize;
+use std::{fmt, ops::Deref, str::FromStr};
+use serde::{
+    de::{self, IntoDeserializer},
+    Deserialize, Serialize,
+};
 
-const DEV_LOGGING_LEVEL: &str = "debug";
-const PROD_LOGGING_LEVEL: &str = "error";
+use crate::validation::ValidationReport;
+
+const DEV_LOGGING_LEVEL: LoggingLevel = LoggingLevel::new(LogLevel::Debug);
+const PROD_LOGGING_LEVEL: LoggingLevel = LoggingLevel::new(LogLevel::Error);
 
 #[derive(serde::Serialize)]
 #[derive(Deserialize, Clone, Debug)]
@@ -18,7 +23,7 @@ pub struct ProxyConfiguration {
     pub api_key: Option<String>,
     pub azure_table_storage_key: Option<String>,
     pub proxy_rules: Vec<ProxyRuleInner>,
-    pub logging_level: Option<String>,
+    pub logging_level: Option<LoggingLevel>,
     pub add_sql_injection_protection: Option<bool>,
     pub lets_encrypt_contact_email: Option<String>,
     pub enable_compression: Option<bool>,
@@ -34,7 +39,7 @@ pub struct ProxyConfiguration {
     pub proxy_nodelay: Option<bool>,
     pub proxy_keepalive_sec: Option<u32>,
     pub proxy_timeout: Option<u16>,
-    pub proxy_min_tls_version:  Option<String>
+    pub proxy_min_tls_version:  Option<TlsVersion>
 }
 
 #[derive(serde::Serialize)]
@@ -52,7 +57,7 @@ pub struct ProxyConfigurationInner {
     pub api_key: Option<String>,
     pub azure_table_storage_key: Option<String>,
     pub proxy_rules: Vec<ProxyRuleInner>,
-    pub logging_level: String,
+    pub logging_level: LoggingLevel,
     pub add_sql_injection_protection: bool,
     pub lets_encrypt_contact_email: Option<String>,
     pub enable_compression: bool,
@@ -68,19 +73,20 @@ pub struct ProxyConfigurationInner {
     pub proxy_nodelay: bool,
     pub proxy_keepalive_sec: u32,
     pub proxy_timeout: u16,
-    pub proxy_min_tls_version: String
+    pub proxy_min_tls_version: TlsVersion
 }
 
 impl ProxyConfiguration {
-    pub fn into_inner(&self, config_file_found: bool) -> ProxyConfigurationInner {
+    pub fn into_inner(&self, config_file_found: bool) -> Result<ProxyConfigurationInner, ValidationReport> {
+        let report = self.validate();
+
+        if report.has_errors() {
+            return Err(report);
+        }
+
         let listening_address = {
             if let Some(addr) = self.listening_address.clone() {
-                if IpAddr::from_str(&addr).is_ok() {
-                    addr
-                }
-                else {
-                    panic!("Provided listening http address cannot be parsed.")
-                }
+                addr
             }
             else {
                 "0.0.0.0".into()
@@ -89,11 +95,7 @@ impl ProxyConfiguration {
 
         let listening_port_http: u16 = {
             if let Some(port) = self.listening_port_http {
-                if port <= u16::MAX {
-                    port
-                } else {
-                    panic!("Provided listening http port must be between 0 and {}.", u16::MAX);
-                }
+                port
             } else {
                 80
             }
@@ -101,11 +103,7 @@ impl ProxyConfiguration {
 
         let listening_port_https: u16 = {
             if let Some(port) = self.listening_port_https {
-                if port <= u16::MAX {
-                    port
-                } else {
-                    panic!("Provided listening http port must be between 0 and {}.", u16::MAX);
-                }
+                port
             } else {
                 443
             }
@@ -113,39 +111,17 @@ impl ProxyConfiguration {
 
         let logging_level = if cfg!(debug_assertions) {
             if let Some(level) = self.logging_level.clone() {
-                if 
-                    level.starts_with("off") || 
-                    level.starts_with("trace") || 
-                    level.starts_with("debug") || 
-                    level.starts_with("info") || 
-                    level.starts_with("warn") || 
-                    level.starts_with("error") {
-                    level
-                }
-                else {
-                    panic!("Provided logging level is incorrect, select one of those: off, trace, debug, info, warn, error.")
-                }
+                level
             }
             else {
-                DEV_LOGGING_LEVEL.into()
+                DEV_LOGGING_LEVEL
             }
         } else {
             if let Some(level) = self.logging_level.clone() {
-                if 
-                    level.starts_with("off") || 
-                    level.starts_with("trace") || 
-                    level.starts_with("debug") || 
-                    level.starts_with("info") || 
-                    level.starts_with("warn") || 
-                    level.starts_with("error") {
-                    level
-                }
-                else {
-                    panic!("Provided logging level is incorrect, select one of those: off, trace, debug, info, warn, error.")
-                }
+                level
             }
             else {
-                PROD_LOGGING_LEVEL.into()
+                PROD_LOGGING_LEVEL
             }
         };
 
@@ -213,21 +189,15 @@ impl ProxyConfiguration {
             }
         };
 
-        let proxy_min_tls_version: String = {
-            if let Some(proxy_min_tls_version) = self.proxy_min_tls_version.clone() {
-                match proxy_min_tls_version.as_str() {
-                    "TLS_1_0" => proxy_min_tls_version,
-                    "TLS_1_1" => proxy_min_tls_version,
-                    "TLS_1_2" => proxy_min_tls_version,
-                    "TLS_1_3" => proxy_min_tls_version,
-                    _ => panic!("Invalid TLS value.")
-                }
+        let proxy_min_tls_version: TlsVersion = {
+            if let Some(proxy_min_tls_version) = self.proxy_min_tls_version {
+                proxy_min_tls_version
             } else {
-                "TLS_1_3".into()
+                TlsVersion::Tls13
             }
         };
 
-        ProxyConfigurationInner {
+        Ok(ProxyConfigurationInner {
             configuration_file_found: config_file_found,
             listening_address: listening_address,
             listening_port_http: listening_port_http,
@@ -257,7 +227,7 @@ impl ProxyConfiguration {
             proxy_keepalive_sec: proxy_keepalive_sec,
             proxy_timeout: proxy_timeout,
             proxy_min_tls_version: proxy_min_tls_version
-        }
+        })
     }
 
     pub fn default_inner() -> ProxyConfigurationInner {
@@ -287,7 +257,7 @@ impl ProxyConfiguration {
                 api_key: None,
                 azure_table_storage_key: None,
                 proxy_rules: Vec::default(),
-                logging_level: DEV_LOGGING_LEVEL.into(),
+                logging_level: DEV_LOGGING_LEVEL,
                 add_sql_injection_protection: false,
                 lets_encrypt_contact_email: None,
                 enable_compression: false,
@@ -303,7 +273,7 @@ impl ProxyConfiguration {
                 proxy_nodelay: true,
                 proxy_keepalive_sec: 120,
                 proxy_timeout: 45,
-                proxy_min_tls_version: "TLS_1_3".into()
+                proxy_min_tls_version: TlsVersion::Tls13
             }
         } else {
             ProxyConfigurationInner {
@@ -319,7 +289,7 @@ impl ProxyConfiguration {
                 api_key: None,
                 azure_table_storage_key: None,
                 proxy_rules: Vec::default(),
-                logging_level: PROD_LOGGING_LEVEL.into(),
+                logging_level: PROD_LOGGING_LEVEL,
                 add_sql_injection_protection: false,
                 lets_encrypt_contact_email: None,
                 enable_compression: false,
@@ -335,7 +305,7 @@ impl ProxyConfiguration {
                 proxy_nodelay: true,
                 proxy_keepalive_sec: 120,
                 proxy_timeout: 45,
-                proxy_min_tls_version: "TLS_1_3".into()
+                proxy_min_tls_version: TlsVersion::Tls13
             }
         }
     }
@@ -369,7 +339,7 @@ impl Default for ProxyConfigurationInner {
                 api_key: None,
                 azure_table_storage_key: None,
                 proxy_rules: Vec::default(),
-                logging_level: DEV_LOGGING_LEVEL.into(),
+                logging_level: DEV_LOGGING_LEVEL,
                 add_sql_injection_protection: false,
                 lets_encrypt_contact_email: None,
                 enable_compression: false,
@@ -385,7 +355,7 @@ impl Default for ProxyConfigurationInner {
                 proxy_nodelay: true,
                 proxy_keepalive_sec: 120,
                 proxy_timeout: 45,
-                proxy_min_tls_version: "TLS_1_3".into()
+                proxy_min_tls_version: TlsVersion::Tls13
             }
         } else {
             Self {
@@ -401,7 +371,7 @@ impl Default for ProxyConfigurationInner {
                 api_key: None,
                 azure_table_storage_key: None,
                 proxy_rules: Vec::default(),
-                logging_level: PROD_LOGGING_LEVEL.into(),
+                logging_level: PROD_LOGGING_LEVEL,
                 add_sql_injection_protection: false,
                 lets_encrypt_contact_email: None,
                 enable_compression: false,
@@ -417,7 +387,7 @@ impl Default for ProxyConfigurationInner {
                 proxy_nodelay: true,
                 proxy_keepalive_sec: 120,
                 proxy_timeout: 45,
-                proxy_min_tls_version: "TLS_1_3".into()
+                proxy_min_tls_version: TlsVersion::Tls13
             }
         }
     }
@@ -446,7 +416,8 @@ pub struct ProxyRuleInner {
     pub enable_minification: bool,
     pub minification_flags: Option<String>,
     pub enable_webp_transformation: bool,
-    pub webp_transformation_min_age: Option<u64>
+    pub webp_transformation_min_age: Option<u64>,
+    pub manual_certificate: Option<bool>
 }
 
 #[derive(serde::Serialize)]
@@ -525,4 +496,194 @@ pub struct RoutingLocation {
 pub struct UserAgentRule {
     pub user_agent: String,
     pub match_type: MatchType
-}
\ No newline at end of file
+}
+
+// Serialized by name and parsed strictly, so "errorXYZ" is rejected instead of passing as "error".
+#[derive(serde::Serialize)]
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Off,
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error
+}
+
+// `logging_level` is a level optionally followed by the proxy's filter directives, e.g.
+// `info,hyper=warn`. Only the level is checked, the directives are passed on as they are.
+#[derive(serde::Serialize)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct LoggingLevel {
+    pub level: LogLevel,
+    pub directives: Option<String>
+}
+
+// Ordered from oldest to newest, so versions compare like the protocol versions they name.
+#[derive(serde::Serialize)]
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    #[serde(rename = "TLS_1_0")]
+    Tls10,
+    #[serde(rename = "TLS_1_1")]
+    Tls11,
+    #[serde(rename = "TLS_1_2")]
+    Tls12,
+    #[serde(rename = "TLS_1_3")]
+    Tls13
+}
+
+impl TlsVersion {
+    pub fn is_deprecated(self) -> bool {
+        self < Self::Tls12
+    }
+}
+
+impl LoggingLevel {
+    pub const fn new(level: LogLevel) -> Self {
+        LoggingLevel { level, directives: None }
+    }
+}
+
+fn parse_name<'de, T: Deserialize<'de>>(raw: &'de str) -> Result<T, String> {
+    T::deserialize(raw.into_deserializer()).map_err(|e: de::value::Error| e.to_string())
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        parse_name(raw)
+    }
+}
+
+impl FromStr for TlsVersion {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        parse_name(raw)
+    }
+}
+
+impl FromStr for LoggingLevel {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let (level, directives) = match raw.split_once(',') {
+            Some((level, directives)) => (level, Some(directives)),
+            None => (raw, None)
+        };
+
+        Ok(LoggingLevel {
+            level: level.parse()?,
+            directives: directives.map(String::from)
+        })
+    }
+}
+
+// The names come from the serde attributes above.
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.serialize(f)
+    }
+}
+
+impl fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.serialize(f)
+    }
+}
+
+impl fmt::Display for LoggingLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.directives {
+            Some(directives) => write!(f, "{},{}", self.level, directives),
+            None => write!(f, "{}", self.level)
+        }
+    }
+}
+
+impl TryFrom<String> for LoggingLevel {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        raw.parse()
+    }
+}
+
+impl From<LoggingLevel> for String {
+    fn from(logging_level: LoggingLevel) -> Self {
+        logging_level.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings;
+
+    #[derive(serde::Serialize, Deserialize, Debug, PartialEq)]
+    struct Levels {
+        logging_level: LoggingLevel,
+        proxy_min_tls_version: TlsVersion
+    }
+
+    const LOG_LEVELS: [LogLevel; 6] = [LogLevel::Off, LogLevel::Trace, LogLevel::Debug, LogLevel::Info, LogLevel::Warn, LogLevel::Error];
+    const TLS_VERSIONS: [TlsVersion; 4] = [TlsVersion::Tls10, TlsVersion::Tls11, TlsVersion::Tls12, TlsVersion::Tls13];
+
+    #[test]
+    fn round_trips_every_value() {
+        for (level, name) in LOG_LEVELS.into_iter().zip(settings::LOG_LEVELS) {
+            assert_eq!(level.to_string(), name);
+            assert_eq!(name.parse::<LogLevel>(), Ok(level));
+        }
+        for (version, name) in TLS_VERSIONS.into_iter().zip(settings::TLS_VERSIONS) {
+            assert_eq!(version.to_string(), name);
+            assert_eq!(name.parse::<TlsVersion>(), Ok(version));
+        }
+
+        let levels = Levels { logging_level: LoggingLevel::new(LogLevel::Warn), proxy_min_tls_version: TlsVersion::Tls12 };
+        let toml_string = toml::to_string(&levels).unwrap();
+        assert_eq!(toml_string, "logging_level = \"warn\"\nproxy_min_tls_version = \"TLS_1_2\"\n");
+        assert_eq!(toml::from_str::<Levels>(&toml_string).unwrap(), levels);
+    }
+
+    #[test]
+    fn keeps_filter_directives_after_the_level() {
+        let logging_level: LoggingLevel = "info,hyper=warn,arc2proxy::cache=debug".parse().unwrap();
+        assert_eq!(logging_level.level, LogLevel::Info);
+        assert_eq!(logging_level.directives.as_deref(), Some("hyper=warn,arc2proxy::cache=debug"));
+        assert_eq!(logging_level.to_string(), "info,hyper=warn,arc2proxy::cache=debug");
+
+        let levels: Levels = toml::from_str("logging_level = \"error,hyper=off\"\nproxy_min_tls_version = \"TLS_1_3\"\n").unwrap();
+        assert_eq!(levels.logging_level, LoggingLevel { level: LogLevel::Error, directives: Some("hyper=off".into()) });
+        assert_eq!(toml::to_string(&levels).unwrap(), "logging_level = \"error,hyper=off\"\nproxy_min_tls_version = \"TLS_1_3\"\n");
+    }
+
+    #[test]
+    fn rejects_anything_but_an_exact_name() {
+        assert_eq!(
+            "errorXYZ".parse::<LogLevel>(),
+            Err("unknown variant `errorXYZ`, expected one of `off`, `trace`, `debug`, `info`, `warn`, `error`".to_string())
+        );
+        assert!("Error".parse::<LogLevel>().is_err());
+        assert!("info,hyper=warn".parse::<LogLevel>().is_err());
+        assert!("errorXYZ,hyper=warn".parse::<LoggingLevel>().is_err());
+        assert!(",hyper=warn".parse::<LoggingLevel>().is_err());
+        assert!("TLS1.3".parse::<TlsVersion>().is_err());
+
+        let error = toml::from_str::<Levels>("logging_level = \"errorXYZ\"\nproxy_min_tls_version = \"TLS_1_3\"\n").unwrap_err();
+        assert!(error.message().contains("unknown variant `errorXYZ`"));
+        let error = toml::from_str::<Levels>("logging_level = \"error\"\nproxy_min_tls_version = \"TLS_1_4\"\n").unwrap_err();
+        assert!(error.message().contains("unknown variant `TLS_1_4`, expected one of `TLS_1_0`, `TLS_1_1`, `TLS_1_2`, `TLS_1_3`"));
+    }
+
+    #[test]
+    fn orders_tls_versions_by_age() {
+        assert!(TlsVersion::Tls10 < TlsVersion::Tls13);
+        assert!(TlsVersion::Tls11.is_deprecated());
+        assert!(!TlsVersion::Tls12.is_deprecated());
+    }
+}
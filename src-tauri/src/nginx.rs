@@ -0,0 +1,1038 @@
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use crate::{
+    error::ConfigError,
+    models::{MatchType, PathRule, ProxyRuleInner, RoutingLocation, RoutingMethod, RoutingRule, RuleType, UserAgentRule},
+};
+
+const SOURCE_NAME: &str = "nginx configuration";
+// What `expires max` means to nginx.
+const MAX_EXPIRES_SECONDS: u64 = 315_360_000;
+
+// A directive the importer could not carry over, with the reason why.
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Untranslated {
+    pub line: usize,
+    pub directive: String,
+    // The server name or block the directive was found in.
+    pub context: String,
+    pub reason: String
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NginxImport {
+    pub rules: Vec<ProxyRuleInner>,
+    pub untranslated: Vec<Untranslated>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Semicolon,
+    Open,
+    Close
+}
+
+#[derive(Debug, Clone)]
+struct Directive {
+    name: String,
+    args: Vec<String>,
+    block: Option<Vec<Directive>>,
+    line: usize,
+    column: usize
+}
+
+impl Directive {
+    // The directive as written, without its block, for the report.
+    fn text(&self) -> String {
+        let mut text = self.name.clone();
+        for arg in &self.args {
+            text.push(' ');
+            text.push_str(arg);
+        }
+        text
+    }
+
+    fn children(&self) -> &[Directive] {
+        self.block.as_deref().unwrap_or_default()
+    }
+}
+
+fn parse_error(line: usize, column: usize, message: impl Into<String>) -> ConfigError {
+    ConfigError::Parse {
+        path: SOURCE_NAME.into(),
+        message: message.into(),
+        line: Some(line),
+        column: Some(column),
+        key: None
+    }
+}
+
+fn advance(c: char, line: &mut usize, column: &mut usize) {
+    if c == '\n' {
+        *line += 1;
+        *column = 0;
+    } else {
+        *column += 1;
+    }
+}
+
+// Splits the text into words and punctuation the way nginx does: `#` starts a comment,
+// quotes group a word, and `${name}` stays part of the word it is in.
+fn tokenize(text: &str) -> Result<Vec<(Token, usize, usize)>, ConfigError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let (mut line, mut column) = (1, 0);
+
+    while let Some(c) = chars.next() {
+        advance(c, &mut line, &mut column);
+        let (start_line, start_column) = (line, column);
+
+        match c {
+            c if c.is_whitespace() => {}
+            '#' => {
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+            }
+            ';' => tokens.push((Token::Semicolon, line, column)),
+            '{' => tokens.push((Token::Open, line, column)),
+            '}' => tokens.push((Token::Close, line, column)),
+            '"' | '\'' => {
+                let mut word = String::new();
+                loop {
+                    let Some(next) = chars.next() else {
+                        return Err(parse_error(start_line, start_column, "Unterminated quoted string."));
+                    };
+                    advance(next, &mut line, &mut column);
+
+                    match next {
+                        '\\' => {
+                            if let Some(escaped) = chars.next() {
+                                advance(escaped, &mut line, &mut column);
+                                word.push(escaped);
+                            }
+                        }
+                        next if next == c => break,
+                        next => word.push(next)
+                    }
+                }
+                tokens.push((Token::Word(word), start_line, start_column));
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || matches!(next, ';' | '}' | '"' | '\'') || (next == '{' && !word.ends_with('$')) {
+                        break;
+                    }
+                    chars.next();
+                    advance(next, &mut line, &mut column);
+                    word.push(next);
+
+                    if next == '{' {
+                        for inner in chars.by_ref() {
+                            advance(inner, &mut line, &mut column);
+                            word.push(inner);
+                            if inner == '}' {
+                                break;
+                            }
+                        }
+                    }
+                }
+                tokens.push((Token::Word(word), start_line, start_column));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_block<I>(tokens: &mut I, opened_at: Option<(usize, usize)>) -> Result<Vec<Directive>, ConfigError>
+where
+    I: Iterator<Item = (Token, usize, usize)>
+{
+    let mut directives = Vec::new();
+    let mut current: Option<Directive> = None;
+
+    loop {
+        let Some((token, line, column)) = tokens.next() else {
+            return match (opened_at, current) {
+                (Some((line, column)), _) => Err(parse_error(line, column, "This block is never closed.")),
+                (None, Some(directive)) => Err(parse_error(directive.line, directive.column, format!("\"{}\" is missing a \";\".", directive.name))),
+                (None, None) => Ok(directives)
+            };
+        };
+
+        match token {
+            Token::Word(word) => match &mut current {
+                Some(directive) => directive.args.push(word),
+                None => current = Some(Directive { name: word, args: Vec::new(), block: None, line, column })
+            },
+            Token::Semicolon => match current.take() {
+                Some(directive) => directives.push(directive),
+                None => return Err(parse_error(line, column, "Unexpected \";\"."))
+            },
+            Token::Open => match current.take() {
+                Some(mut directive) => {
+                    directive.block = Some(parse_block(tokens, Some((line, column)))?);
+                    directives.push(directive);
+                }
+                None => return Err(parse_error(line, column, "A block needs a directive name before \"{\"."))
+            },
+            Token::Close => {
+                if let Some(directive) = current {
+                    return Err(parse_error(directive.line, directive.column, format!("\"{}\" is missing a \";\".", directive.name)));
+                }
+                if opened_at.is_none() {
+                    return Err(parse_error(line, column, "Unexpected \"}\"."));
+                }
+                return Ok(directives);
+            }
+        }
+    }
+}
+
+fn parse(text: &str) -> Result<Vec<Directive>, ConfigError> {
+    parse_block(&mut tokenize(text)?.into_iter(), None)
+}
+
+// nginx time values such as `30d`, `1h30m` or a plain number of seconds.
+fn parse_duration(value: &str) -> Option<u64> {
+    let mut total = 0u64;
+    let mut digits = String::new();
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+
+        let number: u64 = digits.parse().ok()?;
+        digits.clear();
+
+        let seconds = match c {
+            'm' if chars.peek() == Some(&'s') => {
+                chars.next();
+                0
+            }
+            's' => number,
+            'm' => number * 60,
+            'h' => number * 3_600,
+            'd' => number * 86_400,
+            'w' => number * 604_800,
+            'M' => number * 2_592_000,
+            'y' => number * 31_536_000,
+            _ => return None
+        };
+        total = total.checked_add(seconds)?;
+    }
+
+    if !digits.is_empty() {
+        total = total.checked_add(digits.parse().ok()?)?;
+    }
+
+    Some(total)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProxyTarget {
+    https: bool,
+    host: String,
+    port: Option<u16>
+}
+
+// What a `location` block turned into.
+struct LocationSummary<'a> {
+    target: Option<(ProxyTarget, &'a Directive)>,
+    rule_type: RuleType,
+    max_age_seconds: Option<u64>
+}
+
+#[derive(Clone, Copy, Default)]
+struct Inherited {
+    gzip: Option<bool>,
+    access_log: Option<bool>
+}
+
+struct Importer<'a> {
+    upstreams: HashMap<&'a str, &'a Directive>,
+    // Each upstream is translated once per scheme, so its problems are reported only once.
+    routing_rules: HashMap<(&'a str, bool), RoutingRule>,
+    rules: Vec<ProxyRuleInner>,
+    // Line of the server block each rule came from, by index.
+    rule_lines: Vec<usize>,
+    untranslated: Vec<Untranslated>
+}
+
+impl<'a> Importer<'a> {
+    fn report(&mut self, directive: &Directive, context: &str, reason: impl Into<String>) {
+        self.untranslated.push(Untranslated {
+            line: directive.line,
+            directive: directive.text(),
+            context: context.into(),
+            reason: reason.into()
+        });
+    }
+
+    fn collect_upstreams(&mut self, directives: &'a [Directive]) {
+        for directive in directives {
+            match directive.name.as_str() {
+                "upstream" if directive.args.len() == 1 => {
+                    self.upstreams.insert(directive.args[0].as_str(), directive);
+                }
+                "http" => self.collect_upstreams(directive.children()),
+                _ => {}
+            }
+        }
+    }
+
+    fn walk(&mut self, directives: &'a [Directive], context: &str, mut inherited: Inherited) {
+        // nginx applies these to the whole block no matter where they appear in it.
+        for directive in directives {
+            match directive.name.as_str() {
+                "gzip" => inherited.gzip = Some(directive.args.first().is_some_and(|arg| arg == "on")),
+                "access_log" => inherited.access_log = Some(directive.args.first().is_some_and(|arg| arg != "off")),
+                _ => {}
+            }
+        }
+
+        for directive in directives {
+            match directive.name.as_str() {
+                "http" => self.walk(directive.children(), "http", inherited),
+                "server" if directive.block.is_some() => self.server(directive, inherited),
+                "upstream" => {
+                    if directive.args.len() != 1 {
+                        self.report(directive, context, "An upstream block needs exactly one name.");
+                    }
+                }
+                "gzip" | "access_log" => {}
+                "include" => self.report(directive, context, "Included files are not followed, import them separately."),
+                _ => self.report(directive, context, "This directive has no arc2proxy equivalent.")
+            }
+        }
+    }
+
+    fn parse_proxy_pass(&mut self, directive: &Directive, context: &str) -> Option<ProxyTarget> {
+        let Some(url) = directive.args.first() else {
+            self.report(directive, context, "proxy_pass needs a URL.");
+            return None;
+        };
+
+        if url.contains('$') {
+            self.report(directive, context, "proxy_pass targets built from variables cannot be translated.");
+            return None;
+        }
+
+        let (https, rest) = if let Some(rest) = url.strip_prefix("https://") {
+            (true, rest)
+        } else if let Some(rest) = url.strip_prefix("http://") {
+            (false, rest)
+        } else {
+            self.report(directive, context, "Only http:// and https:// targets can be translated.");
+            return None;
+        };
+
+        if rest.starts_with("unix:") {
+            self.report(directive, context, "UNIX socket targets are not supported by arc2proxy.");
+            return None;
+        }
+
+        let (authority, uri) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        if !uri.is_empty() && uri != "/" {
+            self.report(
+                directive,
+                context,
+                format!("The URI \"{}\" is dropped, requests are forwarded with their original path.", uri)
+            );
+        }
+
+        let (host, port) = match split_host_port(authority) {
+            Some(parts) => parts,
+            None => {
+                self.report(directive, context, format!("\"{}\" is not a valid host and port.", authority));
+                return None;
+            }
+        };
+
+        Some(ProxyTarget { https, host, port })
+    }
+
+    fn location(&mut self, directive: &'a Directive, domain: &str, path_rules: &mut Vec<PathRule>) -> Option<LocationSummary<'a>> {
+        let (match_type, path) = match directive.args.as_slice() {
+            [path] if !path.starts_with('@') => (MatchType::StartsWith, path.clone()),
+            [modifier, path] if modifier == "^~" => (MatchType::StartsWith, path.clone()),
+            [modifier, path] if modifier == "=" => (MatchType::Equals, path.clone()),
+            [modifier, _] if modifier == "~" || modifier == "~*" => {
+                self.report(directive, domain, "Regular expression locations cannot be expressed as path rules.");
+                return None;
+            }
+            _ => {
+                self.report(directive, domain, "This location cannot be translated.");
+                return None;
+            }
+        };
+
+        let mut summary = LocationSummary { target: None, rule_type: RuleType::Whitelist, max_age_seconds: None };
+        let mut cache_valid = None;
+
+        for child in directive.children() {
+            match (child.name.as_str(), child.args.as_slice()) {
+                ("proxy_pass", _) => summary.target = self.parse_proxy_pass(child, domain).map(|target| (target, child)),
+                ("deny", [who]) if who == "all" => summary.rule_type = RuleType::Blacklist,
+                ("return", [code, ..]) if matches!(code.as_str(), "403" | "404" | "410" | "444") => summary.rule_type = RuleType::Blacklist,
+                ("expires", [value]) => match value.as_str() {
+                    "off" | "epoch" => summary.max_age_seconds = Some(0),
+                    "max" => summary.max_age_seconds = Some(MAX_EXPIRES_SECONDS),
+                    value => match parse_duration(value.trim_start_matches(['+', '-'])) {
+                        Some(seconds) if !value.starts_with('-') => summary.max_age_seconds = Some(seconds),
+                        Some(_) => summary.max_age_seconds = Some(0),
+                        None => self.report(child, domain, "Only fixed expiry times can be translated.")
+                    }
+                },
+                ("proxy_cache_valid", [.., time]) => match parse_duration(time) {
+                    Some(seconds) => cache_valid = Some(seconds),
+                    None => self.report(child, domain, format!("\"{}\" is not a valid time.", time))
+                },
+                ("gzip", _) | ("access_log", _) => self.report(child, domain, "arc2proxy only sets this per domain, not per path."),
+                _ => self.report(child, domain, "This directive has no arc2proxy equivalent.")
+            }
+        }
+
+        if summary.max_age_seconds.is_none() {
+            summary.max_age_seconds = cache_valid;
+        }
+
+        if path != "/" || match_type != MatchType::StartsWith {
+            path_rules.push(PathRule {
+                max_age_seconds: summary.max_age_seconds.unwrap_or(0),
+                path,
+                match_type,
+                rule_type: summary.rule_type.clone()
+            });
+        }
+
+        Some(summary)
+    }
+
+    // `if ($http_user_agent ~* (bot|spider)) { return 403; }` becomes disallowed user agents
+    // as long as the pattern is a list of plain words.
+    fn user_agent_block(&mut self, directive: &Directive, domain: &str, agents: &mut Vec<UserAgentRule>) {
+        let denies = matches!(
+            directive.children(),
+            [child] if child.name == "return" && child.args.first().is_some_and(|code| matches!(code.as_str(), "403" | "404" | "444"))
+        );
+
+        // The parentheses end up in the words next to them, or on their own around quotes.
+        let args: Vec<&str> = directive.args.iter().map(String::as_str).filter(|arg| *arg != "(" && *arg != ")").collect();
+
+        let rules = match args.as_slice() {
+            [variable, operator, pattern] if variable.trim_start_matches('(') == "$http_user_agent" => Some((*operator, pattern.trim_end_matches(')'))),
+            _ => None
+        }
+        .and_then(|(operator, pattern)| match operator {
+            "=" => Some(vec![UserAgentRule { user_agent: pattern.into(), match_type: MatchType::Equals }]),
+            "~" | "~*" => {
+                let words: Vec<&str> = pattern.trim_start_matches('(').trim_end_matches(')').split('|').collect();
+                let plain = words.iter().all(|word| !word.is_empty() && word.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ' ' | '/')));
+                plain.then(|| {
+                    words
+                        .into_iter()
+                        .map(|word| UserAgentRule { user_agent: word.into(), match_type: MatchType::Contains })
+                        .collect()
+                })
+            }
+            _ => None
+        });
+
+        match rules {
+            Some(rules) if denies => agents.extend(rules),
+            _ => self.report(directive, domain, "Only if blocks that deny plain user agent patterns can be translated.")
+        }
+    }
+
+    fn routing_rule(&mut self, upstream: &Directive, https: bool) -> RoutingRule {
+        let name = format!("upstream {}", upstream.args[0]);
+        let mut method = None;
+        let mut servers = Vec::new();
+
+        for child in upstream.children() {
+            match child.name.as_str() {
+                "server" => {
+                    let Some(address) = child.args.first() else {
+                        self.report(child, &name, "A server needs an address.");
+                        continue;
+                    };
+                    if address.starts_with("unix:") {
+                        self.report(child, &name, "UNIX socket servers are not supported by arc2proxy.");
+                        continue;
+                    }
+                    let Some((host, port)) = split_host_port(address) else {
+                        self.report(child, &name, format!("\"{}\" is not a valid host and port.", address));
+                        continue;
+                    };
+
+                    let (mut backup, mut down) = (false, false);
+                    for parameter in &child.args[1..] {
+                        match parameter.split_once('=').unwrap_or((parameter.as_str(), "")) {
+                            ("backup", _) => backup = true,
+                            ("down", _) => down = true,
+                            ("weight", "1") | ("max_fails", _) | ("fail_timeout", _) => {}
+                            _ => self.report(child, &name, format!("The server parameter \"{}\" is not supported by routing locations.", parameter))
+                        }
+                    }
+
+                    if down {
+                        self.report(child, &name, "Servers marked as down are left out.");
+                        continue;
+                    }
+
+                    // nginx uses port 80 for upstream servers whatever the scheme.
+                    servers.push((host, port.unwrap_or(80), backup, child));
+                }
+                "least_conn" | "least_time" => method = Some(RoutingMethod::Performance),
+                "keepalive" | "keepalive_timeout" | "keepalive_requests" | "zone" => {}
+                _ => self.report(child, &name, "This directive has no arc2proxy equivalent.")
+            }
+        }
+
+        let has_backups = servers.iter().any(|(_, _, backup, _)| *backup);
+        let routing_method = method.unwrap_or(if has_backups || servers.len() < 2 { RoutingMethod::Priority } else { RoutingMethod::Weighted });
+
+        if routing_method != RoutingMethod::Priority {
+            for (_, _, _, server) in servers.iter().filter(|(_, _, backup, _)| *backup) {
+                self.report(server, &name, "Backup servers only exist with the Priority routing method, this one is used like the others.");
+            }
+        } else if has_backups {
+            for (_, _, _, server) in servers.iter().filter(|(_, _, backup, _)| !*backup).skip(1) {
+                self.report(server, &name, "Only the first server is primary with the Priority routing method, this one becomes a fallback.");
+            }
+        }
+
+        // Backups come last so they get the lowest priorities.
+        servers.sort_by_key(|(_, _, backup, _)| *backup);
+
+        let routing_locations = servers
+            .into_iter()
+            .enumerate()
+            .map(|(index, (host, port, _, _))| {
+                let mut location = RoutingLocation {
+                    primary: None,
+                    priority: None,
+                    forward_addr: None,
+                    forward_ipv4: None,
+                    forward_ipv6: None,
+                    forward_port_http: None,
+                    forward_port_https: None
+                };
+                set_address(&host, &mut location.forward_addr, &mut location.forward_ipv4, &mut location.forward_ipv6);
+                if https {
+                    location.forward_port_https = Some(port);
+                } else {
+                    location.forward_port_http = Some(port);
+                }
+                if routing_method == RoutingMethod::Priority {
+                    location.primary = Some(index == 0);
+                    location.priority = if index == 0 { None } else { Some(index as u16) };
+                }
+                location
+            })
+            .collect();
+
+        RoutingRule {
+            routing_method,
+            routing_locations,
+            https_only: https,
+            enable_health_checks: false,
+            health_check_interval: 0,
+            health_check_path: None
+        }
+    }
+
+    fn server(&mut self, server: &'a Directive, inherited: Inherited) {
+        let mut domains = Vec::new();
+        let mut inherited = inherited;
+
+        for directive in server.children() {
+            match directive.name.as_str() {
+                "gzip" => inherited.gzip = Some(directive.args.first().is_some_and(|arg| arg == "on")),
+                "access_log" => inherited.access_log = Some(directive.args.first().is_some_and(|arg| arg != "off")),
+                _ => {}
+            }
+        }
+
+        for directive in server.children().iter().filter(|directive| directive.name == "server_name") {
+            for name in &directive.args {
+                if name == "_" || name.is_empty() {
+                    self.report(directive, "server", "Catch-all server names have no arc2proxy equivalent.");
+                } else if name.starts_with('~') || name.contains('*') || name.starts_with('.') {
+                    self.report(directive, "server", format!("\"{}\" is a wildcard or regular expression, rules need an exact domain.", name));
+                } else {
+                    domains.push(name.to_lowercase());
+                }
+            }
+        }
+
+        let context = domains.first().cloned().unwrap_or_else(|| "server".into());
+        let mut path_rules = Vec::new();
+        let mut agents = Vec::new();
+        let mut root_target = None;
+        let mut other_targets = Vec::new();
+        let mut root_max_age = None;
+        let mut root_rule_type = RuleType::Whitelist;
+
+        for directive in server.children() {
+            match directive.name.as_str() {
+                "server_name" | "gzip" | "access_log" => {}
+                "listen" => {
+                    let port = directive.args.first().and_then(|arg| arg.rsplit(':').next()).unwrap_or_default();
+                    if port != "80" && port != "443" {
+                        self.report(directive, &context, "arc2proxy listens on the ports of its global settings for every domain.");
+                    }
+                }
+                "location" => {
+                    let is_root = directive.args.len() == 1 && directive.args[0] == "/";
+                    if let Some(summary) = self.location(directive, &context, &mut path_rules) {
+                        if is_root {
+                            root_max_age = summary.max_age_seconds;
+                            root_rule_type = summary.rule_type;
+                            root_target = summary.target;
+                        } else if let Some(target) = summary.target {
+                            other_targets.push(target);
+                        }
+                    }
+                }
+                "if" => self.user_agent_block(directive, &context, &mut agents),
+                "ssl_certificate" | "ssl_certificate_key" => {
+                    self.report(directive, &context, "arc2proxy requests certificates from Let's Encrypt itself.")
+                }
+                _ => self.report(directive, &context, "This directive has no arc2proxy equivalent.")
+            }
+        }
+
+        // Without `location /` the first proxied location stands in for the whole domain.
+        let target = match root_target {
+            Some(target) => Some(target),
+            None if !other_targets.is_empty() => Some(other_targets.remove(0)),
+            None => None
+        };
+
+        let Some((target, _)) = target else {
+            self.report(server, &context, "The server block has no proxy_pass, so no rule was created for it.");
+            return;
+        };
+
+        for (other, directive) in other_targets {
+            if other != target {
+                self.report(directive, &context, "A rule forwards to one target, this location's requests go to the domain's target instead.");
+            }
+        }
+
+        let routing_rules = match self.upstreams.get(target.host.as_str()).copied() {
+            Some(upstream) => Some(match self.routing_rules.get(&(target.host.as_str(), target.https)) {
+                Some(routing_rule) => routing_rule.clone(),
+                None => {
+                    let routing_rule = self.routing_rule(upstream, target.https);
+                    self.routing_rules.insert((upstream.args[0].as_str(), target.https), routing_rule.clone());
+                    routing_rule
+                }
+            }),
+            None => None
+        };
+
+        for domain in domains {
+            if let Some(index) = self.rules.iter().position(|rule| rule.domain == domain) {
+                let line = self.rule_lines[index];
+                self.report(server, &domain, format!("A rule for {} was already imported from the server block on line {}.", domain, line));
+                continue;
+            }
+
+            let mut rule = empty_rule(domain);
+            rule.max_age_seconds = root_max_age.unwrap_or(0);
+            rule.rule_type = root_rule_type.clone();
+            rule.enable_logging = inherited.access_log.unwrap_or(false);
+            rule.enable_compression = inherited.gzip.unwrap_or(false);
+            rule.path_rules = if path_rules.is_empty() { None } else { Some(path_rules.clone()) };
+            rule.disallowed_user_agents = if agents.is_empty() { None } else { Some(agents.clone()) };
+
+            match &routing_rules {
+                Some(routing_rules) => rule.routing_rules = Some(routing_rules.clone()),
+                None => {
+                    set_address(&target.host, &mut rule.forward_addr, &mut rule.forward_ipv4, &mut rule.forward_ipv6);
+                    if target.https {
+                        rule.forward_port_https = Some(target.port.unwrap_or(443));
+                    } else {
+                        rule.forward_port_http = Some(target.port.unwrap_or(80));
+                    }
+                }
+            }
+
+            self.rules.push(rule);
+            self.rule_lines.push(server.line);
+        }
+    }
+}
+
+// Splits `host:port`, `host` and `[v6]:port`.
+fn split_host_port(authority: &str) -> Option<(String, Option<u16>)> {
+    let (host, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+        let (host, rest) = bracketed.split_once(']')?;
+        (host, rest.strip_prefix(':'))
+    } else {
+        match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None)
+        }
+    };
+
+    if host.is_empty() {
+        return None;
+    }
+
+    let port = match port {
+        Some(port) => Some(port.parse().ok()?),
+        None => None
+    };
+
+    Some((host.to_string(), port))
+}
+
+fn set_address(host: &str, addr: &mut Option<String>, ipv4: &mut Option<String>, ipv6: &mut Option<String>) {
+    if host.parse::<Ipv4Addr>().is_ok() {
+        *ipv4 = Some(host.into());
+    } else if host.parse::<Ipv6Addr>().is_ok() {
+        *ipv6 = Some(host.into());
+    } else {
+        *addr = Some(host.into());
+    }
+}
+
+fn empty_rule(domain: String) -> ProxyRuleInner {
+    ProxyRuleInner {
+        domain,
+        max_age_seconds: 0,
+        paths: None,
+        forward_addr: None,
+        forward_ipv4: None,
+        forward_ipv6: None,
+        forward_port_http: None,
+        forward_port_https: None,
+        rule_type: RuleType::Whitelist,
+        enable_logging: false,
+        path_rules: None,
+        routing_rules: None,
+        ignore_query_string: false,
+        enable_sql_injection_protection: false,
+        disallowed_user_agents: None,
+        enable_compression: false,
+        compression_flags: None,
+        enable_minification: false,
+        minification_flags: None,
+        enable_webp_transformation: false,
+        webp_transformation_min_age: None,
+        manual_certificate: None
+    }
+}
+
+// Turns the `server` blocks of an nginx configuration into proxy rules, one per server name.
+// `proxy_pass` decides where a rule forwards to, pointing it at an `upstream` gives it routing
+// locations, and `location` blocks become path rules. Anything else ends up in `untranslated`.
+pub fn import(text: &str) -> Result<NginxImport, ConfigError> {
+    let directives = parse(text)?;
+
+    let mut importer = Importer {
+        upstreams: HashMap::new(),
+        routing_rules: HashMap::new(),
+        rules: Vec::new(),
+        rule_lines: Vec::new(),
+        untranslated: Vec::new()
+    };
+
+    importer.collect_upstreams(&directives);
+    importer.walk(&directives, "main", Inherited::default());
+    importer.untranslated.sort_by_key(|untranslated| untranslated.line);
+
+    Ok(NginxImport {
+        rules: importer.rules,
+        untranslated: importer.untranslated
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reasons(import: &NginxImport) -> Vec<(usize, &str, &str)> {
+        import
+            .untranslated
+            .iter()
+            .map(|untranslated| (untranslated.line, untranslated.directive.as_str(), untranslated.reason.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn imports_one_rule_per_server_name() {
+        let import = import(
+            r#"
+http {
+    gzip on;
+
+    server {
+        listen 80;
+        listen 443 ssl;
+        server_name example.com WWW.example.com;
+        access_log /var/log/nginx/example.log;
+
+        location / {
+            proxy_pass http://127.0.0.1:3000;
+            expires 1h;
+        }
+    }
+}
+"#
+        )
+        .unwrap();
+
+        assert_eq!(import.untranslated, Vec::new());
+        assert_eq!(import.rules.len(), 2);
+        assert_eq!(import.rules[0].domain, "example.com");
+        assert_eq!(import.rules[1].domain, "www.example.com");
+
+        for rule in &import.rules {
+            assert_eq!(rule.forward_ipv4.as_deref(), Some("127.0.0.1"));
+            assert_eq!(rule.forward_port_http, Some(3000));
+            assert_eq!(rule.max_age_seconds, 3_600);
+            assert!(rule.enable_logging);
+            assert!(rule.enable_compression);
+            assert_eq!(rule.path_rules, None);
+        }
+    }
+
+    #[test]
+    fn translates_proxy_pass_targets() {
+        let import = import(
+            r#"
+server { server_name a.com; location / { proxy_pass https://backend.internal; } }
+server { server_name b.com; location / { proxy_pass http://backend.internal/; } }
+server { server_name c.com; location / { proxy_pass https://[::1]:8443; } }
+server { server_name d.com; location / { proxy_pass http://10.0.0.4:8080/app/; } }
+"#
+        )
+        .unwrap();
+
+        let targets: Vec<_> = import
+            .rules
+            .iter()
+            .map(|rule| (rule.domain.as_str(), rule.forward_addr.clone().or(rule.forward_ipv4.clone()).or(rule.forward_ipv6.clone()), rule.forward_port_http, rule.forward_port_https))
+            .collect();
+        assert_eq!(targets, vec![
+            ("a.com", Some("backend.internal".to_string()), None, Some(443)),
+            ("b.com", Some("backend.internal".to_string()), Some(80), None),
+            ("c.com", Some("::1".to_string()), None, Some(8443)),
+            ("d.com", Some("10.0.0.4".to_string()), Some(8080), None),
+        ]);
+        assert_eq!(reasons(&import), vec![(
+            5,
+            "proxy_pass http://10.0.0.4:8080/app/",
+            "The URI \"/app/\" is dropped, requests are forwarded with their original path."
+        )]);
+    }
+
+    #[test]
+    fn turns_upstreams_into_routing_rules() {
+        let import = import(
+            r#"
+upstream app {
+    server 10.0.0.1:8080 weight=5;
+    server 10.0.0.2:8080;
+    server backup.internal:8080 backup;
+    keepalive 16;
+}
+
+upstream pool {
+    least_conn;
+    server 10.0.1.1;
+    server 10.0.1.2;
+}
+
+server {
+    server_name app.com;
+    location / { proxy_pass http://app; }
+}
+
+server {
+    server_name pool.com;
+    location / { proxy_pass https://pool; }
+}
+"#
+        )
+        .unwrap();
+
+        let app = import.rules[0].routing_rules.as_ref().unwrap();
+        assert_eq!(app.routing_method, RoutingMethod::Priority);
+        assert!(!app.https_only);
+        let locations: Vec<_> = app
+            .routing_locations
+            .iter()
+            .map(|location| (location.forward_addr.clone().or(location.forward_ipv4.clone()), location.forward_port_http, location.primary, location.priority))
+            .collect();
+        assert_eq!(locations, vec![
+            (Some("10.0.0.1".to_string()), Some(8080), Some(true), None),
+            (Some("10.0.0.2".to_string()), Some(8080), Some(false), Some(1)),
+            (Some("backup.internal".to_string()), Some(8080), Some(false), Some(2)),
+        ]);
+        assert!(!app.validate("routing_rules").has_errors());
+
+        let pool = import.rules[1].routing_rules.as_ref().unwrap();
+        assert_eq!(pool.routing_method, RoutingMethod::Performance);
+        assert!(pool.https_only);
+        assert!(pool.routing_locations.iter().all(|location| location.forward_port_https == Some(80) && location.primary.is_none()));
+
+        assert_eq!(reasons(&import), vec![
+            (3, "server 10.0.0.1:8080 weight=5", "The server parameter \"weight=5\" is not supported by routing locations."),
+            (4, "server 10.0.0.2:8080", "Only the first server is primary with the Priority routing method, this one becomes a fallback."),
+        ]);
+    }
+
+    #[test]
+    fn translates_location_modifiers_to_path_rules() {
+        let import = import(
+            r#"
+server {
+    server_name example.com;
+
+    location / { proxy_pass http://127.0.0.1:3000; }
+    location = /login { proxy_pass http://127.0.0.1:3000; }
+    location ^~ /static/ { expires 30d; }
+    location /admin { deny all; }
+    location ~ \.php$ { return 403; }
+    location ~* \.(png|jpg)$ { expires max; }
+}
+"#
+        )
+        .unwrap();
+
+        let path_rules: Vec<_> = import.rules[0]
+            .path_rules
+            .iter()
+            .flatten()
+            .map(|path_rule| (path_rule.match_type.clone(), path_rule.path.as_str(), path_rule.rule_type.clone(), path_rule.max_age_seconds))
+            .collect();
+        assert_eq!(path_rules, vec![
+            (MatchType::Equals, "/login", RuleType::Whitelist, 0),
+            (MatchType::StartsWith, "/static/", RuleType::Whitelist, 2_592_000),
+            (MatchType::StartsWith, "/admin", RuleType::Blacklist, 0),
+        ]);
+
+        let regex_reason = "Regular expression locations cannot be expressed as path rules.";
+        assert_eq!(reasons(&import), vec![
+            (9, "location ~ \\.php$", regex_reason),
+            (10, "location ~* \\.(png|jpg)$", regex_reason),
+        ]);
+    }
+
+    #[test]
+    fn reads_quoted_strings_and_comments() {
+        let import = import(
+            r#"
+# server { this whole line is a comment
+server {
+    server_name "quoted.com" 'single.com'; # trailing comment with ; and }
+    if ($http_user_agent ~* "(BadBot|evil spider)") {
+        return 403;
+    }
+    location "/a b" { proxy_pass "http://127.0.0.1:3000"; }
+    add_header X-Note "say \"hi\"";
+}
+"#
+        )
+        .unwrap();
+
+        let domains: Vec<&str> = import.rules.iter().map(|rule| rule.domain.as_str()).collect();
+        assert_eq!(domains, vec!["quoted.com", "single.com"]);
+
+        let agents: Vec<&str> = import.rules[0].disallowed_user_agents.iter().flatten().map(|agent| agent.user_agent.as_str()).collect();
+        assert_eq!(agents, vec!["BadBot", "evil spider"]);
+        assert_eq!(import.rules[0].forward_port_http, Some(3000));
+        assert_eq!(import.rules[0].path_rules.as_ref().unwrap()[0].path, "/a b");
+
+        assert_eq!(reasons(&import), vec![(9, "add_header X-Note say \"hi\"", "This directive has no arc2proxy equivalent.")]);
+    }
+
+    #[test]
+    fn rejects_unbalanced_blocks_without_panicking() {
+        let broken = [
+            ("server { server_name a.com;", 1, "This block is never closed."),
+            ("server { }\n}", 2, "Unexpected \"}\"."),
+            ("server {\n  server_name a.com\n}", 2, "\"server_name\" is missing a \";\"."),
+            ("server_name a.com", 1, "\"server_name\" is missing a \";\"."),
+            ("server { server_name \"a.com; }", 1, "Unterminated quoted string."),
+            ("{ }", 1, "A block needs a directive name before \"{\"."),
+            (";", 1, "Unexpected \";\"."),
+        ];
+
+        for (text, expected_line, expected_message) in broken {
+            match import(text) {
+                Err(ConfigError::Parse { line, message, .. }) => {
+                    assert_eq!((line, message.as_str()), (Some(expected_line), expected_message), "{}", text);
+                }
+                other => panic!("{} should not parse: {:?}", text, other)
+            }
+        }
+    }
+
+    #[test]
+    fn reports_untranslated_directives() {
+        let import = import(
+            r#"
+include /etc/nginx/conf.d/*.conf;
+worker_processes auto;
+
+server {
+    listen 8080;
+    server_name _ *.wild.com real.com;
+    ssl_certificate /etc/ssl/real.pem;
+    location / {
+        proxy_pass http://127.0.0.1:3000;
+        proxy_set_header Host $host;
+        gzip on;
+    }
+    location /ws { proxy_pass http://127.0.0.1:4000; }
+}
+
+server {
+    server_name static.com;
+    root /var/www;
+}
+"#
+        )
+        .unwrap();
+
+        assert_eq!(import.rules.len(), 1);
+        assert_eq!(import.rules[0].domain, "real.com");
+
+        let report: Vec<_> = import
+            .untranslated
+            .iter()
+            .map(|untranslated| (untranslated.line, untranslated.context.as_str(), untranslated.reason.as_str()))
+            .collect();
+        assert_eq!(report, vec![
+            (2, "main", "Included files are not followed, import them separately."),
+            (3, "main", "This directive has no arc2proxy equivalent."),
+            (6, "real.com", "arc2proxy listens on the ports of its global settings for every domain."),
+            (7, "server", "Catch-all server names have no arc2proxy equivalent."),
+            (7, "server", "\"*.wild.com\" is a wildcard or regular expression, rules need an exact domain."),
+            (8, "real.com", "arc2proxy requests certificates from Let's Encrypt itself."),
+            (11, "real.com", "This directive has no arc2proxy equivalent."),
+            (12, "real.com", "arc2proxy only sets this per domain, not per path."),
+            (14, "real.com", "A rule forwards to one target, this location's requests go to the domain's target instead."),
+            (17, "static.com", "The server block has no proxy_pass, so no rule was created for it."),
+            (19, "static.com", "This directive has no arc2proxy equivalent."),
+        ]);
+    }
+}
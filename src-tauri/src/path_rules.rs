@@ -0,0 +1,230 @@
+use crate::{
+    error::ConfigError,
+    models::{MatchType, PathRule, ProxyConfiguration},
+    rules,
+};
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    // Both rules match exactly the same paths the same way.
+    Duplicate,
+    // Every path of one rule is also matched by a rule with the other rule type.
+    Contradiction,
+    // Every path of one rule is also matched by a rule with another max_age_seconds.
+    MaxAgeMismatch
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathRuleConflict {
+    pub first: usize,
+    pub second: usize,
+    pub kind: ConflictKind,
+    pub message: String
+}
+
+pub fn match_path(match_type: &MatchType, pattern: &str, value: &str) -> bool {
+    match match_type {
+        MatchType::Contains => value.contains(pattern),
+        MatchType::Equals => value == pattern,
+        MatchType::StartsWith => value.starts_with(pattern),
+        MatchType::EndsWith => value.ends_with(pattern),
+        MatchType::DoesNotContain => !value.contains(pattern),
+        MatchType::DoesNotEqual => value != pattern
+    }
+}
+
+// Whether every path matched by `a` is also matched by `b`. Only answers yes when that is
+// certain, so partially overlapping rules are not reported.
+fn covers(a: &PathRule, b: &PathRule) -> bool {
+    use MatchType::*;
+
+    let (p, q) = (a.path.as_str(), b.path.as_str());
+
+    match (&a.match_type, &b.match_type) {
+        (Equals, _) => match_path(&b.match_type, q, p),
+        (StartsWith, StartsWith) => p.starts_with(q),
+        (EndsWith, EndsWith) => p.ends_with(q),
+        (StartsWith | EndsWith | Contains, Contains) => p.contains(q),
+        (StartsWith | EndsWith | Contains, DoesNotEqual) => !match_path(&a.match_type, p, q),
+        (DoesNotContain, DoesNotContain) => q.contains(p),
+        (DoesNotContain, DoesNotEqual) => q.contains(p),
+        (DoesNotEqual, DoesNotEqual) => p == q,
+        _ => false
+    }
+}
+
+fn describe(index: usize, rule: &PathRule) -> String {
+    format!("#{} ({:?} {:?} \"{}\")", index + 1, rule.rule_type, rule.match_type, rule.path)
+}
+
+// Pairs of path rules that contradict each other. The earlier rule of a pair is `first`.
+pub fn analyze(path_rules: &[PathRule]) -> Vec<PathRuleConflict> {
+    let mut conflicts = Vec::new();
+
+    for (first, a) in path_rules.iter().enumerate() {
+        for (second, b) in path_rules.iter().enumerate().skip(first + 1) {
+            let a_in_b = covers(a, b);
+            let b_in_a = covers(b, a);
+
+            if !a_in_b && !b_in_a {
+                continue;
+            }
+
+            let relation = match (a_in_b, b_in_a) {
+                (true, true) => format!("{} and {} match the same paths", describe(first, a), describe(second, b)),
+                (true, false) => format!("every path of {} is also matched by {}", describe(first, a), describe(second, b)),
+                _ => format!("every path of {} is also matched by {}", describe(second, b), describe(first, a))
+            };
+
+            let conflict = if a.rule_type != b.rule_type {
+                Some((ConflictKind::Contradiction, format!("{} with the opposite rule type.", capitalize(&relation))))
+            } else if a.max_age_seconds != b.max_age_seconds {
+                Some((
+                    ConflictKind::MaxAgeMismatch,
+                    format!(
+                        "{} with a different max_age_seconds ({} and {}).",
+                        capitalize(&relation),
+                        a.max_age_seconds,
+                        b.max_age_seconds
+                    )
+                ))
+            } else if a_in_b && b_in_a {
+                Some((ConflictKind::Duplicate, format!("{}.", capitalize(&relation))))
+            } else {
+                None
+            };
+
+            if let Some((kind, message)) = conflict {
+                conflicts.push(PathRuleConflict { first, second, kind, message });
+            }
+        }
+    }
+
+    conflicts
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new()
+    }
+}
+
+// Runs `edit` on the path rules of `domain`, dropping the list once it is empty.
+fn edit_path_rules<T, F>(config: &mut ProxyConfiguration, domain: &str, edit: F) -> Result<T, ConfigError>
+where
+    F: FnOnce(&mut Vec<PathRule>) -> Result<T, ConfigError>
+{
+    rules::edit_rule(config, domain, |rule| {
+        let mut path_rules = rule.path_rules.take().unwrap_or_default();
+        let result = edit(&mut path_rules);
+        rule.path_rules = if path_rules.is_empty() { None } else { Some(path_rules) };
+        result
+    })
+}
+
+pub fn path_rules_of<'a>(config: &'a ProxyConfiguration, domain: &str) -> Result<&'a [PathRule], ConfigError> {
+    let index = rules::find_rule_index(config, domain)?;
+    Ok(config.proxy_rules[index].path_rules.as_deref().unwrap_or_default())
+}
+
+pub fn add_path_rule(config: &mut ProxyConfiguration, domain: &str, path_rule: PathRule) -> Result<(), ConfigError> {
+    edit_path_rules(config, domain, |path_rules| {
+        path_rules.push(path_rule);
+        Ok(())
+    })
+}
+
+pub fn update_path_rule(config: &mut ProxyConfiguration, domain: &str, index: usize, path_rule: PathRule) -> Result<(), ConfigError> {
+    edit_path_rules(config, domain, |path_rules| {
+        rules::check_index(path_rules, index, "path_rules", "path rule")?;
+        path_rules[index] = path_rule;
+        Ok(())
+    })
+}
+
+pub fn delete_path_rule(config: &mut ProxyConfiguration, domain: &str, index: usize) -> Result<PathRule, ConfigError> {
+    edit_path_rules(config, domain, |path_rules| {
+        rules::check_index(path_rules, index, "path_rules", "path rule")?;
+        Ok(path_rules.remove(index))
+    })
+}
+
+// `order` lists the current indices in their new order.
+pub fn reorder_path_rules(config: &mut ProxyConfiguration, domain: &str, order: &[usize]) -> Result<(), ConfigError> {
+    edit_path_rules(config, domain, |path_rules| rules::reorder_items(path_rules, order, "path_rules", "path rule"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::RuleType;
+
+    use super::*;
+
+    fn path_rule(rule_type: RuleType, match_type: MatchType, path: &str, max_age_seconds: u64) -> PathRule {
+        PathRule { max_age_seconds, path: path.into(), match_type, rule_type }
+    }
+
+    #[test]
+    fn reports_whitelist_shadowed_by_blacklist() {
+        let conflicts = analyze(&[
+            path_rule(RuleType::Whitelist, MatchType::StartsWith, "/api", 0),
+            path_rule(RuleType::Blacklist, MatchType::Contains, "api", 0),
+        ]);
+
+        assert_eq!(conflicts, vec![PathRuleConflict {
+            first: 0,
+            second: 1,
+            kind: ConflictKind::Contradiction,
+            message: "Every path of #1 (Whitelist StartsWith \"/api\") is also matched by #2 (Blacklist Contains \"api\") with the opposite rule type.".into()
+        }]);
+    }
+
+    #[test]
+    fn reports_different_max_age_on_the_same_path() {
+        let conflicts = analyze(&[
+            path_rule(RuleType::Whitelist, MatchType::Equals, "/static/app.js", 60),
+            path_rule(RuleType::Whitelist, MatchType::Equals, "/static/app.js", 3600),
+        ]);
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kind, ConflictKind::MaxAgeMismatch);
+        assert_eq!(
+            conflicts[0].message,
+            "#1 (Whitelist Equals \"/static/app.js\") and #2 (Whitelist Equals \"/static/app.js\") match the same paths with a different max_age_seconds (60 and 3600)."
+        );
+    }
+
+    #[test]
+    fn reports_duplicates_but_not_partial_overlaps() {
+        let conflicts = analyze(&[
+            path_rule(RuleType::Whitelist, MatchType::StartsWith, "/api", 0),
+            path_rule(RuleType::Whitelist, MatchType::StartsWith, "/api", 0),
+            path_rule(RuleType::Blacklist, MatchType::EndsWith, ".json", 0),
+        ]);
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!((conflicts[0].first, conflicts[0].second, conflicts[0].kind), (0, 1, ConflictKind::Duplicate));
+
+        // Narrower rules with the same outcome are harmless.
+        assert!(analyze(&[
+            path_rule(RuleType::Blacklist, MatchType::StartsWith, "/admin/users", 0),
+            path_rule(RuleType::Blacklist, MatchType::StartsWith, "/admin", 0),
+        ])
+        .is_empty());
+    }
+
+    #[test]
+    fn understands_negated_match_types() {
+        let conflicts = analyze(&[
+            path_rule(RuleType::Whitelist, MatchType::DoesNotContain, "admin", 0),
+            path_rule(RuleType::Blacklist, MatchType::DoesNotEqual, "/admin", 0),
+        ]);
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].kind, ConflictKind::Contradiction);
+    }
+}
@@ -0,0 +1,79 @@
+use std::{env, fs, path::{Path, PathBuf}};
+
+use crate::{error::ConfigError, location, storage};
+
+pub const DEFAULT_MAX_BACKUPS: usize = 20;
+pub const PREFERENCES_FILE_NAME: &str = "preferences.json";
+// Must match `identifier` in tauri.conf.json, Tauri names the app config directory after it.
+pub const APP_IDENTIFIER: &str = "com.arc2proxy-manager.app";
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preferences {
+    pub config_path: Option<PathBuf>,
+    pub max_backups: Option<usize>,
+    pub certificate_dir: Option<PathBuf>
+}
+
+impl Preferences {
+    pub fn max_backups(&self) -> usize {
+        self.max_backups.unwrap_or(DEFAULT_MAX_BACKUPS)
+    }
+}
+
+// Where Tauri's app_config_dir puts preferences.json, for when the CLI runs without Tauri.
+pub fn default_preferences_path() -> Option<PathBuf> {
+    let config_dir = if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library").join("Application Support"))
+    } else {
+        location::user_config_dir()
+    };
+
+    config_dir.map(|dir| dir.join(APP_IDENTIFIER).join(PREFERENCES_FILE_NAME))
+}
+
+// GUI preferences live in preferences.json in the app config directory. Without a path
+// (e.g. in tests) nothing is persisted and the defaults are used.
+pub struct PreferenceStore {
+    path: Option<PathBuf>
+}
+
+impl PreferenceStore {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        PreferenceStore { path }
+    }
+
+    // The app config directory, other private app files are kept next to preferences.json.
+    pub fn dir(&self) -> Option<&Path> {
+        self.path.as_deref().and_then(Path::parent)
+    }
+
+    pub fn load(&self) -> Preferences {
+        self.path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, preferences: &Preferences) -> Result<(), ConfigError> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(())
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| ConfigError::io(parent, e))?;
+        }
+
+        let json = serde_json::to_string_pretty(preferences).map_err(|e| ConfigError::Serialize { message: e.to_string() })?;
+        storage::write_atomic(path, json.as_bytes()).map_err(|e| ConfigError::io(path, e))
+    }
+
+    pub fn update<F: FnOnce(&mut Preferences)>(&self, change: F) -> Result<Preferences, ConfigError> {
+        let mut preferences = self.load();
+        change(&mut preferences);
+        self.save(&preferences)?;
+        Ok(preferences)
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use rustls::{pki_types::ServerName, ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+
+use crate::{
+    error::ConfigError,
+    models::{ProxyConfiguration, RoutingLocation, RoutingRule},
+    rules,
+};
+
+// Same default as the proxy itself.
+pub const DEFAULT_PROXY_TIMEOUT_SECONDS: u16 = 45;
+const DEFAULT_HEALTH_CHECK_PATH: &str = "/";
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsResult {
+    pub ok: bool,
+    pub version: Option<String>,
+    pub error: Option<String>
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeResult {
+    pub index: usize,
+    pub url: String,
+    pub status: Option<u16>,
+    pub latency_ms: Option<u64>,
+    // None for plain HTTP probes.
+    pub tls: Option<TlsResult>,
+    pub error: Option<String>,
+    pub healthy: bool
+}
+
+struct Target {
+    https: bool,
+    host: String,
+    port: u16
+}
+
+// https_only locations are probed over HTTPS, everything else over plain HTTP, the same
+// way the proxy forwards to them.
+fn target_of(location: &RoutingLocation, https_only: bool) -> Result<Target, String> {
+    let host = location
+        .forward_addr
+        .clone()
+        .or_else(|| location.forward_ipv4.clone())
+        .or_else(|| location.forward_ipv6.clone())
+        .ok_or("The location has no address.")?;
+
+    let (https, port) = if https_only {
+        (true, location.forward_port_https.ok_or("The location has no HTTPS port.")?)
+    } else {
+        (false, location.forward_port_http.ok_or("The location has no HTTP port.")?)
+    };
+
+    Ok(Target { https, host, port })
+}
+
+fn url_of(target: &Target, path: &str) -> String {
+    let host = if target.host.contains(':') { format!("[{}]", target.host) } else { target.host.clone() };
+    format!("{}://{}:{}{}", if target.https { "https" } else { "http" }, host, target.port, path)
+}
+
+fn resolve(host: &str, port: u16) -> Result<SocketAddr, String> {
+    (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Could not resolve {}: {}", host, e))?
+        .next()
+        .ok_or_else(|| format!("Could not resolve {}.", host))
+}
+
+// `timeout` applies to the connection and to every read and write on it.
+pub(crate) fn connect(host: &str, port: u16, timeout: Duration) -> Result<TcpStream, String> {
+    let address = resolve(host, port)?;
+    let stream = TcpStream::connect_timeout(&address, timeout).map_err(|e| format!("Could not connect to {}: {}", address, e))?;
+    stream.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    Ok(stream)
+}
+
+fn tls_config() -> Result<Arc<ClientConfig>, String> {
+    let roots = RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+
+    ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map(|builder| Arc::new(builder.with_root_certificates(roots).with_no_client_auth()))
+        .map_err(|e| e.to_string())
+}
+
+// Sends the request and returns the status code from the response line.
+fn exchange<S: Read + Write>(stream: &mut S, domain: &str, path: &str) -> Result<u16, String> {
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: arc2proxy-gui health check\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+        path, domain
+    );
+    stream.write_all(request.as_bytes()).map_err(|e| e.to_string())?;
+    stream.flush().map_err(|e| e.to_string())?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line).map_err(|e| e.to_string())?;
+
+    let mut parts = status_line.split_whitespace();
+    match (parts.next(), parts.next().and_then(|code| code.parse::<u16>().ok())) {
+        (Some(version), Some(code)) if version.starts_with("HTTP/") => Ok(code),
+        _ if status_line.is_empty() => Err("The connection was closed without a response.".into()),
+        _ => Err(format!("Unexpected response: {}", status_line.trim()))
+    }
+}
+
+// Probes a single location. `domain` is used for the Host header and as the TLS server
+// name, since that is what the backend serves to the proxy.
+pub fn probe_location(index: usize, location: &RoutingLocation, routing_rule: &RoutingRule, domain: &str, timeout: Duration) -> ProbeResult {
+    let path = routing_rule
+        .health_check_path
+        .clone()
+        .filter(|path| !path.is_empty())
+        .unwrap_or_else(|| DEFAULT_HEALTH_CHECK_PATH.into());
+
+    let mut result = ProbeResult {
+        index,
+        url: String::new(),
+        status: None,
+        latency_ms: None,
+        tls: None,
+        error: None,
+        healthy: false
+    };
+
+    let target = match target_of(location, routing_rule.https_only) {
+        Ok(target) => target,
+        Err(error) => {
+            result.error = Some(error);
+            return result;
+        }
+    };
+    result.url = url_of(&target, &path);
+
+    let started = Instant::now();
+
+    let outcome = connect(&target.host, target.port, timeout).and_then(|stream| {
+        if !target.https {
+            let mut stream = stream;
+            return exchange(&mut stream, domain, &path);
+        }
+
+        let server_name = ServerName::try_from(domain.to_string()).map_err(|e| format!("\"{}\" is not a valid TLS server name: {}", domain, e))?;
+        let mut connection = ClientConnection::new(tls_config()?, server_name).map_err(|e| e.to_string())?;
+        let mut stream = stream;
+
+        while connection.is_handshaking() {
+            if let Err(e) = connection.complete_io(&mut stream) {
+                result.tls = Some(TlsResult { ok: false, version: None, error: Some(e.to_string()) });
+                return Err(format!("TLS handshake failed: {}", e));
+            }
+        }
+
+        result.tls = Some(TlsResult {
+            ok: true,
+            version: connection.protocol_version().map(|version| format!("{:?}", version)),
+            error: None
+        });
+
+        exchange(&mut StreamOwned::new(connection, stream), domain, &path)
+    });
+
+    match outcome {
+        Ok(status) => {
+            result.status = Some(status);
+            result.latency_ms = Some(started.elapsed().as_millis() as u64);
+            result.healthy = (200..400).contains(&status);
+        }
+        Err(error) => result.error = Some(error)
+    }
+
+    result
+}
+
+// Probes every routing location of `domain` at the same time, so one slow backend does not
+// add its timeout to all the others.
+pub fn probe_routing_locations(config: &ProxyConfiguration, domain: &str) -> Result<Vec<ProbeResult>, ConfigError> {
+    let index = rules::find_rule_index(config, domain)?;
+    let rule = &config.proxy_rules[index];
+
+    let routing_rule = rule
+        .routing_rules
+        .as_ref()
+        .ok_or_else(|| ConfigError::validation("routing_rules", format!("\"{}\" has no routing locations.", rule.domain)))?;
+
+    let timeout = Duration::from_secs(config.proxy_timeout.unwrap_or(DEFAULT_PROXY_TIMEOUT_SECONDS).max(1) as u64);
+
+    Ok(thread::scope(|scope| {
+        let probes: Vec<_> = routing_rule
+            .routing_locations
+            .iter()
+            .enumerate()
+            .map(|(index, location)| scope.spawn(move || probe_location(index, location, routing_rule, &rule.domain, timeout)))
+            .collect();
+
+        probes.into_iter().map(|probe| probe.join().unwrap()).collect()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::TcpListener,
+        sync::mpsc::{self, Receiver},
+    };
+
+    use super::*;
+    use crate::models::RoutingMethod;
+
+    // Answers every connection with `response` right away and passes on the request head,
+    // so a TLS client gets its garbage reply without waiting for a request line.
+    fn mock_server(response: &'static str) -> (u16, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let _ = stream.write_all(response.as_bytes());
+                stream.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+
+                let mut request = String::new();
+                let mut reader = BufReader::new(stream);
+                while reader.read_line(&mut request).is_ok_and(|read| read > 2) {}
+                let _ = sender.send(request);
+            }
+        });
+
+        (port, receiver)
+    }
+
+    fn location(port: u16) -> RoutingLocation {
+        RoutingLocation {
+            primary: Some(true),
+            priority: None,
+            forward_addr: None,
+            forward_ipv4: Some("127.0.0.1".into()),
+            forward_ipv6: None,
+            forward_port_http: Some(port),
+            forward_port_https: Some(port)
+        }
+    }
+
+    fn routing_rule(https_only: bool, locations: Vec<RoutingLocation>) -> RoutingRule {
+        RoutingRule {
+            routing_method: RoutingMethod::Priority,
+            routing_locations: locations,
+            https_only,
+            enable_health_checks: true,
+            health_check_interval: 10,
+            health_check_path: Some("/health".into())
+        }
+    }
+
+    #[test]
+    fn reports_status_and_latency() {
+        let (healthy, requests) = mock_server("HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n");
+        let (failing, _) = mock_server("HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n");
+        let rule = routing_rule(false, vec![location(healthy), location(failing)]);
+
+        let result = probe_location(0, &rule.routing_locations[0], &rule, "example.com", Duration::from_secs(5));
+        assert_eq!(result.status, Some(204));
+        assert!(result.healthy);
+        assert!(result.latency_ms.is_some());
+        assert_eq!(result.tls, None);
+        assert_eq!(result.url, format!("http://127.0.0.1:{}/health", healthy));
+
+        let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(request.starts_with("GET /health HTTP/1.1\r\n"));
+        assert!(request.contains("Host: example.com\r\n"));
+
+        let result = probe_location(1, &rule.routing_locations[1], &rule, "example.com", Duration::from_secs(5));
+        assert_eq!(result.status, Some(503));
+        assert!(!result.healthy);
+    }
+
+    #[test]
+    fn reports_tls_handshake_failure() {
+        let (port, _) = mock_server("HTTP/1.1 200 OK\r\n\r\n");
+        let rule = routing_rule(true, vec![location(port)]);
+
+        let result = probe_location(0, &rule.routing_locations[0], &rule, "example.com", Duration::from_secs(5));
+        assert_eq!(result.status, None);
+        assert!(!result.healthy);
+        assert!(result.tls.is_some_and(|tls| !tls.ok && tls.error.is_some()));
+    }
+
+    #[test]
+    fn times_out_on_silent_backend() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let rule = routing_rule(false, vec![location(port)]);
+
+        let started = Instant::now();
+        let result = probe_location(0, &rule.routing_locations[0], &rule, "example.com", Duration::from_millis(300));
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(result.error.is_some());
+        assert!(!result.healthy);
+        drop(listener);
+    }
+
+    #[test]
+    fn reports_refused_connection() {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let rule = routing_rule(false, vec![location(port)]);
+
+        let result = probe_location(0, &rule.routing_locations[0], &rule, "example.com", Duration::from_secs(5));
+        assert!(result.error.is_some_and(|error| error.starts_with("Could not connect")));
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{
+    error::ConfigError,
+    models::{ProxyConfiguration, RoutingLocation, RoutingMethod, RoutingRule},
+    rules,
+};
+
+// Everything on a RoutingRule apart from its locations.
+#[derive(serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutingOptions {
+    pub routing_method: RoutingMethod,
+    pub https_only: bool,
+    pub enable_health_checks: bool,
+    pub health_check_interval: u32,
+    pub health_check_path: Option<String>
+}
+
+fn empty_routing_rule() -> RoutingRule {
+    RoutingRule {
+        routing_method: RoutingMethod::default(),
+        routing_locations: Vec::new(),
+        https_only: false,
+        enable_health_checks: false,
+        health_check_interval: 0,
+        health_check_path: None
+    }
+}
+
+pub fn routing_rule_of(config: &ProxyConfiguration, domain: &str) -> Result<Option<RoutingRule>, ConfigError> {
+    let index = rules::find_rule_index(config, domain)?;
+    Ok(config.proxy_rules[index].routing_rules.clone())
+}
+
+// Runs `edit` on the routing rule of `domain`, creating it on the first location and
+// removing it again with the last one.
+fn edit_routing_rule<T, F>(config: &mut ProxyConfiguration, domain: &str, edit: F) -> Result<T, ConfigError>
+where
+    F: FnOnce(&mut RoutingRule) -> Result<T, ConfigError>
+{
+    rules::edit_rule(config, domain, |rule| {
+        let mut routing_rule = rule.routing_rules.take().unwrap_or_else(empty_routing_rule);
+        let result = edit(&mut routing_rule);
+        rule.routing_rules = if routing_rule.routing_locations.is_empty() { None } else { Some(routing_rule) };
+        result
+    })
+}
+
+// The first location under Priority routing is the only candidate for primary, so it becomes it.
+pub fn add_location(config: &mut ProxyConfiguration, domain: &str, mut location: RoutingLocation) -> Result<(), ConfigError> {
+    edit_routing_rule(config, domain, |routing_rule| {
+        if routing_rule.routing_locations.is_empty() && routing_rule.routing_method == RoutingMethod::Priority {
+            location.primary = Some(true);
+        }
+        routing_rule.routing_locations.push(location);
+        Ok(())
+    })
+}
+
+pub fn update_location(config: &mut ProxyConfiguration, domain: &str, index: usize, location: RoutingLocation) -> Result<(), ConfigError> {
+    edit_routing_rule(config, domain, |routing_rule| {
+        rules::check_index(&routing_rule.routing_locations, index, "routing_locations", "routing location")?;
+        routing_rule.routing_locations[index] = location;
+        Ok(())
+    })
+}
+
+pub fn delete_location(config: &mut ProxyConfiguration, domain: &str, index: usize) -> Result<RoutingLocation, ConfigError> {
+    edit_routing_rule(config, domain, |routing_rule| {
+        rules::check_index(&routing_rule.routing_locations, index, "routing_locations", "routing location")?;
+        Ok(routing_rule.routing_locations.remove(index))
+    })
+}
+
+// `order` lists the current indices in their new order.
+pub fn reorder_locations(config: &mut ProxyConfiguration, domain: &str, order: &[usize]) -> Result<(), ConfigError> {
+    edit_routing_rule(config, domain, |routing_rule| {
+        rules::reorder_items(&mut routing_rule.routing_locations, order, "routing_locations", "routing location")
+    })
+}
+
+pub fn update_options(config: &mut ProxyConfiguration, domain: &str, options: RoutingOptions) -> Result<(), ConfigError> {
+    let index = rules::find_rule_index(config, domain)?;
+    if config.proxy_rules[index].routing_rules.is_none() {
+        return Err(ConfigError::validation("routing_rules", "Add a routing location before changing the routing options."));
+    }
+
+    edit_routing_rule(config, domain, |routing_rule| {
+        routing_rule.routing_method = options.routing_method;
+        routing_rule.https_only = options.https_only;
+        routing_rule.enable_health_checks = options.enable_health_checks;
+        routing_rule.health_check_interval = options.health_check_interval;
+        routing_rule.health_check_path = options.health_check_path.filter(|path| !path.is_empty());
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[[proxy_rules]]
+domain = "example.com"
+max_age_seconds = 0
+rule_type = "Whitelist"
+enable_logging = false
+ignore_query_string = false
+enable_sql_injection_protection = false
+enable_compression = false
+enable_minification = false
+enable_webp_transformation = false
+forward_ipv4 = "10.0.0.1"
+forward_port_http = 8080
+"#;
+
+    fn location(ipv4: &str, priority: Option<u16>) -> RoutingLocation {
+        RoutingLocation {
+            primary: None,
+            priority,
+            forward_addr: None,
+            forward_ipv4: Some(ipv4.into()),
+            forward_ipv6: None,
+            forward_port_http: Some(8080),
+            forward_port_https: None
+        }
+    }
+
+    fn addresses(config: &ProxyConfiguration) -> Vec<String> {
+        routing_rule_of(config, "example.com")
+            .unwrap()
+            .map(|rule| rule.routing_locations.into_iter().filter_map(|location| location.forward_ipv4).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn first_location_becomes_primary() {
+        let mut config: ProxyConfiguration = toml::from_str(CONFIG).unwrap();
+        add_location(&mut config, "example.com", location("10.0.0.2", None)).unwrap();
+
+        let rule = routing_rule_of(&config, "example.com").unwrap().unwrap();
+        assert_eq!(rule.routing_method, RoutingMethod::Priority);
+        assert_eq!(rule.routing_locations[0].primary, Some(true));
+        assert!(!rule.validate("routing_rules").has_errors());
+
+        add_location(&mut config, "example.com", location("10.0.0.3", Some(1))).unwrap();
+        let rule = routing_rule_of(&config, "example.com").unwrap().unwrap();
+        assert_eq!(rule.routing_locations[1].primary, None);
+    }
+
+    #[test]
+    fn rejects_locations_that_break_validation() {
+        let mut config: ProxyConfiguration = toml::from_str(CONFIG).unwrap();
+        add_location(&mut config, "example.com", location("10.0.0.2", Some(1))).unwrap();
+
+        let error = add_location(&mut config, "example.com", location("10.0.0.3", Some(1))).unwrap_err();
+        assert!(error.to_string().contains("Priority 1 is used by more than one location."));
+
+        let error = add_location(&mut config, "example.com", location("not an ip", Some(2))).unwrap_err();
+        assert!(error.to_string().contains("is not a valid IPv4 address"));
+
+        assert_eq!(addresses(&config), vec!["10.0.0.2"]);
+    }
+
+    #[test]
+    fn updates_reorders_and_removes_locations() {
+        let mut config: ProxyConfiguration = toml::from_str(CONFIG).unwrap();
+        add_location(&mut config, "example.com", location("10.0.0.1", None)).unwrap();
+        add_location(&mut config, "example.com", location("10.0.0.2", Some(1))).unwrap();
+        add_location(&mut config, "example.com", location("10.0.0.3", Some(2))).unwrap();
+
+        reorder_locations(&mut config, "example.com", &[2, 0, 1]).unwrap();
+        assert_eq!(addresses(&config), vec!["10.0.0.3", "10.0.0.1", "10.0.0.2"]);
+        assert!(reorder_locations(&mut config, "example.com", &[0, 1]).is_err());
+        assert!(reorder_locations(&mut config, "example.com", &[0, 0, 1]).is_err());
+
+        update_location(&mut config, "example.com", 0, location("10.0.0.4", Some(2))).unwrap();
+        assert_eq!(addresses(&config), vec!["10.0.0.4", "10.0.0.1", "10.0.0.2"]);
+
+        assert!(delete_location(&mut config, "example.com", 3).is_err());
+        assert_eq!(delete_location(&mut config, "example.com", 0).unwrap().forward_ipv4.as_deref(), Some("10.0.0.4"));
+        delete_location(&mut config, "example.com", 1).unwrap();
+        delete_location(&mut config, "example.com", 0).unwrap();
+        assert_eq!(routing_rule_of(&config, "example.com").unwrap(), None);
+    }
+
+    #[test]
+    fn options_need_a_location_first() {
+        let mut config: ProxyConfiguration = toml::from_str(CONFIG).unwrap();
+        let options = RoutingOptions {
+            routing_method: RoutingMethod::Weighted,
+            https_only: false,
+            enable_health_checks: true,
+            health_check_interval: 10,
+            health_check_path: Some("/health".into())
+        };
+
+        assert!(update_options(&mut config, "example.com", options.clone()).is_err());
+
+        add_location(&mut config, "example.com", location("10.0.0.2", None)).unwrap();
+        update_options(&mut config, "example.com", options).unwrap();
+        let rule = routing_rule_of(&config, "example.com").unwrap().unwrap();
+        assert_eq!(rule.routing_method, RoutingMethod::Weighted);
+        assert_eq!(rule.health_check_path.as_deref(), Some("/health"));
+    }
+}
@@ -0,0 +1,323 @@
+use crate::{
+    error::ConfigError,
+    models::{ProxyConfiguration, ProxyRuleInner},
+};
+
+pub fn find_rule_index(config: &ProxyConfiguration, domain: &str) -> Result<usize, ConfigError> {
+    config
+        .proxy_rules
+        .iter()
+        .position(|rule| rule.domain.eq_ignore_ascii_case(domain))
+        .ok_or_else(|| ConfigError::validation("proxy_rules", format!("Domain \"{}\" does not exist.", domain)))
+}
+
+fn ensure_domain_is_free(config: &ProxyConfiguration, domain: &str, ignore_index: Option<usize>) -> Result<(), ConfigError> {
+    let taken = config
+        .proxy_rules
+        .iter()
+        .enumerate()
+        .any(|(index, rule)| Some(index) != ignore_index && rule.domain.eq_ignore_ascii_case(domain));
+
+    if taken {
+        Err(ConfigError::validation("proxy_rules", format!("Domain \"{}\" is already configured.", domain)))
+    } else {
+        Ok(())
+    }
+}
+
+fn validate_rule(rule: &ProxyRuleInner, index: usize) -> Result<(), ConfigError> {
+    let report = rule.validate(&format!("proxy_rules[{}]", index));
+    if report.has_errors() {
+        Err(report.into())
+    } else {
+        Ok(())
+    }
+}
+
+// Runs `edit` on a copy of the proxy rule for `domain` and keeps the copy only when it does
+// not introduce new validation errors. Problems that were already there do not block it.
+pub fn edit_rule<T, F>(config: &mut ProxyConfiguration, domain: &str, edit: F) -> Result<T, ConfigError>
+where
+    F: FnOnce(&mut ProxyRuleInner) -> Result<T, ConfigError>
+{
+    let index = find_rule_index(config, domain)?;
+    let prefix = format!("proxy_rules[{}]", index);
+
+    let before = config.proxy_rules[index].validate(&prefix);
+    let mut rule = config.proxy_rules[index].clone();
+    let result = edit(&mut rule)?;
+
+    let report = rule.validate(&prefix).new_errors_since(&before);
+    if report.has_errors() {
+        return Err(report.into());
+    }
+
+    config.proxy_rules[index] = rule;
+    Ok(result)
+}
+
+pub fn check_index<T>(items: &[T], index: usize, field: &str, name: &str) -> Result<(), ConfigError> {
+    if index < items.len() {
+        Ok(())
+    } else {
+        Err(ConfigError::validation(field, format!("There is no {} #{}.", name, index + 1)))
+    }
+}
+
+// `order` lists the current indices in their new order. Nothing is moved unless the whole
+// order is valid.
+pub fn reorder_items<T>(items: &mut Vec<T>, order: &[usize], field: &str, name: &str) -> Result<(), ConfigError> {
+    if order.len() != items.len() {
+        return Err(ConfigError::validation(field, format!("Expected {} entries but got {}.", items.len(), order.len())));
+    }
+
+    let mut seen = vec![false; items.len()];
+    for &index in order {
+        check_index(items, index, field, name)?;
+        if std::mem::replace(&mut seen[index], true) {
+            return Err(ConfigError::validation(field, format!("The {} #{} is listed twice.", name, index + 1)));
+        }
+    }
+
+    let mut taken: Vec<Option<T>> = std::mem::take(items).into_iter().map(Some).collect();
+    *items = order.iter().filter_map(|&index| taken[index].take()).collect();
+    Ok(())
+}
+
+pub fn add_rule(config: &mut ProxyConfiguration, rule: ProxyRuleInner) -> Result<(), ConfigError> {
+    ensure_domain_is_free(config, &rule.domain, None)?;
+    validate_rule(&rule, config.proxy_rules.len())?;
+    config.proxy_rules.push(rule);
+    Ok(())
+}
+
+pub fn update_rule(config: &mut ProxyConfiguration, domain: &str, rule: ProxyRuleInner) -> Result<(), ConfigError> {
+    let index = find_rule_index(config, domain)?;
+    ensure_domain_is_free(config, &rule.domain, Some(index))?;
+    validate_rule(&rule, index)?;
+    config.proxy_rules[index] = rule;
+    Ok(())
+}
+
+pub fn delete_rule(config: &mut ProxyConfiguration, domain: &str) -> Result<ProxyRuleInner, ConfigError> {
+    let index = find_rule_index(config, domain)?;
+    Ok(config.proxy_rules.remove(index))
+}
+
+// The copy is inserted right after the original so it shows up next to it in the list.
+pub fn duplicate_rule(config: &mut ProxyConfiguration, domain: &str, new_domain: &str) -> Result<(), ConfigError> {
+    let index = find_rule_index(config, domain)?;
+    ensure_domain_is_free(config, new_domain, None)?;
+
+    let mut rule = config.proxy_rules[index].clone();
+    rule.domain = new_domain.into();
+    validate_rule(&rule, index + 1)?;
+
+    config.proxy_rules.insert(index + 1, rule);
+    Ok(())
+}
+
+pub fn reorder_rules(config: &mut ProxyConfiguration, domains: &[String]) -> Result<(), ConfigError> {
+    if domains.len() != config.proxy_rules.len() {
+        return Err(ConfigError::validation(
+            "proxy_rules",
+            format!("Expected {} domains but got {}.", config.proxy_rules.len(), domains.len())
+        ));
+    }
+
+    let mut order = Vec::with_capacity(domains.len());
+
+    for domain in domains {
+        let index = find_rule_index(config, domain)?;
+        if order.contains(&index) {
+            return Err(ConfigError::validation("proxy_rules", format!("Domain \"{}\" is listed twice.", domain)));
+        }
+        order.push(index);
+    }
+
+    let rules = std::mem::take(&mut config.proxy_rules);
+    let mut rules: Vec<Option<ProxyRuleInner>> = rules.into_iter().map(Some).collect();
+    config.proxy_rules = order.into_iter().filter_map(|index| rules[index].take()).collect();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(domain: &str) -> ProxyRuleInner {
+        toml::from_str(&format!(
+            r#"
+domain = "{}"
+max_age_seconds = 0
+rule_type = "Whitelist"
+enable_logging = false
+ignore_query_string = false
+enable_sql_injection_protection = false
+enable_compression = false
+enable_minification = false
+enable_webp_transformation = false
+forward_ipv4 = "10.0.0.1"
+forward_port_http = 8080
+"#,
+            domain
+        ))
+        .unwrap()
+    }
+
+    fn config(domains: &[&str]) -> ProxyConfiguration {
+        let mut config: ProxyConfiguration = toml::from_str("proxy_rules = []").unwrap();
+        config.proxy_rules = domains.iter().map(|domain| rule(domain)).collect();
+        config
+    }
+
+    fn domains(config: &ProxyConfiguration) -> Vec<&str> {
+        config.proxy_rules.iter().map(|rule| rule.domain.as_str()).collect()
+    }
+
+    fn message(error: ConfigError) -> String {
+        match error {
+            ConfigError::Validation { message, .. } => message,
+            ConfigError::Invalid { issues } => issues.into_iter().map(|issue| format!("{}: {}", issue.field, issue.message)).collect(),
+            other => panic!("unexpected error {:?}", other)
+        }
+    }
+
+    #[test]
+    fn finds_rules_ignoring_case() {
+        let config = config(&["a.com", "B.com"]);
+        assert_eq!(find_rule_index(&config, "A.COM").unwrap(), 0);
+        assert_eq!(find_rule_index(&config, "b.com").unwrap(), 1);
+        assert_eq!(message(find_rule_index(&config, "c.com").unwrap_err()), "Domain \"c.com\" does not exist.");
+    }
+
+    #[test]
+    fn adds_only_new_valid_rules() {
+        let mut config = config(&["a.com"]);
+        add_rule(&mut config, rule("b.com")).unwrap();
+        assert_eq!(domains(&config), vec!["a.com", "b.com"]);
+
+        assert_eq!(message(add_rule(&mut config, rule("A.com")).unwrap_err()), "Domain \"A.com\" is already configured.");
+
+        let mut invalid = rule("c.com");
+        invalid.forward_ipv4 = Some("10.0.0".into());
+        assert_eq!(
+            message(add_rule(&mut config, invalid).unwrap_err()),
+            "proxy_rules[2].forward_ipv4: \"10.0.0\" is not a valid IPv4 address."
+        );
+        assert_eq!(domains(&config), vec!["a.com", "b.com"]);
+    }
+
+    #[test]
+    fn updates_a_rule_in_place() {
+        let mut config = config(&["a.com", "b.com"]);
+
+        let mut renamed = rule("A.com");
+        renamed.max_age_seconds = 60;
+        update_rule(&mut config, "a.com", renamed).unwrap();
+        assert_eq!(domains(&config), vec!["A.com", "b.com"]);
+        assert_eq!(config.proxy_rules[0].max_age_seconds, 60);
+
+        assert_eq!(message(update_rule(&mut config, "a.com", rule("B.COM")).unwrap_err()), "Domain \"B.COM\" is already configured.");
+        assert_eq!(message(update_rule(&mut config, "c.com", rule("c.com")).unwrap_err()), "Domain \"c.com\" does not exist.");
+        assert_eq!(message(update_rule(&mut config, "b.com", rule("")).unwrap_err()), "proxy_rules[1].domain: Domain cannot be empty.");
+        assert_eq!(domains(&config), vec!["A.com", "b.com"]);
+    }
+
+    #[test]
+    fn deletes_a_rule() {
+        let mut config = config(&["a.com", "b.com"]);
+        assert_eq!(delete_rule(&mut config, "A.COM").unwrap().domain, "a.com");
+        assert_eq!(domains(&config), vec!["b.com"]);
+        assert_eq!(message(delete_rule(&mut config, "a.com").unwrap_err()), "Domain \"a.com\" does not exist.");
+    }
+
+    #[test]
+    fn duplicates_a_rule_next_to_the_original() {
+        let mut config = config(&["a.com", "b.com"]);
+        config.proxy_rules[0].max_age_seconds = 60;
+
+        duplicate_rule(&mut config, "a.com", "c.com").unwrap();
+        assert_eq!(domains(&config), vec!["a.com", "c.com", "b.com"]);
+        assert_eq!(config.proxy_rules[1].max_age_seconds, 60);
+
+        assert_eq!(message(duplicate_rule(&mut config, "a.com", "B.com").unwrap_err()), "Domain \"B.com\" is already configured.");
+        assert_eq!(message(duplicate_rule(&mut config, "d.com", "e.com").unwrap_err()), "Domain \"d.com\" does not exist.");
+        assert_eq!(
+            message(duplicate_rule(&mut config, "a.com", "not a domain").unwrap_err()),
+            "proxy_rules[1].domain: \"not a domain\" is not a valid domain."
+        );
+        assert_eq!(domains(&config), vec!["a.com", "c.com", "b.com"]);
+    }
+
+    #[test]
+    fn edits_keep_old_problems_but_reject_new_ones() {
+        let mut config = config(&["a.com"]);
+        config.proxy_rules[0].forward_ipv6 = Some("fe80::1::1".into());
+
+        edit_rule(&mut config, "A.com", |rule| {
+            rule.max_age_seconds = 60;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(config.proxy_rules[0].max_age_seconds, 60);
+
+        let error = edit_rule(&mut config, "a.com", |rule| {
+            rule.forward_ipv4 = Some("10.0.0".into());
+            Ok(())
+        })
+        .unwrap_err();
+        assert_eq!(message(error), "proxy_rules[0].forward_ipv4: \"10.0.0\" is not a valid IPv4 address.");
+        assert_eq!(config.proxy_rules[0].forward_ipv4.as_deref(), Some("10.0.0.1"));
+
+        let error = edit_rule(&mut config, "a.com", |_| Err::<(), _>(ConfigError::validation("paths", "No."))).unwrap_err();
+        assert_eq!(message(error), "No.");
+    }
+
+    #[test]
+    fn checks_indices() {
+        let items = [1, 2];
+        assert!(check_index(&items, 1, "paths", "path").is_ok());
+        assert_eq!(
+            check_index(&items, 2, "paths", "path").unwrap_err(),
+            ConfigError::validation("paths", "There is no path #3.")
+        );
+    }
+
+    #[test]
+    fn reorders_only_complete_orders() {
+        let mut items = vec!["a", "b", "c"];
+        reorder_items(&mut items, &[2, 0, 1], "paths", "path").unwrap();
+        assert_eq!(items, vec!["c", "a", "b"]);
+
+        for (order, expected) in [
+            (vec![0, 1], "Expected 3 entries but got 2."),
+            (vec![0, 1, 3], "There is no path #4."),
+            (vec![0, 1, 1], "The path #2 is listed twice."),
+        ] {
+            assert_eq!(
+                reorder_items(&mut items, &order, "paths", "path").unwrap_err(),
+                ConfigError::validation("paths", expected)
+            );
+        }
+        assert_eq!(items, vec!["c", "a", "b"]);
+    }
+
+    #[test]
+    fn reorders_rules_by_domain() {
+        let mut config = config(&["a.com", "b.com", "c.com"]);
+        reorder_rules(&mut config, &["C.com".into(), "a.com".into(), "b.com".into()]).unwrap();
+        assert_eq!(domains(&config), vec!["c.com", "a.com", "b.com"]);
+
+        assert_eq!(message(reorder_rules(&mut config, &["a.com".into()]).unwrap_err()), "Expected 3 domains but got 1.");
+        assert_eq!(
+            message(reorder_rules(&mut config, &["a.com".into(), "A.com".into(), "b.com".into()]).unwrap_err()),
+            "Domain \"A.com\" is listed twice."
+        );
+        assert_eq!(
+            message(reorder_rules(&mut config, &["a.com".into(), "b.com".into(), "d.com".into()]).unwrap_err()),
+            "Domain \"d.com\" does not exist."
+        );
+        assert_eq!(domains(&config), vec!["c.com", "a.com", "b.com"]);
+    }
+}
@@ -0,0 +1,540 @@
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use regex::Regex;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    models::{ProxyConfiguration, ProxyRuleInner},
+    settings::{self, Setting, SettingType},
+    validation::ValidationReport,
+};
+
+pub const SCHEMA_DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+const RULE_TYPES: [&str; 2] = ["Whitelist", "Blacklist"];
+const MATCH_TYPES: [&str; 6] = ["Contains", "Equals", "StartsWith", "EndsWith", "DoesNotContain", "DoesNotEqual"];
+const ROUTING_METHODS: [&str; 3] = ["Weighted", "Priority", "Performance"];
+
+fn integer_range(setting_type: SettingType) -> (i64, Option<i64>) {
+    match setting_type {
+        SettingType::U16 => (0, Some(u16::MAX as i64)),
+        SettingType::U32 => (0, Some(u32::MAX as i64)),
+        // TOML integers are i64, so nothing larger can be written anyway.
+        SettingType::U64 | SettingType::Usize => (0, None),
+        SettingType::I32 => (i32::MIN as i64, Some(i32::MAX as i64)),
+        SettingType::Bool | SettingType::String => (0, None)
+    }
+}
+
+fn setting_schema(setting: &Setting) -> Value {
+    let mut schema = Map::new();
+    schema.insert("title".into(), setting.label.into());
+
+    match setting.setting_type {
+        SettingType::Bool => {
+            schema.insert("type".into(), "boolean".into());
+        }
+        SettingType::String => {
+            schema.insert("type".into(), "string".into());
+
+            // The proxy accepts filter directives after the level, e.g. `info,hyper=warn`.
+            if setting.name == "logging_level" {
+                schema.insert("pattern".into(), format!("^({})(,|$)", settings::LOG_LEVELS.join("|")).into());
+            } else if !setting.allowed_values.is_empty() {
+                schema.insert("enum".into(), setting.allowed_values.into());
+            }
+        }
+        setting_type => {
+            let (min, max) = integer_range(setting_type);
+            schema.insert("type".into(), "integer".into());
+            schema.insert("minimum".into(), setting.min.unwrap_or(min).into());
+            if let Some(max) = setting.max.or(max) {
+                schema.insert("maximum".into(), max.into());
+            }
+        }
+    }
+
+    if setting.name == "listening_address" {
+        schema.insert("anyOf".into(), json!([{ "format": "ipv4" }, { "format": "ipv6" }]));
+    }
+
+    if let Some(default) = setting.default.and_then(|default| setting.parse(default).ok()) {
+        schema.insert("default".into(), default);
+    }
+
+    if setting.secret {
+        schema.insert("writeOnly".into(), true.into());
+    }
+
+    Value::Object(schema)
+}
+
+fn port(title: &str) -> Value {
+    json!({ "title": title, "type": "integer", "minimum": 1, "maximum": u16::MAX })
+}
+
+fn flag(title: &str) -> Value {
+    json!({ "title": title, "type": "boolean", "default": false })
+}
+
+fn definitions() -> Value {
+    json!({
+        "RuleType": { "enum": RULE_TYPES },
+        "MatchType": { "enum": MATCH_TYPES },
+        "RoutingMethod": { "enum": ROUTING_METHODS },
+        "ProxyRuleInner": {
+            "type": "object",
+            "properties": {
+                "domain": { "title": "Domain", "type": "string", "minLength": 1, "pattern": "^[^\\s/]+$" },
+                "max_age_seconds": { "title": "Cache Max Age", "type": "integer", "minimum": 0, "default": 0 },
+                "paths": { "title": "Paths", "type": "array", "items": { "type": "string", "pattern": "^/" } },
+                "forward_addr": { "title": "Forward Address", "type": "string", "minLength": 1 },
+                "forward_ipv4": { "title": "Forward IPv4", "type": "string", "format": "ipv4" },
+                "forward_ipv6": { "title": "Forward IPv6", "type": "string", "format": "ipv6" },
+                "forward_port_http": port("Forward HTTP Port"),
+                "forward_port_https": port("Forward HTTPS Port"),
+                "rule_type": { "title": "Rule Type", "$ref": "#/$defs/RuleType", "default": "Whitelist" },
+                "enable_logging": flag("Enable Logging"),
+                "path_rules": { "title": "Path Rules", "type": "array", "items": { "$ref": "#/$defs/PathRule" } },
+                "routing_rules": { "title": "Routing", "$ref": "#/$defs/RoutingRule" },
+                "ignore_query_string": flag("Ignore Query String"),
+                "enable_sql_injection_protection": flag("SQL Injection Protection"),
+                "disallowed_user_agents": { "title": "Disallowed User Agents", "type": "array", "items": { "$ref": "#/$defs/UserAgentRule" } },
+                "enable_compression": flag("Enable Compression"),
+                "compression_flags": { "title": "Compression Flags", "type": "string" },
+                "enable_minification": flag("Enable Minification"),
+                "minification_flags": { "title": "Minification Flags", "type": "string" },
+                "enable_webp_transformation": flag("Enable WebP Transformation"),
+                "webp_transformation_min_age": { "title": "WebP Minimum Age", "type": "integer", "minimum": 0 },
+                "manual_certificate": flag("Manual Certificate")
+            },
+            "required": [
+                "domain",
+                "max_age_seconds",
+                "rule_type",
+                "enable_logging",
+                "ignore_query_string",
+                "enable_sql_injection_protection",
+                "enable_compression",
+                "enable_minification",
+                "enable_webp_transformation"
+            ],
+            "additionalProperties": false
+        },
+        "PathRule": {
+            "type": "object",
+            "properties": {
+                "max_age_seconds": { "title": "Cache Max Age", "type": "integer", "minimum": 0, "default": 0 },
+                "path": { "title": "Path", "type": "string", "minLength": 1 },
+                "match_type": { "title": "Match Type", "$ref": "#/$defs/MatchType" },
+                "rule_type": { "title": "Rule Type", "$ref": "#/$defs/RuleType" }
+            },
+            "required": ["max_age_seconds", "path", "match_type", "rule_type"],
+            "additionalProperties": false
+        },
+        "RoutingRule": {
+            "type": "object",
+            "properties": {
+                "routing_method": { "title": "Routing Method", "$ref": "#/$defs/RoutingMethod", "default": "Priority" },
+                "routing_locations": { "title": "Locations", "type": "array", "minItems": 1, "items": { "$ref": "#/$defs/RoutingLocation" } },
+                "https_only": flag("HTTPS Only"),
+                "enable_health_checks": flag("Enable Health Checks"),
+                "health_check_interval": { "title": "Health Check Interval", "type": "integer", "minimum": 0, "maximum": u32::MAX, "default": 0 },
+                "health_check_path": { "title": "Health Check Path", "type": "string", "pattern": "^/" }
+            },
+            "required": ["routing_method", "routing_locations", "https_only", "enable_health_checks", "health_check_interval"],
+            "additionalProperties": false
+        },
+        "RoutingLocation": {
+            "type": "object",
+            "properties": {
+                "primary": { "title": "Primary", "type": "boolean" },
+                "priority": { "title": "Priority", "type": "integer", "minimum": 0, "maximum": u16::MAX },
+                "forward_addr": { "title": "Forward Address", "type": "string", "minLength": 1 },
+                "forward_ipv4": { "title": "Forward IPv4", "type": "string", "format": "ipv4" },
+                "forward_ipv6": { "title": "Forward IPv6", "type": "string", "format": "ipv6" },
+                "forward_port_http": port("Forward HTTP Port"),
+                "forward_port_https": port("Forward HTTPS Port")
+            },
+            "additionalProperties": false
+        },
+        "UserAgentRule": {
+            "type": "object",
+            "properties": {
+                "user_agent": { "title": "User Agent", "type": "string", "minLength": 1 },
+                "match_type": { "title": "Match Type", "$ref": "#/$defs/MatchType" }
+            },
+            "required": ["user_agent", "match_type"],
+            "additionalProperties": false
+        }
+    })
+}
+
+// The JSON Schema of the configuration file. Top-level settings come from the settings
+// table so their defaults and ranges match what the settings editor enforces.
+pub fn schema() -> &'static Value {
+    static SCHEMA: OnceLock<Value> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        let mut properties = Map::new();
+        for setting in settings::SETTINGS {
+            properties.insert(setting.name.into(), setting_schema(setting));
+        }
+        properties.insert(
+            "proxy_rules".into(),
+            json!({ "title": "Proxy Rules", "type": "array", "items": { "$ref": "#/$defs/ProxyRuleInner" } })
+        );
+
+        json!({
+            "$schema": SCHEMA_DRAFT,
+            "title": "arc2proxy configuration",
+            "type": "object",
+            "properties": properties,
+            "required": ["proxy_rules"],
+            "additionalProperties": false,
+            "$defs": definitions()
+        })
+    })
+}
+
+fn collect_patterns(schema: &Value, patterns: &mut HashMap<String, Regex>) {
+    match schema {
+        Value::Object(object) => {
+            if let Some(pattern) = object.get("pattern").and_then(Value::as_str) {
+                if let Ok(regex) = Regex::new(pattern) {
+                    patterns.insert(pattern.into(), regex);
+                }
+            }
+            object.values().for_each(|child| collect_patterns(child, patterns));
+        }
+        Value::Array(items) => items.iter().for_each(|item| collect_patterns(item, patterns)),
+        _ => {}
+    }
+}
+
+// Every `pattern` of the schema, compiled the first time one is needed.
+fn compiled(pattern: &str) -> Option<&'static Regex> {
+    static PATTERNS: OnceLock<HashMap<String, Regex>> = OnceLock::new();
+    PATTERNS
+        .get_or_init(|| {
+            let mut patterns = HashMap::new();
+            collect_patterns(schema(), &mut patterns);
+            patterns
+        })
+        .get(pattern)
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "nothing",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_i64() || number.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "table"
+    }
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() { key.into() } else { format!("{}.{}", prefix, key) }
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::String(text) => format!("\"{}\"", text),
+        other => other.to_string()
+    }
+}
+
+// Checks `value` against `schema`. Only the keywords the generator above emits are
+// understood, anything else is ignored.
+fn check_node(schema: &Value, value: &Value, field: &str, report: &mut ValidationReport) {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let name = reference.trim_start_matches("#/$defs/");
+        if let Some(definition) = schema_definition(name) {
+            check_node(definition, value, field, report);
+        }
+    }
+
+    if let Some(expected) = schema.get("type").and_then(Value::as_str) {
+        let actual = type_name(value);
+        let matches = match expected {
+            "object" => actual == "table",
+            "number" => actual == "integer" || actual == "number",
+            expected => actual == expected
+        };
+
+        if !matches {
+            let expected = if expected == "object" { "table" } else { expected };
+            report.error(field, format!("Expected {}, found {}.", expected, actual));
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            let allowed: Vec<String> = allowed.iter().map(|allowed| allowed.as_str().map_or_else(|| allowed.to_string(), String::from)).collect();
+            report.error(field, format!("{} is not one of: {}.", describe(value), allowed.join(", ")));
+        }
+    }
+
+    if let Some(number) = value.as_i64().or_else(|| value.as_u64().map(|number| number.min(i64::MAX as u64) as i64)) {
+        if let Some(minimum) = schema.get("minimum").and_then(Value::as_i64) {
+            if number < minimum {
+                report.error(field, format!("Must be at least {}.", minimum));
+            }
+        }
+        if let Some(maximum) = schema.get("maximum").and_then(Value::as_i64) {
+            if number > maximum {
+                report.error(field, format!("Must be at most {}.", maximum));
+            }
+        }
+    }
+
+    if let Some(text) = value.as_str() {
+        if schema.get("minLength").and_then(Value::as_u64).is_some_and(|min| (text.chars().count() as u64) < min) {
+            report.error(field, "Cannot be empty.");
+        } else if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+            if compiled(pattern).is_some_and(|regex| !regex.is_match(text)) {
+                report.error(field, format!("\"{}\" does not match the pattern {}.", text, pattern));
+            }
+        }
+
+        match schema.get("format").and_then(Value::as_str) {
+            Some("ipv4") if Ipv4Addr::from_str(text).is_err() => report.error(field, format!("\"{}\" is not a valid IPv4 address.", text)),
+            Some("ipv6") if Ipv6Addr::from_str(text).is_err() => report.error(field, format!("\"{}\" is not a valid IPv6 address.", text)),
+            _ => {}
+        }
+    }
+
+    if let Some(any_of) = schema.get("anyOf").and_then(Value::as_array) {
+        let matches_one = any_of.iter().any(|option| {
+            let mut scratch = ValidationReport::default();
+            check_node(option, value, field, &mut scratch);
+            !scratch.has_errors()
+        });
+
+        if !matches_one {
+            report.error(field, format!("{} is not a valid IP address.", describe(value)));
+        }
+    }
+
+    if let Some(items) = value.as_array() {
+        if let Some(min_items) = schema.get("minItems").and_then(Value::as_u64) {
+            if (items.len() as u64) < min_items {
+                report.error(field, format!("At least {} item(s) are required.", min_items));
+            }
+        }
+
+        if let Some(item_schema) = schema.get("items") {
+            for (index, item) in items.iter().enumerate() {
+                check_node(item_schema, item, &format!("{}[{}]", field, index), report);
+            }
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        let properties = schema.get("properties").and_then(Value::as_object);
+
+        for required in schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+            if !object.contains_key(required) {
+                report.error(join(field, required), "This key is required.");
+            }
+        }
+
+        for (key, child) in object {
+            match properties.and_then(|properties| properties.get(key)) {
+                Some(child_schema) => check_node(child_schema, child, &join(field, key), report),
+                // Unknown keys are skipped when the file is loaded, so they are most likely typos
+                // rather than errors.
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    report.warning(join(field, key), format!("Unknown key \"{}\", it is ignored.", key))
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+fn schema_definition(name: &str) -> Option<&'static Value> {
+    schema().get("$defs").and_then(|definitions| definitions.get(name))
+}
+
+// Optional values serialize to null, which the file leaves out instead.
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, without_nulls(value)))
+                .collect()
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(without_nulls).collect()),
+        other => other
+    }
+}
+
+pub fn check(document: &Value) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    check_node(schema(), document, "", &mut report);
+    report
+}
+
+pub fn check_toml(text: &str) -> Result<ValidationReport, toml::de::Error> {
+    let document: toml::Value = toml::from_str(text)?;
+    Ok(check(&serde_json::to_value(document).unwrap_or_default()))
+}
+
+pub fn check_configuration(config: &ProxyConfiguration) -> ValidationReport {
+    check(&without_nulls(serde_json::to_value(config).unwrap_or_default()))
+}
+
+pub fn check_rule(rule: &ProxyRuleInner, prefix: &str) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    if let Some(definition) = schema_definition("ProxyRuleInner") {
+        check_node(definition, &without_nulls(serde_json::to_value(rule).unwrap_or_default()), prefix, &mut report);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every optional value is set, so a field the schema does not describe shows up as an
+    // unknown key.
+    const FULL_CONFIG: &str = r#"
+listening_address = "0.0.0.0"
+listening_port_http = 80
+listening_port_https = 443
+backtracing = false
+add_caching = true
+add_rate_limiting = true
+add_logging = true
+disable_default_body_limit = false
+api_key = "key"
+azure_table_storage_key = "storage"
+logging_level = "info,hyper=warn"
+add_sql_injection_protection = false
+lets_encrypt_contact_email = "admin@example.com"
+enable_compression = true
+compression_flags = "gzip=6,mime=text/html"
+recv_buffer_size = 65536
+send_buffer_size = 65536
+ip_ttl = 64
+tcp_keep_alive_seconds = 60
+max_backlog = 1024
+enable_streaming = true
+nonblocking = true
+nodelay = true
+proxy_nodelay = true
+proxy_keepalive_sec = 120
+proxy_timeout = 45
+proxy_min_tls_version = "TLS_1_3"
+
+[[proxy_rules]]
+domain = "example.com"
+max_age_seconds = 60
+paths = ["/api"]
+forward_addr = "backend.internal"
+forward_ipv4 = "10.0.0.1"
+forward_ipv6 = "fd00::1"
+forward_port_http = 8080
+forward_port_https = 8443
+rule_type = "Whitelist"
+enable_logging = true
+ignore_query_string = true
+enable_sql_injection_protection = true
+enable_compression = true
+compression_flags = "brotli"
+enable_minification = true
+minification_flags = "html,css"
+enable_webp_transformation = true
+webp_transformation_min_age = 3600
+manual_certificate = true
+
+[[proxy_rules.path_rules]]
+max_age_seconds = 0
+path = "/admin"
+match_type = "StartsWith"
+rule_type = "Blacklist"
+
+[[proxy_rules.disallowed_user_agents]]
+user_agent = "bot"
+match_type = "Contains"
+
+[proxy_rules.routing_rules]
+routing_method = "Priority"
+https_only = false
+enable_health_checks = true
+health_check_interval = 10
+health_check_path = "/health"
+
+[[proxy_rules.routing_rules.routing_locations]]
+primary = true
+priority = 0
+forward_addr = "app.internal"
+forward_ipv4 = "10.0.0.2"
+forward_ipv6 = "fd00::2"
+forward_port_http = 8080
+forward_port_https = 8443
+"#;
+
+    fn has_null(value: &Value) -> bool {
+        match value {
+            Value::Null => true,
+            Value::Array(items) => items.iter().any(has_null),
+            Value::Object(object) => object.values().any(has_null),
+            _ => false
+        }
+    }
+
+    #[test]
+    fn describes_every_field_of_a_full_configuration() {
+        let config: ProxyConfiguration = toml::from_str(FULL_CONFIG).unwrap();
+        assert!(!has_null(&serde_json::to_value(&config).unwrap()), "the fixture should set every field");
+
+        assert_eq!(check_configuration(&config).issues, Vec::new());
+        assert_eq!(check_rule(&config.proxy_rules[0], "proxy_rules[0]").issues, Vec::new());
+        assert_eq!(check_toml(FULL_CONFIG).unwrap().issues, Vec::new());
+    }
+
+    #[test]
+    fn reports_values_outside_the_schema() {
+        let text = FULL_CONFIG
+            .replace("domain = \"example.com\"", "domain = \"example.com/api\"")
+            .replace("health_check_path = \"/health\"", "health_check_path = \"health\"")
+            .replace("ip_ttl = 64", "ip_ttl = 300")
+            .replace("forward_ipv4 = \"10.0.0.1\"", "forward_ipv4 = \"10.0.0\"")
+            .replace("logging_level = \"info,hyper=warn\"", "logging_level = \"errorXYZ\"\nlistening_port = 80");
+
+        let issues: Vec<(String, String)> = check_toml(&text)
+            .unwrap()
+            .issues
+            .into_iter()
+            .map(|issue| (issue.field, issue.message))
+            .collect();
+        assert_eq!(issues, vec![
+            ("ip_ttl".to_string(), "Must be at most 255.".to_string()),
+            ("listening_port".to_string(), "Unknown key \"listening_port\", it is ignored.".to_string()),
+            ("logging_level".to_string(), "\"errorXYZ\" does not match the pattern ^(off|trace|debug|info|warn|error)(,|$).".to_string()),
+            ("proxy_rules[0].domain".to_string(), "\"example.com/api\" does not match the pattern ^[^\\s/]+$.".to_string()),
+            ("proxy_rules[0].forward_ipv4".to_string(), "\"10.0.0\" is not a valid IPv4 address.".to_string()),
+            ("proxy_rules[0].routing_rules.health_check_path".to_string(), "\"health\" does not match the pattern ^/.".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn compiles_every_pattern_of_the_schema() {
+        for pattern in ["^[^\\s/]+$", "^/", "^(off|trace|debug|info|warn|error)(,|$)"] {
+            assert!(compiled(pattern).is_some(), "{}", pattern);
+        }
+        assert!(compiled("not in the schema").is_none());
+    }
+}
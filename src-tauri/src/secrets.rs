@@ -0,0 +1,485 @@
+use std::{
+    env,
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use regex::Regex;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use serde_json::Value;
+
+use crate::{
+    backups::DiffLine,
+    document,
+    error::ConfigError,
+    location::{self, ConfigLocator},
+    models::ProxyConfiguration,
+    session::FieldChange,
+    settings,
+    storage,
+};
+
+// What the GUI gets instead of a secret value.
+pub const MASK: &str = "********";
+pub const ENV_PREFIX: &str = "env:";
+pub const FILE_PREFIX: &str = "file:";
+pub const ENCRYPTED_PREFIX: &str = "enc:";
+pub const KEY_FILE_NAME: &str = "secrets.key";
+pub const RUNTIME_DIR_NAME: &str = "runtime";
+
+const SECRET_BYTES: usize = 32;
+
+// A secret is either kept in the file as it is, or as a reference that is resolved when the
+// proxy starts: `env:NAME`, `file:PATH` or `enc:HEX` (encrypted with the key in secrets.key).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretSource<'a> {
+    Plain(&'a str),
+    Env(&'a str),
+    File(&'a str),
+    Encrypted(&'a str)
+}
+
+impl<'a> SecretSource<'a> {
+    pub fn of(value: &'a str) -> Self {
+        if let Some(name) = value.strip_prefix(ENV_PREFIX) {
+            SecretSource::Env(name.trim())
+        } else if let Some(path) = value.strip_prefix(FILE_PREFIX) {
+            SecretSource::File(path.trim())
+        } else if let Some(data) = value.strip_prefix(ENCRYPTED_PREFIX) {
+            SecretSource::Encrypted(data.trim())
+        } else {
+            SecretSource::Plain(value)
+        }
+    }
+
+    // A reference only says where the secret is kept, so it is safe to show.
+    pub fn is_reference(&self) -> bool {
+        matches!(self, SecretSource::Env(_) | SecretSource::File(_))
+    }
+}
+
+pub fn is_secret(name: &str) -> bool {
+    settings::find_setting(name).is_ok_and(|setting| setting.secret)
+}
+
+fn secret_fields(config: &mut ProxyConfiguration) -> [(&'static str, &mut Option<String>); 2] {
+    [
+        ("api_key", &mut config.api_key),
+        ("azure_table_storage_key", &mut config.azure_table_storage_key)
+    ]
+}
+
+fn mask_value(value: &str) -> String {
+    if SecretSource::of(value).is_reference() {
+        value.to_string()
+    } else {
+        MASK.to_string()
+    }
+}
+
+pub fn mask(config: &ProxyConfiguration) -> ProxyConfiguration {
+    let mut masked = config.clone();
+    for (_, value) in secret_fields(&mut masked) {
+        if let Some(value) = value {
+            *value = mask_value(value);
+        }
+    }
+    masked
+}
+
+// A configuration sent back by the GUI still carries the mask, the current values are kept for it.
+pub fn unmask(config: &mut ProxyConfiguration, current: &ProxyConfiguration) {
+    let mut current = current.clone();
+    for ((_, value), (_, current_value)) in secret_fields(config).into_iter().zip(secret_fields(&mut current)) {
+        if value.as_deref() == Some(MASK) {
+            *value = current_value.take();
+        }
+    }
+}
+
+// Copies the secrets only, e.g. into an edit session after a secret was written.
+pub fn copy_secrets(from: &ProxyConfiguration, to: &mut ProxyConfiguration) {
+    let mut from = from.clone();
+    for ((_, value), (_, from_value)) in secret_fields(to).into_iter().zip(secret_fields(&mut from)) {
+        *value = from_value.take();
+    }
+}
+
+pub fn mask_changes(changes: Vec<FieldChange>) -> Vec<FieldChange> {
+    let mask_json = |value: Value| match value {
+        Value::String(text) => Value::from(mask_value(&text)),
+        other => other
+    };
+
+    changes
+        .into_iter()
+        .map(|mut change| {
+            if is_secret(&change.path) {
+                change.old_value = change.old_value.map(mask_json);
+                change.new_value = change.new_value.map(mask_json);
+            }
+            change
+        })
+        .collect()
+}
+
+fn secret_line_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^(\s*(?:api_key|azure_table_storage_key)\s*=\s*)(.*)$").unwrap())
+}
+
+// Backups are diffed as text, so the lines holding secrets are masked by their key.
+pub fn mask_diff(lines: Vec<DiffLine>) -> Vec<DiffLine> {
+    lines
+        .into_iter()
+        .map(|mut line| {
+            if let Some(captures) = secret_line_pattern().captures(&line.text) {
+                let value = toml::from_str::<toml::Table>(&format!("value = {}", &captures[2]))
+                    .ok()
+                    .and_then(|table| table.get("value").and_then(|value| value.as_str()).map(mask_value))
+                    .unwrap_or_else(|| MASK.to_string());
+                line.text = format!("{}{}", &captures[1], toml::Value::String(value));
+            }
+            line
+        })
+        .collect()
+}
+
+fn random_bytes(count: usize) -> Result<Vec<u8>, ConfigError> {
+    let mut bytes = vec![0u8; count];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| ConfigError::validation("secrets", "The system random number generator failed."))?;
+    Ok(bytes)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&text[index..index + 2], 16).ok())
+        .collect()
+}
+
+// 256 random bits, hex encoded so it can be put in a header or a URL as it is.
+pub fn generate_api_key() -> Result<String, ConfigError> {
+    random_bytes(SECRET_BYTES).map(|bytes| to_hex(&bytes))
+}
+
+// Only checks that a reference is well formed, whether it resolves is checked when the proxy starts.
+pub fn check_reference(value: &str) -> Option<&'static str> {
+    match SecretSource::of(value) {
+        SecretSource::Env("") => Some("env: needs the name of an environment variable."),
+        SecretSource::File("") => Some("file: needs the path of the file holding the secret."),
+        SecretSource::Encrypted(data) if from_hex(data).is_none_or(|bytes| bytes.len() <= NONCE_LEN) => {
+            Some("The encrypted value is damaged.")
+        }
+        _ => None
+    }
+}
+
+// Keeps the encryption key and the resolved copy of the configuration in the app config directory.
+pub struct SecretStore {
+    dir: PathBuf
+}
+
+pub fn store(locator: &ConfigLocator) -> Result<SecretStore, ConfigError> {
+    locator
+        .preferences()
+        .dir()
+        .map(|dir| SecretStore { dir: dir.to_path_buf() })
+        .ok_or_else(|| ConfigError::validation("secrets", "There is no app config directory to keep secrets in."))
+}
+
+impl SecretStore {
+    pub fn key_path(&self) -> PathBuf {
+        self.dir.join(KEY_FILE_NAME)
+    }
+
+    pub fn runtime_dir(&self) -> PathBuf {
+        self.dir.join(RUNTIME_DIR_NAME)
+    }
+
+    pub fn runtime_config_path(&self) -> PathBuf {
+        self.runtime_dir().join(location::CONFIG_FILE_NAME)
+    }
+
+    // The key is created the first time something is encrypted.
+    fn key(&self, create: bool) -> Result<LessSafeKey, ConfigError> {
+        let path = self.key_path();
+
+        let bytes = match fs::read_to_string(&path) {
+            Ok(text) => from_hex(text.trim())
+                .ok_or_else(|| ConfigError::validation("secrets", format!("{} is damaged.", path.display())))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && create => {
+                let bytes = random_bytes(SECRET_BYTES)?;
+                storage::write_private(&path, to_hex(&bytes).as_bytes()).map_err(|e| ConfigError::io(&path, e))?;
+                bytes
+            }
+            Err(e) => return Err(ConfigError::io(&path, e))
+        };
+
+        UnboundKey::new(&CHACHA20_POLY1305, &bytes)
+            .map(LessSafeKey::new)
+            .map_err(|_| ConfigError::validation("secrets", format!("{} is damaged.", path.display())))
+    }
+
+    // The setting name is authenticated too, so an encrypted value cannot be moved to another setting.
+    pub fn encrypt(&self, name: &str, value: &str) -> Result<String, ConfigError> {
+        let key = self.key(true)?;
+        let nonce_bytes = random_bytes(NONCE_LEN)?;
+        let nonce = Nonce::try_assume_unique_for_key(&nonce_bytes).map_err(|_| ConfigError::validation(name, "Could not encrypt the value."))?;
+
+        let mut data = value.as_bytes().to_vec();
+        key.seal_in_place_append_tag(nonce, Aad::from(name.as_bytes()), &mut data)
+            .map_err(|_| ConfigError::validation(name, "Could not encrypt the value."))?;
+
+        Ok(format!("{}{}{}", ENCRYPTED_PREFIX, to_hex(&nonce_bytes), to_hex(&data)))
+    }
+
+    pub fn decrypt(&self, name: &str, data: &str) -> Result<String, ConfigError> {
+        let damaged = || ConfigError::validation(name, "The encrypted value is damaged or was encrypted with another key.");
+
+        let bytes = from_hex(data).filter(|bytes| bytes.len() > NONCE_LEN).ok_or_else(damaged)?;
+        let (nonce_bytes, sealed) = bytes.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce_bytes).map_err(|_| damaged())?;
+
+        let mut sealed = sealed.to_vec();
+        let plain = self
+            .key(false)?
+            .open_in_place(nonce, Aad::from(name.as_bytes()), &mut sealed)
+            .map_err(|_| damaged())?;
+
+        String::from_utf8(plain.to_vec()).map_err(|_| damaged())
+    }
+
+    fn resolve_value(&self, name: &str, value: &str, config_dir: &Path) -> Result<String, ConfigError> {
+        match SecretSource::of(value) {
+            SecretSource::Plain(value) => Ok(value.to_string()),
+            SecretSource::Env(variable) => {
+                env::var(variable).map_err(|_| ConfigError::validation(name, format!("Environment variable {} is not set.", variable)))
+            }
+            SecretSource::File(path) => {
+                let path = config_dir.join(path);
+                fs::read_to_string(&path)
+                    .map(|text| text.trim_end().to_string())
+                    .map_err(|e| ConfigError::io(&path, e))
+            }
+            SecretSource::Encrypted(data) => self.decrypt(name, data)
+        }
+    }
+
+    // Relative `file:` paths are relative to the configuration file.
+    pub fn resolve(&self, config: &ProxyConfiguration, config_dir: &Path) -> Result<ProxyConfiguration, ConfigError> {
+        let mut resolved = config.clone();
+        for (name, value) in secret_fields(&mut resolved) {
+            if let Some(value) = value {
+                *value = self.resolve_value(name, value, config_dir)?;
+            }
+        }
+        Ok(resolved)
+    }
+
+    // Writes the configuration with every secret resolved for the proxy to read.
+    pub fn write_runtime_copy(&self, config_path: &Path, config: &ProxyConfiguration) -> Result<(), ConfigError> {
+        let config_dir = config_path.parent().unwrap_or(Path::new(""));
+        let resolved = self.resolve(config, config_dir)?;
+
+        let original = fs::read_to_string(config_path).map_err(|e| ConfigError::io(config_path, e))?;
+        let text = document::render(&original, &resolved)?;
+        let path = self.runtime_config_path();
+        storage::write_private(&path, text.as_bytes()).map_err(|e| ConfigError::io(&path, e))
+    }
+
+    // Keeps a running proxy's copy in step with the file the GUI edits.
+    pub fn refresh_runtime_copy(&self, config_path: &Path, config: &ProxyConfiguration) -> Result<(), ConfigError> {
+        if self.runtime_config_path().exists() {
+            self.write_runtime_copy(config_path, config)?;
+        }
+        Ok(())
+    }
+
+    pub fn remove_runtime_copy(&self) {
+        let _ = fs::remove_file(self.runtime_config_path());
+    }
+}
+
+pub fn needs_resolving(config: &ProxyConfiguration) -> bool {
+    [&config.api_key, &config.azure_table_storage_key]
+        .into_iter()
+        .flatten()
+        .any(|value| !matches!(SecretSource::of(value), SecretSource::Plain(_)))
+}
+
+// Secrets are write-only: a new value replaces the old one, which is never read back. An empty
+// value clears the secret and references are stored as they are.
+pub fn set_secret(config: &mut ProxyConfiguration, name: &str, value: &str, encrypt: bool, store: &SecretStore) -> Result<(), ConfigError> {
+    if !is_secret(name) {
+        return Err(ConfigError::validation(name, "Only secret settings are set this way."));
+    }
+
+    let value = value.trim();
+    if value == MASK {
+        return Err(ConfigError::validation(name, "Enter the new value, not the masked one."));
+    }
+    if let Some(problem) = check_reference(value) {
+        return Err(ConfigError::validation(name, problem));
+    }
+
+    let new_value = match SecretSource::of(value) {
+        _ if value.is_empty() => None,
+        SecretSource::Plain(value) if encrypt => Some(store.encrypt(name, value)?),
+        SecretSource::Encrypted(data) => {
+            store.decrypt(name, data)?;
+            Some(value.to_string())
+        }
+        _ => Some(value.to_string())
+    };
+
+    for (field, field_value) in secret_fields(config) {
+        if field == name {
+            *field_value = new_value.clone();
+        }
+    }
+
+    Ok(())
+}
+
+// Returns the new key, the only time it is shown. An encrypted key stays encrypted.
+pub fn rotate_api_key(config: &mut ProxyConfiguration, store: &SecretStore) -> Result<String, ConfigError> {
+    let encrypt = match config.api_key.as_deref().map(SecretSource::of) {
+        Some(source) if source.is_reference() => {
+            return Err(ConfigError::validation(
+                "api_key",
+                format!("The API key is read from {}, rotate it there.", config.api_key.as_deref().unwrap_or_default())
+            ));
+        }
+        Some(SecretSource::Encrypted(_)) => true,
+        _ => false
+    };
+
+    let api_key = generate_api_key()?;
+    set_secret(config, "api_key", &api_key, encrypt, store)?;
+    Ok(api_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process;
+
+    use super::*;
+    use crate::backups::DiffKind;
+
+    fn test_store(name: &str) -> SecretStore {
+        let dir = env::temp_dir().join(format!("arc2proxy-gui-secrets-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        SecretStore { dir }
+    }
+
+    fn config(api_key: Option<&str>, azure_table_storage_key: Option<&str>) -> ProxyConfiguration {
+        let mut config: ProxyConfiguration = toml::from_str("proxy_rules = []").unwrap();
+        config.api_key = api_key.map(String::from);
+        config.azure_table_storage_key = azure_table_storage_key.map(String::from);
+        config
+    }
+
+    fn encrypted_data(value: &str) -> &str {
+        match SecretSource::of(value) {
+            SecretSource::Encrypted(data) => data,
+            other => panic!("{:?} is not encrypted", other)
+        }
+    }
+
+    #[test]
+    fn round_trips_encrypted_values() {
+        let store = test_store("round-trip");
+
+        let encrypted = store.encrypt("api_key", "s3cret").unwrap();
+        assert!(!encrypted.contains("s3cret"));
+        assert_eq!(check_reference(&encrypted), None);
+        assert_eq!(store.decrypt("api_key", encrypted_data(&encrypted)).unwrap(), "s3cret");
+
+        // The nonce is random, so the same value never encrypts the same way twice.
+        assert_ne!(store.encrypt("api_key", "s3cret").unwrap(), encrypted);
+
+        fs::remove_dir_all(&store.dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_values_of_another_setting_or_key() {
+        let store = test_store("wrong-aad");
+        let encrypted = store.encrypt("api_key", "s3cret").unwrap();
+        let data = encrypted_data(&encrypted);
+
+        let error = store.decrypt("azure_table_storage_key", data).unwrap_err();
+        assert!(error.to_string().contains("damaged or was encrypted with another key"));
+
+        let other = test_store("wrong-key");
+        other.encrypt("api_key", "other").unwrap();
+        assert!(other.decrypt("api_key", data).is_err());
+
+        assert!(store.decrypt("api_key", &data[..data.len() - 2]).is_err());
+        assert!(store.decrypt("api_key", "abc").is_err());
+
+        fs::remove_dir_all(&store.dir).unwrap();
+        fs::remove_dir_all(&other.dir).unwrap();
+    }
+
+    #[test]
+    fn masks_only_values_that_are_not_references() {
+        let masked = mask(&config(Some("env:API_KEY"), Some("file:storage.key")));
+        assert_eq!(masked.api_key.as_deref(), Some("env:API_KEY"));
+        assert_eq!(masked.azure_table_storage_key.as_deref(), Some("file:storage.key"));
+
+        let masked = mask(&config(Some("s3cret"), Some("enc:00112233")));
+        assert_eq!(masked.api_key.as_deref(), Some(MASK));
+        assert_eq!(masked.azure_table_storage_key.as_deref(), Some(MASK));
+
+        assert_eq!(mask(&config(None, None)).api_key, None);
+    }
+
+    #[test]
+    fn unmask_keeps_the_current_value_for_the_mask() {
+        let current = config(Some("s3cret"), Some("enc:00112233"));
+
+        let mut sent_back = mask(&current);
+        sent_back.azure_table_storage_key = Some("env:STORAGE_KEY".into());
+        unmask(&mut sent_back, &current);
+
+        assert_eq!(sent_back.api_key.as_deref(), Some("s3cret"));
+        assert_eq!(sent_back.azure_table_storage_key.as_deref(), Some("env:STORAGE_KEY"));
+    }
+
+    #[test]
+    fn masks_secret_lines_of_backup_diffs() {
+        let line = |kind: DiffKind, text: &str| DiffLine { kind, text: text.into() };
+
+        let masked = mask_diff(vec![
+            line(DiffKind::Removed, "api_key = \"s3cret\""),
+            line(DiffKind::Added, "  azure_table_storage_key=\"env:STORAGE_KEY\""),
+            line(DiffKind::Added, "api_key = 'enc:00112233' # rotated"),
+            line(DiffKind::Unchanged, "domain = \"api_key.example.com\""),
+        ]);
+
+        let texts: Vec<&str> = masked.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(texts, vec![
+            "api_key = \"********\"",
+            "  azure_table_storage_key=\"env:STORAGE_KEY\"",
+            "api_key = \"********\"",
+            "domain = \"api_key.example.com\"",
+        ]);
+        assert_eq!(masked[0].kind, DiffKind::Removed);
+    }
+}
@@ -0,0 +1,356 @@
+use std::{path::PathBuf, sync::Mutex};
+
+use serde_json::Value;
+
+use crate::{error::ConfigError, models::ProxyConfiguration};
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    pub old_value: Option<Value>,
+    pub new_value: Option<Value>
+}
+
+// Changes queued by the GUI on top of the configuration as it was when the session began.
+#[derive(Debug, Clone)]
+pub struct EditSession {
+    pub path: PathBuf,
+    pub base: ProxyConfiguration,
+    pub staged: ProxyConfiguration
+}
+
+impl EditSession {
+    pub fn new(path: PathBuf, base: ProxyConfiguration) -> Self {
+        EditSession {
+            path,
+            staged: base.clone(),
+            base
+        }
+    }
+
+    pub fn changes(&self) -> Result<Vec<FieldChange>, ConfigError> {
+        diff_configurations(&self.base, &self.staged)
+    }
+
+    // Committing on top of a file someone else changed would silently undo their edit.
+    pub fn check_unchanged(&self, on_disk: &ProxyConfiguration) -> Result<(), ConfigError> {
+        if diff_configurations(&self.base, on_disk)?.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::validation(
+                "session",
+                "The configuration file was changed by someone else since the edit session began."
+            ))
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct SessionState {
+    session: Mutex<Option<EditSession>>
+}
+
+impl SessionState {
+    pub fn get(&self) -> Option<EditSession> {
+        self.session.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, session: EditSession) {
+        *self.session.lock().unwrap() = Some(session);
+    }
+
+    pub fn take(&self) -> Option<EditSession> {
+        self.session.lock().unwrap().take()
+    }
+
+    pub fn with_session<T, F>(&self, change: F) -> Result<T, ConfigError>
+    where
+        F: FnOnce(&mut EditSession) -> Result<T, ConfigError>
+    {
+        let mut session = self.session.lock().unwrap();
+
+        match session.as_mut() {
+            Some(session) => change(session),
+            None => Err(ConfigError::validation("session", "No edit session is active."))
+        }
+    }
+}
+
+pub fn diff_configurations(old: &ProxyConfiguration, new: &ProxyConfiguration) -> Result<Vec<FieldChange>, ConfigError> {
+    let old = serde_json::to_value(old).map_err(|e| ConfigError::Serialize { message: e.to_string() })?;
+    let new = serde_json::to_value(new).map_err(|e| ConfigError::Serialize { message: e.to_string() })?;
+
+    let mut changes = Vec::new();
+    diff_values("", &old, &new, &mut changes);
+    Ok(changes)
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+// null and a missing key mean the same thing for Option fields.
+fn present(value: Option<&Value>) -> Option<&Value> {
+    value.filter(|value| !value.is_null())
+}
+
+fn diff_values(path: &str, old: &Value, new: &Value, changes: &mut Vec<FieldChange>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            let mut keys: Vec<&String> = old_map.keys().collect();
+            keys.extend(new_map.keys().filter(|key| !old_map.contains_key(*key)));
+
+            for key in keys {
+                let child = join(path, key);
+
+                match (present(old_map.get(key)), present(new_map.get(key))) {
+                    (Some(old), Some(new)) => diff_values(&child, old, new, changes),
+                    (Some(old), None) => changes.push(FieldChange {
+                        path: child,
+                        kind: ChangeKind::Removed,
+                        old_value: Some(old.clone()),
+                        new_value: None
+                    }),
+                    (None, Some(new)) => changes.push(FieldChange {
+                        path: child,
+                        kind: ChangeKind::Added,
+                        old_value: None,
+                        new_value: Some(new.clone())
+                    }),
+                    (None, None) => {}
+                }
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) if is_keyed_by_domain(old_items) && is_keyed_by_domain(new_items) => {
+            diff_keyed_arrays(path, old_items, new_items, changes);
+        }
+        (Value::Array(old_items), Value::Array(new_items)) => {
+            for index in 0..old_items.len().max(new_items.len()) {
+                let child = format!("{}[{}]", path, index);
+
+                match (old_items.get(index), new_items.get(index)) {
+                    (Some(old), Some(new)) => diff_values(&child, old, new, changes),
+                    (Some(old), None) => changes.push(FieldChange {
+                        path: child,
+                        kind: ChangeKind::Removed,
+                        old_value: Some(old.clone()),
+                        new_value: None
+                    }),
+                    (None, Some(new)) => changes.push(FieldChange {
+                        path: child,
+                        kind: ChangeKind::Added,
+                        old_value: None,
+                        new_value: Some(new.clone())
+                    }),
+                    (None, None) => {}
+                }
+            }
+        }
+        _ if old != new => changes.push(FieldChange {
+            path: path.to_string(),
+            kind: ChangeKind::Modified,
+            old_value: Some(old.clone()),
+            new_value: Some(new.clone())
+        }),
+        _ => {}
+    }
+}
+
+fn domain_of(value: &Value) -> Option<&str> {
+    value.get("domain").and_then(Value::as_str)
+}
+
+// Domains are compared ignoring case, as rules.rs does when it looks a rule up.
+fn has_domain(value: &Value, domain: &str) -> bool {
+    domain_of(value).is_some_and(|other| other.eq_ignore_ascii_case(domain))
+}
+
+fn is_keyed_by_domain(items: &[Value]) -> bool {
+    items.iter().all(|item| domain_of(item).is_some())
+}
+
+// proxy_rules are matched by domain, so reordering or inserting a rule does not show up as
+// every following rule having changed.
+fn diff_keyed_arrays(path: &str, old_items: &[Value], new_items: &[Value], changes: &mut Vec<FieldChange>) {
+    for old in old_items {
+        let domain = domain_of(old).unwrap_or_default();
+        let child = format!("{}[{}]", path, domain);
+
+        match new_items.iter().find(|new| has_domain(new, domain)) {
+            Some(new) => diff_values(&child, old, new, changes),
+            None => changes.push(FieldChange {
+                path: child,
+                kind: ChangeKind::Removed,
+                old_value: Some(old.clone()),
+                new_value: None
+            })
+        }
+    }
+
+    for new in new_items {
+        let domain = domain_of(new).unwrap_or_default();
+
+        if !old_items.iter().any(|old| has_domain(old, domain)) {
+            changes.push(FieldChange {
+                path: format!("{}[{}]", path, domain),
+                kind: ChangeKind::Added,
+                old_value: None,
+                new_value: Some(new.clone())
+            });
+        }
+    }
+
+    let old_order: Vec<&str> = old_items.iter().filter_map(domain_of).filter(|domain| new_items.iter().any(|new| has_domain(new, domain))).collect();
+    let new_order: Vec<&str> = new_items.iter().filter_map(domain_of).filter(|domain| old_items.iter().any(|old| has_domain(old, domain))).collect();
+    let reordered = old_order.len() != new_order.len() || old_order.iter().zip(&new_order).any(|(old, new)| !old.eq_ignore_ascii_case(new));
+
+    if reordered {
+        changes.push(FieldChange {
+            path: path.to_string(),
+            kind: ChangeKind::Modified,
+            old_value: Some(Value::from(old_order)),
+            new_value: Some(Value::from(new_order))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use serde_json::json;
+
+    use super::*;
+
+    const CONFIG: &str = r#"
+listening_port_http = 80
+
+[[proxy_rules]]
+domain = "a.com"
+max_age_seconds = 60
+rule_type = "Whitelist"
+enable_logging = false
+ignore_query_string = false
+enable_sql_injection_protection = false
+enable_compression = false
+enable_minification = false
+enable_webp_transformation = false
+
+[[proxy_rules]]
+domain = "b.com"
+max_age_seconds = 0
+rule_type = "Whitelist"
+enable_logging = false
+ignore_query_string = false
+enable_sql_injection_protection = false
+enable_compression = false
+enable_minification = false
+enable_webp_transformation = false
+"#;
+
+    fn session() -> EditSession {
+        EditSession::new(PathBuf::from("proxy_config.toml"), toml::from_str(CONFIG).unwrap())
+    }
+
+    fn paths(changes: &[FieldChange]) -> Vec<(&str, ChangeKind)> {
+        changes.iter().map(|change| (change.path.as_str(), change.kind)).collect()
+    }
+
+    #[test]
+    fn reports_changed_scalars() {
+        let mut session = session();
+        assert!(session.changes().unwrap().is_empty());
+
+        session.staged.listening_port_http = Some(8080);
+        session.staged.api_key = Some("secret".into());
+
+        let changes = session.changes().unwrap();
+        assert_eq!(paths(&changes), vec![("api_key", ChangeKind::Added), ("listening_port_http", ChangeKind::Modified)]);
+        assert_eq!(changes[1].old_value, Some(json!(80)));
+        assert_eq!(changes[1].new_value, Some(json!(8080)));
+
+        session.staged.listening_port_http = None;
+        session.staged.api_key = None;
+        assert_eq!(paths(&session.changes().unwrap()), vec![("listening_port_http", ChangeKind::Removed)]);
+    }
+
+    #[test]
+    fn keys_rules_by_domain() {
+        let mut session = session();
+        session.staged.proxy_rules[1].max_age_seconds = 30;
+        assert_eq!(paths(&session.changes().unwrap()), vec![("proxy_rules[b.com].max_age_seconds", ChangeKind::Modified)]);
+
+        let mut session = self::session();
+        let mut added = session.staged.proxy_rules[1].clone();
+        added.domain = "c.com".into();
+        session.staged.proxy_rules.remove(0);
+        session.staged.proxy_rules.insert(0, added);
+        assert_eq!(
+            paths(&session.changes().unwrap()),
+            vec![("proxy_rules[a.com]", ChangeKind::Removed), ("proxy_rules[c.com]", ChangeKind::Added)]
+        );
+    }
+
+    #[test]
+    fn matches_domains_ignoring_case() {
+        let mut session = session();
+        session.staged.proxy_rules[0].domain = "A.com".into();
+
+        let changes = session.changes().unwrap();
+        assert_eq!(paths(&changes), vec![("proxy_rules[a.com].domain", ChangeKind::Modified)]);
+        assert_eq!(changes[0].new_value, Some(json!("A.com")));
+
+        session.staged.proxy_rules.swap(0, 1);
+        assert_eq!(
+            paths(&session.changes().unwrap()),
+            vec![("proxy_rules[a.com].domain", ChangeKind::Modified), ("proxy_rules", ChangeKind::Modified)]
+        );
+    }
+
+    #[test]
+    fn reports_reordered_rules_once() {
+        let mut session = session();
+        session.staged.proxy_rules.swap(0, 1);
+
+        let changes = session.changes().unwrap();
+        assert_eq!(paths(&changes), vec![("proxy_rules", ChangeKind::Modified)]);
+        assert_eq!(changes[0].old_value, Some(json!(["a.com", "b.com"])));
+        assert_eq!(changes[0].new_value, Some(json!(["b.com", "a.com"])));
+    }
+
+    #[test]
+    fn detects_a_file_changed_on_disk() {
+        let dir = std::env::temp_dir().join(format!("arc2proxy-gui-session-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("proxy_config.toml");
+        fs::write(&path, CONFIG).unwrap();
+
+        let mut session = EditSession::new(path.clone(), crate::_read_configuration(&path).unwrap());
+        session.staged.listening_port_http = Some(8080);
+        assert!(session.check_unchanged(&crate::_read_configuration(&path).unwrap()).is_ok());
+
+        // Formatting alone is not a conflict, a changed value is.
+        fs::write(&path, format!("# reformatted\n{}", CONFIG)).unwrap();
+        assert!(session.check_unchanged(&crate::_read_configuration(&path).unwrap()).is_ok());
+
+        fs::write(&path, CONFIG.replace("listening_port_http = 80", "listening_port_http = 81")).unwrap();
+        let error = session.check_unchanged(&crate::_read_configuration(&path).unwrap()).unwrap_err();
+        assert!(error.to_string().contains("changed by someone else"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
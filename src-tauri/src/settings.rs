@@ -0,0 +1,278 @@
+use serde_json::Value;
+
+use crate::{
+    error::ConfigError,
+    models::ProxyConfiguration,
+};
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingType {
+    Bool,
+    String,
+    U16,
+    U32,
+    U64,
+    Usize,
+    I32
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub setting_type: SettingType,
+    pub default: Option<&'static str>,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    pub allowed_values: &'static [&'static str],
+    pub secret: bool
+}
+
+const fn setting(name: &'static str, label: &'static str, setting_type: SettingType) -> Setting {
+    Setting {
+        name,
+        label,
+        setting_type,
+        default: None,
+        min: None,
+        max: None,
+        allowed_values: &[],
+        secret: false
+    }
+}
+
+impl Setting {
+    const fn default(mut self, default: &'static str) -> Self {
+        self.default = Some(default);
+        self
+    }
+
+    const fn range(mut self, min: i64, max: i64) -> Self {
+        self.min = Some(min);
+        self.max = Some(max);
+        self
+    }
+
+    const fn allowed(mut self, allowed_values: &'static [&'static str]) -> Self {
+        self.allowed_values = allowed_values;
+        self
+    }
+
+    const fn secret(mut self) -> Self {
+        self.secret = true;
+        self
+    }
+}
+
+// The names LogLevel and TlsVersion are serialized with. `logging_level` may add filter
+// directives after the level, so it is a text field checked by the model rather than a choice.
+pub const LOG_LEVELS: [&str; 6] = ["off", "trace", "debug", "info", "warn", "error"];
+pub const TLS_VERSIONS: [&str; 4] = ["TLS_1_0", "TLS_1_1", "TLS_1_2", "TLS_1_3"];
+
+// Every top-level field of ProxyConfiguration except proxy_rules, which has its own commands.
+pub const SETTINGS: &[Setting] = &[
+    setting("listening_address", "Listening Address", SettingType::String).default("0.0.0.0"),
+    setting("listening_port_http", "HTTP Listening Port", SettingType::U16).default("80").range(1, u16::MAX as i64),
+    setting("listening_port_https", "HTTPS Listening Port", SettingType::U16).default("443").range(1, u16::MAX as i64),
+    setting("logging_level", "Logging Level", SettingType::String)
+        .default(if cfg!(debug_assertions) { "debug" } else { "error" }),
+    setting("add_caching", "Add Caching", SettingType::Bool).default("true"),
+    setting("add_rate_limiting", "Add Rate Limiting", SettingType::Bool).default("true"),
+    setting("add_logging", "Add Logging", SettingType::Bool).default("true"),
+    setting("disable_default_body_limit", "Disable Body Limit", SettingType::Bool).default("false"),
+    setting("add_sql_injection_protection", "SQL Injection Protection", SettingType::Bool).default("false"),
+    setting("recv_buffer_size", "Receive Buffer Size", SettingType::Usize).range(1, i64::MAX),
+    setting("send_buffer_size", "Send Buffer Size", SettingType::Usize).range(1, i64::MAX),
+    setting("ip_ttl", "IP TTL", SettingType::U32).range(1, 255),
+    setting("tcp_keep_alive_seconds", "TCP Keep-Alive", SettingType::U64),
+    setting("max_backlog", "Max Backlog", SettingType::I32).range(1, i32::MAX as i64),
+    setting("backtracing", "Backtracing", SettingType::Bool).default("false"),
+    setting("api_key", "API Key", SettingType::String).secret(),
+    setting("azure_table_storage_key", "Azure Table Storage Key", SettingType::String).secret(),
+    setting("lets_encrypt_contact_email", "Let's Encrypt Contact Email", SettingType::String),
+    setting("enable_compression", "Enable Compression", SettingType::Bool).default("false"),
+    setting("compression_flags", "Compression Flags", SettingType::String),
+    setting("enable_streaming", "Enable Streaming", SettingType::Bool).default("true"),
+    setting("nonblocking", "Non-Blocking Sockets", SettingType::Bool).default("true"),
+    setting("nodelay", "TCP No Delay", SettingType::Bool).default("true"),
+    setting("proxy_nodelay", "Proxy TCP No Delay", SettingType::Bool).default("true"),
+    setting("proxy_keepalive_sec", "Proxy Keep-Alive", SettingType::U32).default("120"),
+    setting("proxy_timeout", "Proxy Timeout", SettingType::U16).default("45").range(1, u16::MAX as i64),
+    setting("proxy_min_tls_version", "Proxy Min TLS Version", SettingType::String).default("TLS_1_3").allowed(&TLS_VERSIONS),
+];
+
+pub fn find_setting(name: &str) -> Result<&'static Setting, ConfigError> {
+    SETTINGS
+        .iter()
+        .find(|setting| setting.name == name)
+        .ok_or_else(|| ConfigError::validation(name, "Unknown setting."))
+}
+
+impl Setting {
+    // An empty string clears the value, so the proxy falls back to its default.
+    pub fn parse(&self, raw: &str) -> Result<Value, ConfigError> {
+        let raw = raw.trim();
+
+        if raw.is_empty() {
+            return Ok(Value::Null);
+        }
+
+        let value = match self.setting_type {
+            SettingType::Bool => raw
+                .parse::<bool>()
+                .map(Value::from)
+                .map_err(|_| ConfigError::validation(self.name, format!("\"{}\" must be true or false.", raw)))?,
+            SettingType::String => Value::from(raw),
+            SettingType::U16 => self.parse_integer::<u16>(raw)?,
+            SettingType::U32 => self.parse_integer::<u32>(raw)?,
+            SettingType::U64 => self.parse_integer::<u64>(raw)?,
+            SettingType::Usize => self.parse_integer::<usize>(raw)?,
+            SettingType::I32 => self.parse_integer::<i32>(raw)?
+        };
+
+        if !self.allowed_values.is_empty() && !self.allowed_values.contains(&raw) {
+            return Err(ConfigError::validation(
+                self.name,
+                format!("\"{}\" is not one of: {}.", raw, self.allowed_values.join(", "))
+            ));
+        }
+
+        Ok(value)
+    }
+
+    fn parse_integer<T>(&self, raw: &str) -> Result<Value, ConfigError>
+    where
+        T: std::str::FromStr + Into<Value> + TryInto<i64> + Copy,
+        T::Err: std::fmt::Display
+    {
+        let number = raw
+            .parse::<T>()
+            .map_err(|e| ConfigError::validation(self.name, format!("\"{}\" {}", raw, e)))?;
+
+        if let Ok(as_i64) = number.try_into() {
+            let below = self.min.is_some_and(|min| as_i64 < min);
+            let above = self.max.is_some_and(|max| as_i64 > max);
+
+            if below || above {
+                return Err(ConfigError::validation(
+                    self.name,
+                    format!("{} must be between {} and {}.", raw, self.min.unwrap_or(i64::MIN), self.max.unwrap_or(i64::MAX))
+                ));
+            }
+        }
+
+        Ok(number.into())
+    }
+}
+
+pub fn get_value(config: &ProxyConfiguration, name: &str) -> Result<Value, ConfigError> {
+    find_setting(name)?;
+    let json = serde_json::to_value(config).map_err(|e| ConfigError::validation(name, e.to_string()))?;
+    Ok(json.get(name).cloned().unwrap_or(Value::Null))
+}
+
+pub fn set_value(config: &mut ProxyConfiguration, name: &str, raw: &str) -> Result<(), ConfigError> {
+    let setting = find_setting(name)?;
+    if setting.secret {
+        return Err(ConfigError::validation(name, format!("{} is a secret, it is write-only and set on its own.", setting.label)));
+    }
+    let value = setting.parse(raw)?;
+
+    let mut json = serde_json::to_value(&*config).map_err(|e| ConfigError::validation(name, e.to_string()))?;
+    json[name] = value;
+    *config = serde_json::from_value(json).map_err(|e| ConfigError::validation(name, e.to_string()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn parse(name: &str, raw: &str) -> Result<Value, ConfigError> {
+        find_setting(name).unwrap().parse(raw)
+    }
+
+    fn rejected(name: &str, message: &str) -> Result<Value, ConfigError> {
+        Err(ConfigError::validation(name, message))
+    }
+
+    #[test]
+    fn parses_values_by_type() {
+        assert_eq!(parse("add_caching", " false ").unwrap(), json!(false));
+        assert_eq!(parse("add_caching", "yes"), rejected("add_caching", "\"yes\" must be true or false."));
+
+        assert_eq!(parse("proxy_timeout", "30").unwrap(), json!(30));
+        assert_eq!(parse("proxy_timeout", "thirty"), rejected("proxy_timeout", "\"thirty\" invalid digit found in string"));
+        assert_eq!(parse("proxy_timeout", "70000"), rejected("proxy_timeout", "\"70000\" number too large to fit in target type"));
+        assert_eq!(parse("max_backlog", "-1"), rejected("max_backlog", "-1 must be between 1 and 2147483647."));
+
+        assert_eq!(parse("listening_address", "::1").unwrap(), json!("::1"));
+    }
+
+    #[test]
+    fn checks_ranges_and_allowed_values() {
+        assert_eq!(parse("ip_ttl", "0"), rejected("ip_ttl", "0 must be between 1 and 255."));
+        assert_eq!(parse("ip_ttl", "256"), rejected("ip_ttl", "256 must be between 1 and 255."));
+        assert_eq!(parse("ip_ttl", "1").unwrap(), json!(1));
+        assert_eq!(parse("ip_ttl", "255").unwrap(), json!(255));
+
+        assert_eq!(parse("proxy_min_tls_version", "TLS_1_2").unwrap(), json!("TLS_1_2"));
+        assert_eq!(
+            parse("proxy_min_tls_version", "TLS_1_4"),
+            rejected("proxy_min_tls_version", "\"TLS_1_4\" is not one of: TLS_1_0, TLS_1_1, TLS_1_2, TLS_1_3.")
+        );
+    }
+
+    #[test]
+    fn empty_value_clears_the_setting() {
+        for name in ["add_caching", "ip_ttl", "proxy_min_tls_version", "listening_address"] {
+            assert_eq!(parse(name, "  ").unwrap(), Value::Null);
+        }
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        assert_eq!(find_setting("listening_port"), Err(ConfigError::validation("listening_port", "Unknown setting.")));
+
+        let mut config: ProxyConfiguration = toml::from_str("proxy_rules = []").unwrap();
+        assert_eq!(get_value(&config, "listening_port"), rejected("listening_port", "Unknown setting."));
+        assert_eq!(set_value(&mut config, "listening_port", "80"), Err(ConfigError::validation("listening_port", "Unknown setting.")));
+    }
+
+    #[test]
+    fn secrets_are_not_set_here() {
+        let mut config: ProxyConfiguration = toml::from_str("proxy_rules = []").unwrap();
+        assert_eq!(
+            set_value(&mut config, "api_key", "hunter2"),
+            Err(ConfigError::validation("api_key", "API Key is a secret, it is write-only and set on its own."))
+        );
+        assert_eq!(config.api_key, None);
+    }
+
+    #[test]
+    fn set_values_read_back() {
+        let mut config: ProxyConfiguration = toml::from_str("proxy_rules = []").unwrap();
+
+        set_value(&mut config, "listening_port_http", "8080").unwrap();
+        set_value(&mut config, "add_caching", "false").unwrap();
+        set_value(&mut config, "proxy_min_tls_version", "TLS_1_2").unwrap();
+        set_value(&mut config, "lets_encrypt_contact_email", "admin@example.com").unwrap();
+
+        assert_eq!(get_value(&config, "listening_port_http").unwrap(), json!(8080));
+        assert_eq!(get_value(&config, "add_caching").unwrap(), json!(false));
+        assert_eq!(get_value(&config, "proxy_min_tls_version").unwrap(), json!("TLS_1_2"));
+        assert_eq!(get_value(&config, "lets_encrypt_contact_email").unwrap(), json!("admin@example.com"));
+
+        set_value(&mut config, "listening_port_http", "").unwrap();
+        assert_eq!(get_value(&config, "listening_port_http").unwrap(), Value::Null);
+        assert_eq!(config.listening_port_http, None);
+
+        assert!(set_value(&mut config, "ip_ttl", "0").is_err());
+        assert_eq!(get_value(&config, "ip_ttl").unwrap(), Value::Null);
+    }
+}
@@ -0,0 +1,360 @@
+use crate::{
+    models::{PathRule, ProxyConfiguration, RuleType, UserAgentRule},
+    path_rules::match_path,
+};
+
+#[derive(serde::Deserialize)]
+#[derive(Debug, Clone, Default)]
+pub struct SimulatedRequest {
+    pub url: String,
+    pub user_agent: Option<String>,
+    pub method: Option<String>
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Simulation {
+    pub host: String,
+    // What the rules were matched against: the path, plus the query unless it is ignored.
+    pub path: String,
+    pub rule_index: Option<usize>,
+    pub domain: Option<String>,
+    pub path_rule_index: Option<usize>,
+    pub path_rule: Option<PathRule>,
+    pub rule_type: Option<RuleType>,
+    pub blocked_by_user_agent: Option<UserAgentRule>,
+    pub allowed: bool,
+    // None when the response would not be cached.
+    pub max_age_seconds: Option<u64>,
+    // Every decision in order, for showing why a request ended up where it did.
+    pub trace: Vec<String>
+}
+
+struct ParsedUrl {
+    host: String,
+    path: String,
+    query: Option<String>
+}
+
+// Accepts full URLs as well as `example.com/path` without a scheme.
+fn parse_url(url: &str) -> ParsedUrl {
+    let url = url.trim();
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let rest = rest.split('#').next().unwrap_or_default();
+
+    let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+    let (authority, target) = rest.split_at(authority_end);
+    let authority = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+
+    let host = if let Some(bracketed) = authority.strip_prefix('[') {
+        bracketed.split(']').next().unwrap_or_default()
+    } else {
+        authority.split(':').next().unwrap_or_default()
+    };
+
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, Some(query.to_string())),
+        None => (target, None)
+    };
+
+    ParsedUrl {
+        host: host.to_lowercase(),
+        path: if path.is_empty() { "/".into() } else { path.into() },
+        query
+    }
+}
+
+// Evaluates `request` against the configuration:
+//
+// 1. The proxy rule is picked by host name.
+// 2. A matching `disallowed_user_agents` entry blocks the request.
+// 3. The first matching path rule decides the rule type and cache age.
+// 4. Without one, the rule's own `rule_type` and `max_age_seconds` apply to its `paths`
+//    (or to every path when there are none), and paths outside of `paths` get the
+//    opposite: a whitelist allows only its paths, a blacklist blocks only its paths.
+// 5. Allowed GET and HEAD responses are cached for the resulting max age when caching is on.
+//
+// arc2proxy does not document this evaluation order and its source is not part of this
+// repository, so the steps above are this simulator's reading of the proxy_config.toml
+// fields, not a specification. Step 4 in particular is a guess: `paths` are compared as
+// prefixes, and the trace says so whenever they decide the outcome.
+pub fn simulate(config: &ProxyConfiguration, request: &SimulatedRequest) -> Simulation {
+    let url = parse_url(&request.url);
+    let method = request.method.as_deref().unwrap_or("GET").to_uppercase();
+    let mut trace = Vec::new();
+
+    let mut simulation = Simulation {
+        host: url.host.clone(),
+        path: url.path.clone(),
+        rule_index: None,
+        domain: None,
+        path_rule_index: None,
+        path_rule: None,
+        rule_type: None,
+        blocked_by_user_agent: None,
+        allowed: false,
+        max_age_seconds: None,
+        trace: Vec::new()
+    };
+
+    let Some((rule_index, rule)) = config
+        .proxy_rules
+        .iter()
+        .enumerate()
+        .find(|(_, rule)| rule.domain.eq_ignore_ascii_case(&url.host))
+    else {
+        trace.push(format!("No proxy rule exists for host \"{}\", the request is not proxied.", url.host));
+        simulation.trace = trace;
+        return simulation;
+    };
+
+    simulation.rule_index = Some(rule_index);
+    simulation.domain = Some(rule.domain.clone());
+    trace.push(format!("Host \"{}\" matches proxy rule #{} ({}).", url.host, rule_index + 1, rule.domain));
+
+    if let Some(query) = &url.query {
+        if rule.ignore_query_string {
+            trace.push("The query string is ignored for this rule.".into());
+        } else {
+            simulation.path = format!("{}?{}", url.path, query);
+        }
+    }
+
+    let user_agent = request.user_agent.as_deref().unwrap_or_default();
+    let blocked_by = rule
+        .disallowed_user_agents
+        .iter()
+        .flatten()
+        .find(|agent_rule| match_path(&agent_rule.match_type, &agent_rule.user_agent, user_agent));
+
+    if let Some(agent_rule) = blocked_by {
+        trace.push(format!(
+            "User agent \"{}\" is disallowed by {:?} \"{}\".",
+            user_agent, agent_rule.match_type, agent_rule.user_agent
+        ));
+        simulation.blocked_by_user_agent = Some(agent_rule.clone());
+    }
+
+    let path = simulation.path.clone();
+    let path_rule = rule
+        .path_rules
+        .iter()
+        .flatten()
+        .enumerate()
+        .find(|(_, path_rule)| match_path(&path_rule.match_type, &path_rule.path, &path));
+
+    let (rule_type, max_age_seconds) = match path_rule {
+        Some((index, path_rule)) => {
+            trace.push(format!(
+                "Path rule #{} ({:?} {:?} \"{}\") matches \"{}\".",
+                index + 1,
+                path_rule.rule_type,
+                path_rule.match_type,
+                path_rule.path,
+                path
+            ));
+            simulation.path_rule_index = Some(index);
+            simulation.path_rule = Some(path_rule.clone());
+            (path_rule.rule_type.clone(), path_rule.max_age_seconds)
+        }
+        None => {
+            let paths = rule.paths.as_deref().unwrap_or_default();
+
+            if paths.is_empty() {
+                trace.push(format!("No path rule matches, the rule's {:?} applies to every path.", rule.rule_type));
+                (rule.rule_type.clone(), rule.max_age_seconds)
+            } else if paths.iter().any(|prefix| path.starts_with(prefix.as_str())) {
+                trace.push(format!(
+                    "No path rule matches, \"{}\" starts with one of the rule's {:?} paths (assumed prefix match).",
+                    path, rule.rule_type
+                ));
+                (rule.rule_type.clone(), rule.max_age_seconds)
+            } else {
+                let opposite = match rule.rule_type {
+                    RuleType::Whitelist => RuleType::Blacklist,
+                    RuleType::Blacklist => RuleType::Whitelist
+                };
+                trace.push(format!(
+                    "No path rule matches and \"{}\" is outside the rule's {:?} paths, assumed to get the opposite rule type.",
+                    path, rule.rule_type
+                ));
+                (opposite, 0)
+            }
+        }
+    };
+
+    simulation.allowed = rule_type == RuleType::Whitelist && simulation.blocked_by_user_agent.is_none();
+    simulation.rule_type = Some(rule_type);
+    trace.push(if simulation.allowed { "The request is allowed.".into() } else { "The request is blocked.".into() });
+
+    if simulation.allowed {
+        if !config.add_caching.unwrap_or(true) {
+            trace.push("Caching is disabled.".into());
+        } else if method != "GET" && method != "HEAD" {
+            trace.push(format!("{} responses are not cached.", method));
+        } else if max_age_seconds == 0 {
+            trace.push("The max age is 0, the response is not cached.".into());
+        } else {
+            trace.push(format!("The response is cached for {} seconds.", max_age_seconds));
+            simulation.max_age_seconds = Some(max_age_seconds);
+        }
+    }
+
+    simulation.trace = trace;
+    simulation
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::{MatchType, ProxyRuleInner};
+
+    use super::*;
+
+    const RULE: &str = r#"
+domain = "example.com"
+max_age_seconds = 300
+rule_type = "Whitelist"
+enable_logging = false
+ignore_query_string = false
+enable_sql_injection_protection = false
+enable_compression = false
+enable_minification = false
+enable_webp_transformation = false
+"#;
+
+    fn path_rule(match_type: MatchType, path: &str, rule_type: RuleType, max_age_seconds: u64) -> PathRule {
+        PathRule { max_age_seconds, path: path.into(), match_type, rule_type }
+    }
+
+    fn config(edit: impl FnOnce(&mut ProxyRuleInner)) -> ProxyConfiguration {
+        let mut config: ProxyConfiguration = toml::from_str("proxy_rules = []").unwrap();
+        let mut rule: ProxyRuleInner = toml::from_str(RULE).unwrap();
+        edit(&mut rule);
+        config.proxy_rules.push(rule);
+        config
+    }
+
+    fn get(config: &ProxyConfiguration, url: &str) -> Simulation {
+        simulate(config, &SimulatedRequest { url: url.into(), ..SimulatedRequest::default() })
+    }
+
+    #[test]
+    fn evaluates_every_match_type() {
+        let cases = [
+            (MatchType::Contains, "admin", "/site/admin/users", "/site/users"),
+            (MatchType::Equals, "/login", "/login", "/login/reset"),
+            (MatchType::StartsWith, "/api", "/api/v1", "/v1/api"),
+            (MatchType::EndsWith, ".php", "/index.php", "/index.html"),
+            (MatchType::DoesNotContain, "public", "/private/file", "/public/file"),
+            (MatchType::DoesNotEqual, "/health", "/other", "/health"),
+        ];
+
+        for (match_type, pattern, matching, other) in cases {
+            let config = config(|rule| {
+                rule.path_rules = Some(vec![path_rule(match_type.clone(), pattern, RuleType::Blacklist, 0)]);
+            });
+
+            let simulation = get(&config, &format!("https://example.com{}", matching));
+            assert_eq!(simulation.path_rule_index, Some(0), "{:?} should match {}", match_type, matching);
+            assert!(!simulation.allowed);
+
+            let simulation = get(&config, &format!("https://example.com{}", other));
+            assert_eq!(simulation.path_rule_index, None, "{:?} should not match {}", match_type, other);
+            assert!(simulation.allowed);
+        }
+    }
+
+    #[test]
+    fn first_matching_path_rule_wins() {
+        let config = config(|rule| {
+            rule.path_rules = Some(vec![
+                path_rule(MatchType::StartsWith, "/api/public", RuleType::Whitelist, 60),
+                path_rule(MatchType::StartsWith, "/api", RuleType::Blacklist, 0),
+            ]);
+        });
+
+        let simulation = get(&config, "example.com/api/public/status");
+        assert_eq!(simulation.path_rule_index, Some(0));
+        assert!(simulation.allowed);
+
+        let simulation = get(&config, "example.com/api/private");
+        assert_eq!(simulation.path_rule_index, Some(1));
+        assert!(!simulation.allowed);
+    }
+
+    #[test]
+    fn disallowed_user_agent_blocks_an_allowed_path() {
+        let config = config(|rule| {
+            rule.disallowed_user_agents = Some(vec![UserAgentRule { user_agent: "BadBot".into(), match_type: MatchType::Contains }]);
+        });
+
+        let request = SimulatedRequest {
+            url: "https://example.com/".into(),
+            user_agent: Some("Mozilla/5.0 (compatible; BadBot/1.0)".into()),
+            method: None
+        };
+        let simulation = simulate(&config, &request);
+        assert!(simulation.blocked_by_user_agent.is_some());
+        assert_eq!(simulation.rule_type, Some(RuleType::Whitelist));
+        assert!(!simulation.allowed);
+        assert_eq!(simulation.max_age_seconds, None);
+
+        assert!(get(&config, "https://example.com/").allowed);
+    }
+
+    #[test]
+    fn query_string_is_matched_unless_ignored() {
+        let edit = |rule: &mut ProxyRuleInner| {
+            rule.path_rules = Some(vec![path_rule(MatchType::Contains, "debug=1", RuleType::Blacklist, 0)]);
+        };
+
+        let simulation = get(&config(edit), "https://example.com/page?debug=1");
+        assert_eq!(simulation.path, "/page?debug=1");
+        assert!(!simulation.allowed);
+
+        let simulation = get(&config(|rule| { edit(rule); rule.ignore_query_string = true; }), "https://example.com/page?debug=1");
+        assert_eq!(simulation.path, "/page");
+        assert!(simulation.allowed);
+    }
+
+    #[test]
+    fn chooses_the_effective_max_age() {
+        let config = config(|rule| {
+            rule.path_rules = Some(vec![
+                path_rule(MatchType::StartsWith, "/static", RuleType::Whitelist, 86_400),
+                path_rule(MatchType::StartsWith, "/live", RuleType::Whitelist, 0),
+            ]);
+        });
+
+        assert_eq!(get(&config, "example.com/static/app.js").max_age_seconds, Some(86_400));
+        assert_eq!(get(&config, "example.com/index.html").max_age_seconds, Some(300));
+        assert_eq!(get(&config, "example.com/live/feed").max_age_seconds, None);
+
+        let post = SimulatedRequest { url: "example.com/static/app.js".into(), user_agent: None, method: Some("post".into()) };
+        assert_eq!(simulate(&config, &post).max_age_seconds, None);
+
+        let mut without_caching = config.clone();
+        without_caching.add_caching = Some(false);
+        assert_eq!(get(&without_caching, "example.com/static/app.js").max_age_seconds, None);
+    }
+
+    #[test]
+    fn rule_paths_are_assumed_prefixes() {
+        let config = config(|rule| rule.paths = Some(vec!["/app".into()]));
+
+        let simulation = get(&config, "example.com/app/home");
+        assert!(simulation.allowed);
+        assert!(simulation.trace.iter().any(|line| line.contains("assumed prefix match")));
+
+        let simulation = get(&config, "example.com/other");
+        assert_eq!(simulation.rule_type, Some(RuleType::Blacklist));
+        assert!(simulation.trace.iter().any(|line| line.contains("assumed to get the opposite rule type")));
+    }
+
+    #[test]
+    fn unknown_host_is_not_proxied() {
+        let simulation = get(&config(|_| {}), "https://other.com/");
+        assert_eq!(simulation.rule_index, None);
+        assert!(!simulation.allowed);
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    write_atomic_with(path, |file| file.write_all(contents))
+}
+
+// Writes into a temp file next to `path` and renames it over the original only once
+// everything is on disk, so a crash or a full disk never leaves a half-written config.
+pub fn write_atomic_with<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut File) -> io::Result<()>
+{
+    let temp_path = temp_path_for(path);
+
+    let result = (|| {
+        let mut temp_file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)?;
+
+        write(&mut temp_file)?;
+        temp_file.flush()?;
+        temp_file.sync_all()?;
+
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&temp_path, metadata.permissions())?;
+        }
+
+        fs::rename(&temp_path, path)?;
+        sync_parent_dir(path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+// Creates missing parent directories and makes the file readable by the current user only,
+// before anything is written to it.
+pub fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    write_atomic_with(path, |file| {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+        file.write_all(contents)
+    })
+}
+
+fn temp_path_for(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "config".into());
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.subsec_nanos())
+        .unwrap_or(0);
+
+    path.with_file_name(format!(".{}.{}.{}.tmp", file_name, process::id(), nanos))
+}
+
+// The rename itself is only durable once the directory entry is flushed.
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => File::open(parent)?.sync_all(),
+        _ => Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("arc2proxy-gui-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn failed_write_keeps_original_content() {
+        let dir = test_dir("failed-write");
+        let path = dir.join("proxy_config.toml");
+        fs::write(&path, "listening_port_http = 80\n").unwrap();
+
+        let result = write_atomic_with(&path, |file| {
+            file.write_all(b"listening_port_ht")?;
+            Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "listening_port_http = 80\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1, "temp file was not cleaned up");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn successful_write_replaces_content() {
+        let dir = test_dir("successful-write");
+        let path = dir.join("proxy_config.toml");
+        fs::write(&path, "listening_port_http = 80\n").unwrap();
+
+        write_atomic(&path, b"listening_port_http = 8080\n").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "listening_port_http = 8080\n");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = test_dir("permissions");
+        let path = dir.join("proxy_config.toml");
+        fs::write(&path, "listening_port_http = 80\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        write_atomic(&path, b"listening_port_http = 8080\n").unwrap();
+
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
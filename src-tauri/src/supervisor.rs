@@ -0,0 +1,399 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::ConfigError,
+    logs::{self, LogStream, LogTail},
+};
+
+pub const PROXY_STATUS_EVENT: &str = "proxy-status";
+pub const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[cfg(windows)]
+pub const PROXY_BINARY_NAMES: [&str; 2] = ["arc2proxy.exe", "arc2-proxy.exe"];
+#[cfg(not(windows))]
+pub const PROXY_BINARY_NAMES: [&str; 2] = ["arc2proxy", "arc2-proxy"];
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyState {
+    Stopped,
+    Running,
+    Stopping,
+    Exited
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyStatus {
+    pub state: ProxyState,
+    pub pid: Option<u32>,
+    pub binary: Option<PathBuf>,
+    pub exit_code: Option<i32>,
+    pub message: Option<String>
+}
+
+impl ProxyStatus {
+    fn stopped() -> Self {
+        ProxyStatus {
+            state: ProxyState::Stopped,
+            pid: None,
+            binary: None,
+            exit_code: None,
+            message: None
+        }
+    }
+}
+
+pub fn find_proxy_binary(config_path: &Path) -> Result<PathBuf, ConfigError> {
+    let dir = config_path.parent().unwrap_or(Path::new("."));
+
+    PROXY_BINARY_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+        .ok_or_else(|| ConfigError::validation("proxy", format!("No {} binary was found in {}.", PROXY_BINARY_NAMES[0], dir.display())))
+}
+
+type StatusListener = dyn Fn(&ProxyStatus) + Send + Sync;
+
+struct Inner {
+    child: Option<Child>,
+    status: ProxyStatus,
+    // Bumped on every start so a monitor thread of an old process never touches a new one.
+    generation: u64,
+    stopping: bool
+}
+
+// Owns the proxy process. Every state change is reported to the listener, which the
+// Tauri app forwards to the webview as a `proxy-status` event, and the process output
+// goes to `logs`.
+pub struct Supervisor {
+    inner: Arc<Mutex<Inner>>,
+    listener: Arc<StatusListener>,
+    logs: LogTail
+}
+
+impl Supervisor {
+    pub fn new<F: Fn(&ProxyStatus) + Send + Sync + 'static>(listener: F, logs: LogTail) -> Self {
+        Supervisor {
+            inner: Arc::new(Mutex::new(Inner {
+                child: None,
+                status: ProxyStatus::stopped(),
+                generation: 0,
+                stopping: false
+            })),
+            listener: Arc::new(listener),
+            logs
+        }
+    }
+
+    pub fn status(&self) -> ProxyStatus {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(status) = reap(&mut inner) {
+            drop(inner);
+            (self.listener)(&status);
+            return status;
+        }
+        inner.status.clone()
+    }
+
+    pub fn start(&self, binary: &Path, args: &[String], working_dir: &Path) -> Result<ProxyStatus, ConfigError> {
+        let mut inner = self.inner.lock().unwrap();
+        reap(&mut inner);
+
+        if inner.child.is_some() {
+            return Err(ConfigError::validation("proxy", "The proxy is already running."));
+        }
+
+        let mut child = Command::new(binary)
+            .args(args)
+            .current_dir(working_dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| ConfigError::io(binary, e))?;
+
+        if let Some(stdout) = child.stdout.take() {
+            logs::follow_stream(self.logs.clone(), LogStream::Stdout, stdout);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            logs::follow_stream(self.logs.clone(), LogStream::Stderr, stderr);
+        }
+
+        inner.generation += 1;
+        inner.stopping = false;
+        inner.status = ProxyStatus {
+            state: ProxyState::Running,
+            pid: Some(child.id()),
+            binary: Some(binary.to_path_buf()),
+            exit_code: None,
+            message: None
+        };
+        inner.child = Some(child);
+
+        let status = inner.status.clone();
+        let generation = inner.generation;
+        drop(inner);
+
+        (self.listener)(&status);
+        self.monitor(generation);
+
+        Ok(status)
+    }
+
+    // Asks the proxy to shut down and kills it if it is still alive after `timeout`.
+    pub fn stop(&self, timeout: Duration) -> Result<ProxyStatus, ConfigError> {
+        let mut inner = self.inner.lock().unwrap();
+        reap(&mut inner);
+
+        let pid = match inner.child.as_ref() {
+            Some(child) => child.id(),
+            None => return Ok(inner.status.clone())
+        };
+
+        inner.stopping = true;
+        inner.status.state = ProxyState::Stopping;
+        let status = inner.status.clone();
+        drop(inner);
+        (self.listener)(&status);
+
+        let graceful = terminate(pid).map_err(|e| ConfigError::Io {
+            path: format!("pid {}", pid),
+            message: e.to_string()
+        })?;
+
+        let deadline = if graceful { Instant::now() + timeout } else { Instant::now() };
+
+        loop {
+            let mut inner = self.inner.lock().unwrap();
+
+            if inner.child.is_some() && Instant::now() >= deadline {
+                if let Some(child) = inner.child.as_mut() {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                }
+            }
+
+            if let Some(mut status) = reap(&mut inner) {
+                if graceful && Instant::now() >= deadline {
+                    status.message = Some(format!("Killed after not stopping within {} seconds.", timeout.as_secs()));
+                    inner.status = status.clone();
+                }
+                drop(inner);
+                (self.listener)(&status);
+                return Ok(status);
+            }
+
+            if inner.child.is_none() {
+                return Ok(inner.status.clone());
+            }
+
+            drop(inner);
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    pub fn restart(&self, binary: &Path, args: &[String], working_dir: &Path, timeout: Duration) -> Result<ProxyStatus, ConfigError> {
+        self.stop(timeout)?;
+        self.start(binary, args, working_dir)
+    }
+
+    fn monitor(&self, generation: u64) {
+        let inner = Arc::clone(&self.inner);
+        let listener = Arc::clone(&self.listener);
+
+        thread::spawn(move || loop {
+            thread::sleep(POLL_INTERVAL);
+
+            let mut inner = inner.lock().unwrap();
+            if inner.generation != generation || inner.child.is_none() {
+                return;
+            }
+
+            if let Some(status) = reap(&mut inner) {
+                drop(inner);
+                listener(&status);
+                return;
+            }
+        });
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        if let Ok(mut inner) = self.inner.lock() {
+            if let Some(child) = inner.child.as_mut() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+    }
+}
+
+// Returns the new status when the child has exited since the last check.
+fn reap(inner: &mut Inner) -> Option<ProxyStatus> {
+    let exit: ExitStatus = match inner.child.as_mut().map(Child::try_wait) {
+        Some(Ok(Some(exit))) => exit,
+        _ => return None
+    };
+
+    inner.child = None;
+    inner.status = ProxyStatus {
+        state: if inner.stopping { ProxyState::Stopped } else { ProxyState::Exited },
+        pid: None,
+        binary: inner.status.binary.clone(),
+        exit_code: exit.code(),
+        message: if inner.stopping { None } else { Some(format!("The proxy exited unexpectedly ({}).", exit)) }
+    };
+    inner.stopping = false;
+
+    Some(inner.status.clone())
+}
+
+// Returns false when there is no graceful way to stop the process and it has to be killed.
+#[cfg(unix)]
+fn terminate(pid: u32) -> io::Result<bool> {
+    // SAFETY: kill only sends a signal to the given pid.
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } == 0 {
+        Ok(true)
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+fn terminate(_pid: u32) -> io::Result<bool> {
+    Ok(false)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt, process};
+
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("arc2proxy-gui-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // /bin/sh stands in for the proxy binary. Running freshly written scripts instead can
+    // fail with ETXTBSY while other tests fork.
+    fn start_dummy(supervisor: &Supervisor, dir: &Path, script: &str) -> Result<ProxyStatus, ConfigError> {
+        supervisor.start(Path::new("/bin/sh"), &["-c".to_string(), script.to_string()], dir)
+    }
+
+    fn recording_supervisor() -> (Supervisor, Arc<Mutex<Vec<ProxyState>>>) {
+        let states = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&states);
+        let supervisor = Supervisor::new(move |status| recorded.lock().unwrap().push(status.state), LogTail::default());
+        (supervisor, states)
+    }
+
+    #[test]
+    fn finds_binary_next_to_config() {
+        let dir = test_dir("find-binary");
+        let binary = dir.join(PROXY_BINARY_NAMES[0]);
+        fs::write(&binary, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&binary, fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert_eq!(find_proxy_binary(&dir.join("proxy_config.toml")).unwrap(), binary);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn start_and_stop() {
+        let dir = test_dir("start-stop");
+        let (supervisor, states) = recording_supervisor();
+
+        let status = start_dummy(&supervisor, &dir, "exec sleep 30").unwrap();
+        assert_eq!(status.state, ProxyState::Running);
+        assert!(status.pid.is_some());
+        assert!(start_dummy(&supervisor, &dir, "exec sleep 30").is_err());
+
+        let status = supervisor.stop(Duration::from_secs(5)).unwrap();
+        assert_eq!(status.state, ProxyState::Stopped);
+        assert_eq!(status.message, None);
+        assert_eq!(*states.lock().unwrap(), vec![ProxyState::Running, ProxyState::Stopping, ProxyState::Stopped]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn output_goes_to_log_tail() {
+        let dir = test_dir("output");
+        let logs = LogTail::default();
+        let supervisor = Supervisor::new(|_| {}, logs.clone());
+
+        start_dummy(&supervisor, &dir, "echo 'INFO started'; echo 'ERROR failed' >&2").unwrap();
+
+        let mut lines = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while lines.len() < 2 && Instant::now() < deadline {
+            thread::sleep(POLL_INTERVAL);
+            if let Some(batch) = logs.take_batch() {
+                logs.acknowledge(batch.sequence);
+                lines.extend(batch.lines);
+            }
+        }
+
+        lines.sort_by_key(|line| line.text.clone());
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[0].stream, lines[0].text.as_str()), (LogStream::Stderr, "ERROR failed"));
+        assert_eq!((lines[1].stream, lines[1].text.as_str()), (LogStream::Stdout, "INFO started"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stop_kills_after_timeout() {
+        let dir = test_dir("stop-timeout");
+        let (supervisor, _) = recording_supervisor();
+
+        start_dummy(&supervisor, &dir, "trap '' TERM; while true; do sleep 1; done").unwrap();
+        // Give the shell time to install the trap.
+        thread::sleep(Duration::from_millis(300));
+
+        let started = Instant::now();
+        let status = supervisor.stop(Duration::from_millis(500)).unwrap();
+
+        assert!(started.elapsed() >= Duration::from_millis(500));
+        assert_eq!(status.state, ProxyState::Stopped);
+        assert!(status.message.is_some());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unexpected_exit_is_reported() {
+        let dir = test_dir("unexpected-exit");
+        let (supervisor, states) = recording_supervisor();
+
+        start_dummy(&supervisor, &dir, "exit 3").unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while states.lock().unwrap().len() < 2 && Instant::now() < deadline {
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        let status = supervisor.status();
+        assert_eq!(status.state, ProxyState::Exited);
+        assert_eq!(status.exit_code, Some(3));
+        assert_eq!(*states.lock().unwrap(), vec![ProxyState::Running, ProxyState::Exited]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,627 @@
+use std::{
+    collections::HashSet,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use crate::{
+    flags::{CompressionFlags, MinificationFlags},
+    models::{ProxyConfiguration, ProxyRuleInner, RoutingMethod, RoutingRule},
+    path_rules,
+    schema,
+    secrets,
+};
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub field: String,
+    pub message: String,
+    pub severity: Severity
+}
+
+#[derive(serde::Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>
+}
+
+impl ValidationReport {
+    pub fn error(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.issues.push(ValidationIssue {
+            field: field.into(),
+            message: message.into(),
+            severity: Severity::Error
+        });
+    }
+
+    pub fn warning(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.issues.push(ValidationIssue {
+            field: field.into(),
+            message: message.into(),
+            severity: Severity::Warning
+        });
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &ValidationIssue> {
+        self.issues.iter().filter(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn extend(&mut self, other: ValidationReport) {
+        self.issues.extend(other.issues);
+    }
+
+    // Adds the issues of `other` for fields that have none yet, so a value that breaks both
+    // the schema and a check below is only reported once.
+    pub fn extend_new_fields(&mut self, other: ValidationReport) {
+        let known: HashSet<String> = self.issues.iter().map(|issue| issue.field.clone()).collect();
+        self.issues.extend(other.issues.into_iter().filter(|issue| !known.contains(&issue.field)));
+    }
+
+    // Errors that exist in `self` but not in `before`, so a file that is already
+    // broken can still be fixed one value at a time.
+    pub fn new_errors_since(&self, before: &ValidationReport) -> ValidationReport {
+        ValidationReport {
+            issues: self.errors().filter(|issue| !before.issues.contains(issue)).cloned().collect()
+        }
+    }
+}
+
+impl ProxyConfiguration {
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+
+        if let Some(addr) = &self.listening_address {
+            if IpAddr::from_str(addr).is_err() {
+                report.error("listening_address", format!("\"{}\" is not a valid IP address.", addr));
+            }
+        }
+
+        let http_port = self.listening_port_http.unwrap_or(80);
+        let https_port = self.listening_port_https.unwrap_or(443);
+
+        if http_port == 0 {
+            report.error("listening_port_http", "Port 0 cannot be used for listening.");
+        }
+        if https_port == 0 {
+            report.error("listening_port_https", "Port 0 cannot be used for listening.");
+        }
+        if http_port == https_port {
+            report.error("listening_port_https", format!("HTTP and HTTPS cannot both listen on port {}.", http_port));
+        }
+
+        if let Some(version) = self.proxy_min_tls_version {
+            if version.is_deprecated() {
+                report.warning("proxy_min_tls_version", format!("{} is deprecated and considered insecure.", version));
+            }
+        }
+
+        if let Some(api_key) = &self.api_key {
+            if api_key.trim().is_empty() {
+                report.warning("api_key", "API key is empty.");
+            }
+        }
+
+        for (name, value) in [("api_key", &self.api_key), ("azure_table_storage_key", &self.azure_table_storage_key)] {
+            if let Some(problem) = value.as_deref().and_then(secrets::check_reference) {
+                report.error(name, problem);
+            }
+        }
+
+        if let Some(email) = &self.lets_encrypt_contact_email {
+            if !is_plausible_email(email) {
+                report.error("lets_encrypt_contact_email", format!("\"{}\" is not a valid email address.", email));
+            }
+        }
+
+        if let Some(Err(problem)) = self.compression_flags.as_deref().map(str::parse::<CompressionFlags>) {
+            report.error("compression_flags", problem);
+        }
+
+        if self.compression_flags.is_some() && self.enable_compression == Some(false) {
+            report.warning("compression_flags", "Compression flags are set but compression is disabled.");
+        }
+
+        if self.recv_buffer_size == Some(0) {
+            report.error("recv_buffer_size", "Buffer size must be greater than 0.");
+        }
+        if self.send_buffer_size == Some(0) {
+            report.error("send_buffer_size", "Buffer size must be greater than 0.");
+        }
+
+        if let Some(ttl) = self.ip_ttl {
+            if ttl == 0 || ttl > 255 {
+                report.error("ip_ttl", "IP TTL must be between 1 and 255.");
+            }
+        }
+
+        if let Some(max_backlog) = self.max_backlog {
+            if max_backlog <= 0 {
+                report.error("max_backlog", "Max backlog must be greater than 0.");
+            }
+        }
+
+        if self.proxy_timeout == Some(0) {
+            report.error("proxy_timeout", "Proxy timeout must be greater than 0.");
+        }
+
+        if self.proxy_rules.is_empty() {
+            report.warning("proxy_rules", "No proxy rules are configured, every request will be rejected.");
+        }
+
+        let mut domains = HashSet::new();
+
+        for (index, rule) in self.proxy_rules.iter().enumerate() {
+            let prefix = format!("proxy_rules[{}]", index);
+
+            if !domains.insert(rule.domain.to_lowercase()) {
+                report.error(format!("{}.domain", prefix), format!("Domain \"{}\" is configured more than once.", rule.domain));
+            }
+
+            report.extend(rule.validate(&prefix));
+        }
+
+        report.extend_new_fields(schema::check_configuration(self));
+        report
+    }
+}
+
+impl ProxyRuleInner {
+    pub fn validate(&self, prefix: &str) -> ValidationReport {
+        let mut report = ValidationReport::default();
+
+        if self.domain.trim().is_empty() {
+            report.error(format!("{}.domain", prefix), "Domain cannot be empty.");
+        } else if self.domain.contains(char::is_whitespace) || self.domain.contains('/') {
+            report.error(format!("{}.domain", prefix), format!("\"{}\" is not a valid domain.", self.domain));
+        }
+
+        let has_forward_target = self.forward_addr.is_some() || self.forward_ipv4.is_some() || self.forward_ipv6.is_some();
+
+        if !has_forward_target && self.routing_rules.is_none() {
+            report.error(
+                format!("{}.forward_addr", prefix),
+                "One of forward_addr, forward_ipv4, forward_ipv6 or routing_rules must be set."
+            );
+        }
+
+        if let Some(ipv4) = &self.forward_ipv4 {
+            if Ipv4Addr::from_str(ipv4).is_err() {
+                report.error(format!("{}.forward_ipv4", prefix), format!("\"{}\" is not a valid IPv4 address.", ipv4));
+            }
+        }
+
+        if let Some(ipv6) = &self.forward_ipv6 {
+            if Ipv6Addr::from_str(ipv6).is_err() {
+                report.error(format!("{}.forward_ipv6", prefix), format!("\"{}\" is not a valid IPv6 address.", ipv6));
+            }
+        }
+
+        if has_forward_target && self.forward_port_http.is_none() && self.forward_port_https.is_none() {
+            report.warning(
+                format!("{}.forward_port_http", prefix),
+                "No forward port is set, the listening ports will be used."
+            );
+        }
+
+        if let Some(paths) = &self.paths {
+            for (index, path) in paths.iter().enumerate() {
+                if !path.starts_with('/') {
+                    report.error(format!("{}.paths[{}]", prefix, index), format!("\"{}\" must start with '/'.", path));
+                }
+            }
+        }
+
+        if let Some(path_rules) = &self.path_rules {
+            for (index, path_rule) in path_rules.iter().enumerate() {
+                if path_rule.path.is_empty() {
+                    report.error(format!("{}.path_rules[{}].path", prefix, index), "Path cannot be empty.");
+                }
+            }
+
+            for conflict in path_rules::analyze(path_rules) {
+                report.warning(format!("{}.path_rules[{}]", prefix, conflict.second), conflict.message);
+            }
+        }
+
+        if let Some(routing_rules) = &self.routing_rules {
+            report.extend(routing_rules.validate(&format!("{}.routing_rules", prefix)));
+        }
+
+        if let Some(user_agents) = &self.disallowed_user_agents {
+            for (index, user_agent) in user_agents.iter().enumerate() {
+                if user_agent.user_agent.is_empty() {
+                    report.error(format!("{}.disallowed_user_agents[{}].user_agent", prefix, index), "User agent cannot be empty.");
+                }
+            }
+        }
+
+        if let Some(Err(problem)) = self.compression_flags.as_deref().map(str::parse::<CompressionFlags>) {
+            report.error(format!("{}.compression_flags", prefix), problem);
+        }
+
+        if let Some(Err(problem)) = self.minification_flags.as_deref().map(str::parse::<MinificationFlags>) {
+            report.error(format!("{}.minification_flags", prefix), problem);
+        }
+
+        if self.compression_flags.is_some() && !self.enable_compression {
+            report.warning(format!("{}.compression_flags", prefix), "Compression flags are set but compression is disabled.");
+        }
+
+        if self.minification_flags.is_some() && !self.enable_minification {
+            report.warning(format!("{}.minification_flags", prefix), "Minification flags are set but minification is disabled.");
+        }
+
+        if self.webp_transformation_min_age.is_some() && !self.enable_webp_transformation {
+            report.warning(
+                format!("{}.webp_transformation_min_age", prefix),
+                "WebP minimum age is set but WebP transformation is disabled."
+            );
+        }
+
+        report.extend_new_fields(schema::check_rule(self, prefix));
+        report
+    }
+}
+
+impl RoutingRule {
+    pub fn validate(&self, prefix: &str) -> ValidationReport {
+        let mut report = ValidationReport::default();
+
+        if self.routing_locations.is_empty() {
+            report.error(format!("{}.routing_locations", prefix), "At least one routing location is required.");
+        }
+
+        if self.routing_method == RoutingMethod::Priority && !self.routing_locations.is_empty() {
+            let primaries = self.routing_locations.iter().filter(|location| location.primary == Some(true)).count();
+            if primaries != 1 {
+                report.error(
+                    format!("{}.routing_locations", prefix),
+                    format!("Priority routing needs exactly one primary location, found {}.", primaries)
+                );
+            }
+        }
+
+        let mut priorities = HashSet::new();
+
+        for (index, location) in self.routing_locations.iter().enumerate() {
+            let field = format!("{}.routing_locations[{}]", prefix, index);
+
+            if location.forward_addr.is_none() && location.forward_ipv4.is_none() && location.forward_ipv6.is_none() {
+                report.error(format!("{}.forward_addr", field), "One of forward_addr, forward_ipv4 or forward_ipv6 must be set.");
+            }
+
+            if let Some(ipv4) = &location.forward_ipv4 {
+                if Ipv4Addr::from_str(ipv4).is_err() {
+                    report.error(format!("{}.forward_ipv4", field), format!("\"{}\" is not a valid IPv4 address.", ipv4));
+                }
+            }
+
+            if let Some(ipv6) = &location.forward_ipv6 {
+                if Ipv6Addr::from_str(ipv6).is_err() {
+                    report.error(format!("{}.forward_ipv6", field), format!("\"{}\" is not a valid IPv6 address.", ipv6));
+                }
+            }
+
+            if self.https_only && location.forward_port_https.is_none() {
+                report.error(format!("{}.forward_port_https", field), "An HTTPS port is required because https_only is set.");
+            } else if !self.https_only && location.forward_port_http.is_none() {
+                report.error(format!("{}.forward_port_http", field), "An HTTP port is required because https_only is not set.");
+            }
+
+            if let Some(priority) = location.priority {
+                if !priorities.insert(priority) {
+                    report.error(format!("{}.priority", field), format!("Priority {} is used by more than one location.", priority));
+                }
+            } else if self.routing_method == RoutingMethod::Priority && location.primary != Some(true) {
+                report.warning(format!("{}.priority", field), "No priority is set for this fallback location.");
+            }
+        }
+
+        if self.enable_health_checks {
+            match &self.health_check_path {
+                None => report.error(format!("{}.health_check_path", prefix), "A health check path is required when health checks are enabled."),
+                Some(path) if !path.starts_with('/') => {
+                    report.error(format!("{}.health_check_path", prefix), format!("\"{}\" must start with '/'.", path))
+                }
+                Some(_) => {}
+            }
+
+            if self.health_check_interval == 0 {
+                report.error(format!("{}.health_check_interval", prefix), "Health check interval must be above 0.");
+            }
+        }
+
+        report
+    }
+}
+
+fn is_plausible_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => !local.is_empty() && domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.'),
+        None => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::ConfigError, models::RoutingLocation, rules};
+
+    use super::*;
+
+    fn location(primary: Option<bool>, priority: Option<u16>) -> RoutingLocation {
+        RoutingLocation {
+            primary,
+            priority,
+            forward_addr: Some("backend.internal".into()),
+            forward_ipv4: None,
+            forward_ipv6: None,
+            forward_port_http: Some(8080),
+            forward_port_https: None
+        }
+    }
+
+    fn routing_rule(routing_locations: Vec<RoutingLocation>) -> RoutingRule {
+        RoutingRule {
+            routing_method: RoutingMethod::Priority,
+            routing_locations,
+            https_only: false,
+            enable_health_checks: false,
+            health_check_interval: 0,
+            health_check_path: None
+        }
+    }
+
+    fn errors(rule: &RoutingRule) -> Vec<(String, String)> {
+        rule.validate("routing_rules").errors().map(|issue| (issue.field.clone(), issue.message.clone())).collect()
+    }
+
+    #[test]
+    fn priority_routing_needs_exactly_one_primary() {
+        assert!(errors(&routing_rule(vec![location(Some(true), None), location(None, Some(1))])).is_empty());
+
+        for locations in [vec![location(None, Some(1))], vec![location(Some(true), None), location(Some(true), Some(1))]] {
+            let count = locations.iter().filter(|location| location.primary == Some(true)).count();
+            assert_eq!(errors(&routing_rule(locations)), vec![(
+                "routing_rules.routing_locations".to_string(),
+                format!("Priority routing needs exactly one primary location, found {}.", count)
+            )]);
+        }
+
+        let mut weighted = routing_rule(vec![location(None, None), location(None, None)]);
+        weighted.routing_method = RoutingMethod::Weighted;
+        assert!(errors(&weighted).is_empty());
+
+        assert_eq!(errors(&routing_rule(Vec::new())).len(), 1);
+    }
+
+    #[test]
+    fn priorities_must_be_unique() {
+        let rule = routing_rule(vec![location(Some(true), Some(1)), location(None, Some(2)), location(None, Some(2))]);
+        assert_eq!(errors(&rule), vec![(
+            "routing_rules.routing_locations[2].priority".to_string(),
+            "Priority 2 is used by more than one location.".to_string()
+        )]);
+
+        let report = routing_rule(vec![location(Some(true), None), location(None, None)]).validate("routing_rules");
+        assert!(!report.has_errors());
+        assert_eq!(report.issues.len(), 1, "a fallback without priority is only a warning");
+    }
+
+    #[test]
+    fn checks_addresses_and_ports() {
+        let mut missing = location(Some(true), None);
+        missing.forward_addr = None;
+        let mut bad_ips = location(None, Some(1));
+        bad_ips.forward_ipv4 = Some("10.0.0".into());
+        bad_ips.forward_ipv6 = Some("fe80::1::1".into());
+
+        let fields: Vec<String> = errors(&routing_rule(vec![missing, bad_ips])).into_iter().map(|(field, _)| field).collect();
+        assert_eq!(fields, vec![
+            "routing_rules.routing_locations[0].forward_addr",
+            "routing_rules.routing_locations[1].forward_ipv4",
+            "routing_rules.routing_locations[1].forward_ipv6",
+        ]);
+
+        let mut https_only = routing_rule(vec![location(Some(true), None)]);
+        https_only.https_only = true;
+        assert_eq!(errors(&https_only), vec![(
+            "routing_rules.routing_locations[0].forward_port_https".to_string(),
+            "An HTTPS port is required because https_only is set.".to_string()
+        )]);
+
+        https_only.routing_locations[0].forward_port_https = Some(8443);
+        assert!(errors(&https_only).is_empty());
+    }
+
+    #[test]
+    fn checks_health_check_fields() {
+        let mut rule = routing_rule(vec![location(Some(true), None)]);
+        rule.enable_health_checks = true;
+
+        let fields: Vec<String> = errors(&rule).into_iter().map(|(field, _)| field).collect();
+        assert_eq!(fields, vec!["routing_rules.health_check_path", "routing_rules.health_check_interval"]);
+
+        rule.health_check_path = Some("health".into());
+        rule.health_check_interval = 30;
+        assert_eq!(errors(&rule), vec![("routing_rules.health_check_path".to_string(), "\"health\" must start with '/'.".to_string())]);
+
+        rule.health_check_path = Some("/health".into());
+        assert!(errors(&rule).is_empty());
+    }
+
+    fn config(rules: &[&str]) -> ProxyConfiguration {
+        let mut config: ProxyConfiguration = toml::from_str("proxy_rules = []").unwrap();
+        for domain in rules {
+            let rule: ProxyRuleInner = toml::from_str(&format!(
+                r#"
+domain = "{}"
+max_age_seconds = 0
+rule_type = "Whitelist"
+enable_logging = false
+ignore_query_string = false
+enable_sql_injection_protection = false
+enable_compression = false
+enable_minification = false
+enable_webp_transformation = false
+forward_ipv4 = "10.0.0.1"
+forward_port_http = 8080
+"#,
+                domain
+            ))
+            .unwrap();
+            config.proxy_rules.push(rule);
+        }
+        config
+    }
+
+    fn config_errors(config: &ProxyConfiguration) -> Vec<(String, String)> {
+        config.validate().errors().map(|issue| (issue.field.clone(), issue.message.clone())).collect()
+    }
+
+    #[test]
+    fn checks_listening_address_and_ports() {
+        let mut config = config(&["example.com"]);
+        assert!(config_errors(&config).is_empty());
+
+        config.listening_address = Some("0.0.0".into());
+        assert_eq!(config_errors(&config), vec![(
+            "listening_address".to_string(),
+            "\"0.0.0\" is not a valid IP address.".to_string()
+        )]);
+        config.listening_address = Some("::".into());
+        assert!(config_errors(&config).is_empty());
+
+        config.listening_port_http = Some(0);
+        config.listening_port_https = Some(0);
+        assert_eq!(config_errors(&config), vec![
+            ("listening_port_http".to_string(), "Port 0 cannot be used for listening.".to_string()),
+            ("listening_port_https".to_string(), "Port 0 cannot be used for listening.".to_string()),
+            ("listening_port_https".to_string(), "HTTP and HTTPS cannot both listen on port 0.".to_string()),
+        ]);
+
+        config.listening_port_http = Some(8443);
+        config.listening_port_https = None;
+        assert!(config_errors(&config).is_empty());
+        config.listening_port_https = Some(8443);
+        assert_eq!(config_errors(&config), vec![(
+            "listening_port_https".to_string(),
+            "HTTP and HTTPS cannot both listen on port 8443.".to_string()
+        )]);
+
+        config.listening_port_http = None;
+        config.listening_port_https = Some(80);
+        assert_eq!(config_errors(&config).len(), 1, "an unset HTTP port defaults to 80");
+    }
+
+    #[test]
+    fn checks_contact_email() {
+        let mut config = config(&["example.com"]);
+
+        for email in ["admin", "@example.com", "admin@example", "admin@.com", "admin@example."] {
+            config.lets_encrypt_contact_email = Some(email.into());
+            assert_eq!(config_errors(&config), vec![(
+                "lets_encrypt_contact_email".to_string(),
+                format!("\"{}\" is not a valid email address.", email)
+            )]);
+        }
+
+        config.lets_encrypt_contact_email = Some("admin@example.com".into());
+        assert!(config_errors(&config).is_empty());
+    }
+
+    #[test]
+    fn domains_must_be_unique_ignoring_case() {
+        assert!(config_errors(&config(&["example.com", "www.example.com"])).is_empty());
+
+        assert_eq!(config_errors(&config(&["example.com", "www.example.com", "Example.COM"])), vec![(
+            "proxy_rules[2].domain".to_string(),
+            "Domain \"Example.COM\" is configured more than once.".to_string()
+        )]);
+    }
+
+    #[test]
+    fn only_new_errors_block_a_save() {
+        let mut before = config(&["example.com"]);
+        before.listening_address = Some("localhost".into());
+        let before_report = before.validate();
+        assert!(before_report.has_errors());
+
+        let mut after = before.clone();
+        after.proxy_timeout = Some(30);
+        assert_eq!(after.validate().new_errors_since(&before_report), ValidationReport::default());
+
+        after.listening_port_http = Some(0);
+        let new_errors = after.validate().new_errors_since(&before_report);
+        let fields: Vec<&str> = new_errors.issues.iter().map(|issue| issue.field.as_str()).collect();
+        assert_eq!(fields, vec!["listening_port_http"]);
+
+        after.proxy_rules.clear();
+        assert!(after.validate().issues.iter().any(|issue| issue.severity == Severity::Warning));
+        assert_eq!(after.validate().new_errors_since(&before_report).issues.len(), 1, "warnings never block a save");
+    }
+
+    #[test]
+    fn extend_new_fields_skips_fields_that_already_have_issues() {
+        let mut report = ValidationReport::default();
+        report.error("listening_port_http", "Expected an integer.");
+
+        let mut other = ValidationReport::default();
+        other.error("listening_port_http", "Port 0 cannot be used for listening.");
+        other.warning("proxy_rules", "No proxy rules are configured, every request will be rejected.");
+        other.error("proxy_rules", "Duplicate.");
+        report.extend_new_fields(other);
+
+        let issues: Vec<(&str, &str)> = report.issues.iter().map(|issue| (issue.field.as_str(), issue.message.as_str())).collect();
+        assert_eq!(issues, vec![
+            ("listening_port_http", "Expected an integer."),
+            ("proxy_rules", "No proxy rules are configured, every request will be rejected."),
+            ("proxy_rules", "Duplicate."),
+        ]);
+    }
+
+    #[test]
+    fn unknown_flags_are_errors_that_block_a_save() {
+        let mut config = config(&["example.com"]);
+        config.proxy_rules[0].enable_compression = true;
+        config.proxy_rules[0].enable_minification = true;
+        let before = config.validate();
+
+        config.compression_flags = Some("gzpi".into());
+        config.proxy_rules[0].compression_flags = Some("gzip=6,gzpi".into());
+        config.proxy_rules[0].minification_flags = Some("html,svg".into());
+        let flag_errors: Vec<String> = config.validate().new_errors_since(&before).issues.into_iter().map(|issue| issue.field).collect();
+        assert_eq!(flag_errors, vec!["compression_flags", "proxy_rules[0].compression_flags", "proxy_rules[0].minification_flags"]);
+
+        let mut config = self::config(&["example.com"]);
+        config.proxy_rules[0].enable_compression = true;
+        let error = rules::edit_rule(&mut config, "example.com", |rule| {
+            rule.compression_flags = Some("gzpi".into());
+            Ok(())
+        })
+        .unwrap_err();
+        match error {
+            ConfigError::Invalid { issues } => {
+                assert_eq!(issues.len(), 1);
+                assert_eq!(issues[0].field, "proxy_rules[0].compression_flags");
+                assert_eq!(issues[0].message, "Unknown compression flag \"gzpi\", expected gzip, brotli, zstd or mime=TYPE.");
+            }
+            other => panic!("expected the save to be refused, got {:?}", other)
+        }
+        assert_eq!(config.proxy_rules[0].compression_flags, None);
+
+        config.proxy_rules[0].compression_flags = Some("gzip=6".into());
+        assert!(!config.validate().has_errors());
+    }
+}
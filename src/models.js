@@ -90,7 +90,10 @@ class ProxyRuleInner {
         this.enable_sql_injection_protection = false; // boolean
         this.disallowed_user_agents = []; // array of UserAgentRule
         this.enable_compression = false; // boolean
-        this.compression_flags = undefined; // string
+        this.compression_flags = undefined; // string, e.g. gzip=6,brotli,mime=text/html
+        this.enable_minification = false; // boolean
+        this.minification_flags = undefined; // string, e.g. html,css,js
+        this.manual_certificate = undefined; // boolean
     }
 }
   
@@ -132,4 +135,17 @@ class UserAgentRule {
         this.match_type = MatchType.Equals; // MatchType
     }
 }
-  
\ No newline at end of file
+  
+
+export {
+    RuleType,
+    MatchType,
+    RoutingMethod,
+    ProxyConfiguration,
+    ProxyConfigurationInner,
+    ProxyRuleInner,
+    PathRule,
+    RoutingRule,
+    RoutingLocation,
+    UserAgentRule,
+};
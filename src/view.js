@@ -1,50 +1,212 @@
+import { ProxyRuleInner } from './models.js';
+
 const { invoke } = window.__TAURI__.core;
 
 //GENERAL 
 
 async function load_configuration() {
-  await get_configuration();
-  const listening_address = document.querySelector('#listening_address');
-  const listening_port_http = document.querySelector('#listening_port_http');
-  const listening_port_https = document.querySelector('#listening_port_https');
-  const logging_level = document.querySelector('#logging_level');
-  const add_caching = document.querySelector('#add_caching');
-  const add_rate_limiting = document.querySelector('#add_rate_limiting');
-  const add_logging = document.querySelector('#add_logging');
-  const disable_default_body_limit = document.querySelector('#disable_default_body_limit');
-  const add_sql_injection_protection = document.querySelector('#add_sql_injection_protection');
-  const recv_buffer_size = document.querySelector('#recv_buffer_size');
-  const send_buffer_size = document.querySelector('#send_buffer_size');
-  const ip_ttl = document.querySelector('#ip_ttl');
-  const tcp_keep_alive_seconds = document.querySelector('#tcp_keep_alive_seconds');
-  const max_backlog = document.querySelector('#max_backlog');
-
-  listening_address.textContent = config.listening_address;
-  listening_port_http.textContent = config.listening_port_http;
-  listening_port_https.textContent = config.listening_port_https;
-  logging_level.textContent = config.logging_level;
-  add_caching.textContent = config.add_caching;
-  add_rate_limiting.textContent = config.add_rate_limiting;
-  add_logging.textContent = config.add_logging;
-  disable_default_body_limit.textContent = config.disable_default_body_limit;
-  add_sql_injection_protection.textContent = config.add_sql_injection_protection;
-  recv_buffer_size.textContent = config.recv_buffer_size;
-  send_buffer_size.textContent = config.send_buffer_size;
-  ip_ttl.textContent = config.ip_ttl;
-  tcp_keep_alive_seconds.textContent = config.tcp_keep_alive_seconds;
-  max_backlog.textContent = config.max_backlog;
+  await load_location();
+
+  try {
+    await get_configuration();
+    if (settings === undefined) {
+      settings = await invoke('get_settings');
+    }
+  } catch (error) {
+    show_config_error(error);
+    return;
+  }
+
+  hide_config_error();
+  await load_problems();
+
+  await begin_session();
+
+  render_settings();
+  render_rules(config.proxy_rules);
+  await load_backups();
+  await load_certificates();
+}
+
+function display_value(setting) {
+  const value = config[setting.name];
+  if (value === null || value === undefined) {
+    return setting.default === null ? '' : `${setting.default} (default)`;
+  }
+  return value;
+}
+
+function render_settings() {
+  const table = document.querySelector('#startup_settings');
+  table.innerHTML = '';
+
+  let tr;
+  settings.forEach((setting, index) => {
+    if (index % 3 === 0) {
+      tr = document.createElement('tr');
+      table.appendChild(tr);
+    }
+
+    const td = document.createElement('td');
+    const b = document.createElement('b');
+    const span = document.createElement('span');
+    b.innerText = `${setting.label}: `;
+    span.id = setting.name;
+    span.innerText = display_value(setting);
+    const pending = pendingChanges.find(change => change.path === setting.name);
+    if (pending) {
+      span.innerText = `${setting.secret ? '********' : (pending.new_value ?? '')} (pending)`;
+      td.classList.add('pending');
+    }
+    td.appendChild(b);
+    td.appendChild(span);
+    td.addEventListener('click', openModal);
+    tr.appendChild(td);
+  });
 }
 
 //PAGE
 
 let config;
+let settings;
 
 async function get_configuration() {
   config = await invoke("get_configuration");
 }
 
+//LOCATION
+
+const configLocation = document.getElementById('config_location');
+const openConfigButton = document.getElementById('open_config');
+const forgetConfigButton = document.getElementById('forget_config');
+
+const sourceNames = {
+  Selected: 'opened in this session',
+  CommandLine: '--config argument',
+  Environment: 'ARC2PROXY_CONFIG',
+  Preference: 'saved preference',
+  ExeDirectory: 'next to the application',
+  UserConfigDirectory: 'user config directory',
+  SystemDirectory: 'system config directory',
+};
+
+async function load_location() {
+  try {
+    const location = await invoke('get_configuration_location');
+    configLocation.innerText = `${location.path} (${sourceNames[location.source]})`;
+  } catch (error) {
+    configLocation.innerText = 'No configuration file found';
+  }
+}
+
+openConfigButton.addEventListener('click', async () => {
+  const path = prompt('Path to proxy_config.toml:');
+  if (!path) {
+    return;
+  }
+
+  const remember = confirm('Open this file automatically next time?');
+
+  try {
+    await invoke('open_configuration', { path, remember });
+    await load_configuration();
+  } catch (error) {
+    show_config_error(error);
+  }
+});
+
+forgetConfigButton.addEventListener('click', async () => {
+  try {
+    await invoke('forget_configuration');
+  } catch (error) {
+    show_config_error(error);
+  }
+  await load_configuration();
+});
+
+document.getElementById('copy_schema').addEventListener('click', async () => {
+  const schema = await invoke('get_configuration_schema');
+  await navigator.clipboard.writeText(JSON.stringify(schema, null, 2));
+});
+
+//ERRORS
+
+const configError = document.getElementById('config_error');
+
+function describe_error(error) {
+  if (typeof error === 'string') {
+    return error;
+  }
+
+  switch (error.kind) {
+    case 'NotFound':
+      return `Configuration file ${error.path} was not found.`;
+    case 'Io':
+      return `Could not access ${error.path}: ${error.message}`;
+    case 'Parse': {
+      let text = `Could not parse ${error.path}`;
+      if (error.line != null) {
+        text += ` at line ${error.line}, column ${error.column}`;
+      }
+      if (error.key != null) {
+        text += ` (key "${error.key}")`;
+      }
+      return `${text}: ${error.message}`;
+    }
+    case 'Validation':
+      return `Invalid value for "${error.field}": ${error.message}`;
+    case 'Invalid':
+      return `Configuration has ${error.issues.length} problem(s), nothing was saved.`;
+    default:
+      return error.message ?? JSON.stringify(error);
+  }
+}
+
+function show_config_error(error) {
+  configError.innerText = describe_error(error);
+  configError.style.display = 'block';
+}
+
+function hide_config_error() {
+  configError.style.display = 'none';
+}
+
+//PROBLEMS
+
+const problemsPanel = document.getElementById('problems');
+const problemsList = document.getElementById('problems_list');
+
+async function load_problems() {
+  try {
+    const report = await invoke('validate_configuration');
+    render_problems(report.issues);
+  } catch (error) {
+    show_config_error(error);
+  }
+}
+
+function render_problems(issues) {
+  problemsList.innerHTML = '';
+
+  if (issues.length === 0) {
+    problemsPanel.style.display = 'none';
+    return;
+  }
+
+  issues.forEach(issue => {
+    const li = document.createElement('li');
+    li.className = issue.severity === 'Error' ? 'problem-error' : 'problem-warning';
+    li.innerText = `${issue.severity}: ${issue.field} - ${issue.message}`;
+    problemsList.appendChild(li);
+  });
+
+  problemsPanel.style.display = 'block';
+}
+
 window.addEventListener("DOMContentLoaded", async () => {
   await load_configuration();
+  await load_proxy_status();
+  await load_log_file();
 });
 
 //MODAL
@@ -55,7 +217,11 @@ const closeButton = document.querySelector('.close-button');
 
 const modalTitle = document.getElementById('modalTitle');
 const modalInput = document.getElementById('modalInput');
+const modalSelect = document.getElementById('modalSelect');
 const modalForm = document.getElementById('modalForm');
+const secretOptions = document.getElementById('secret_options');
+const encryptSecret = document.getElementById('encrypt_secret');
+const rotateApiKeyButton = document.getElementById('rotate_api_key');
 
 const successMessage = document.getElementById('success');
 const dangerMessage = document.getElementById('danger');
@@ -70,12 +236,20 @@ window.addEventListener('click', (event) => {
   if (event.target === modal) {
     modal.style.display = 'none';
   }
+  if (event.target === ruleModal) {
+    ruleModal.style.display = 'none';
+  }
+  if (event.target === reviewModal) {
+    reviewModal.style.display = 'none';
+  }
 });
 
 // Close modal with Escape key
 document.addEventListener('keydown', (event) => {
   if (event.key === 'Escape') {
     modal.style.display = 'none';
+    ruleModal.style.display = 'none';
+    reviewModal.style.display = 'none';
   }
 });
 
@@ -85,7 +259,13 @@ function openModal(event) {
     const td = event.currentTarget;
     const titleText = td.querySelector('b').innerText;
     const inputName = td.querySelector('span').id;
-    const inputValue = td.querySelector('span').innerText;
+
+    if (inputName === 'compression_flags') {
+        open_flags_modal(null);
+        return;
+    }
+    const setting = settings.find(s => s.name === inputName);
+    const inputValue = setting.secret ? '' : (config[inputName] ?? '');
 
     // Set modal title
     modalTitle.innerText = titleText;
@@ -93,29 +273,1401 @@ function openModal(event) {
     // Set input name and clear any previous value
     modalInput.name = inputName;
     modalInput.value = inputValue;
+    modalInput.type = setting.secret ? 'password' : 'text';
+
+    // Settings with a fixed set of values get a dropdown, the empty option falls back to the default.
+    const hasChoices = setting.allowed_values.length > 0;
+    modalInput.style.display = hasChoices ? 'none' : 'inline-block';
+    modalSelect.style.display = hasChoices ? 'inline-block' : 'none';
+    modalSelect.innerHTML = '';
+    if (hasChoices) {
+        const option = document.createElement('option');
+        option.value = '';
+        option.innerText = setting.default === null ? '(not set)' : `(default: ${setting.default})`;
+        modalSelect.appendChild(option);
+        setting.allowed_values.forEach(value => {
+            const option = document.createElement('option');
+            option.value = value;
+            option.innerText = value;
+            modalSelect.appendChild(option);
+        });
+        modalSelect.value = inputValue;
+    }
+
+    secretOptions.style.display = setting.secret ? 'block' : 'none';
+    encryptSecret.checked = false;
+    rotateApiKeyButton.style.display = inputName === 'api_key' ? 'inline-block' : 'none';
   
     // Display the modal
     modal.style.display = 'block';
 }
 
-const tds = document.querySelectorAll('#startup_settings td');
-
-tds.forEach(td => {
-    td.addEventListener('click', openModal);
-});
-
 modalForm.addEventListener('submit', async (e) => {
     e.preventDefault();
 
     var settingName = modalInput.name;
-    var settingValue = modalInput.value;
-    var success = await invoke('save_value', { settingName, settingValue });
+    const setting = settings.find(s => s.name === settingName);
+    var settingValue = setting.allowed_values.length > 0 ? modalSelect.value : modalInput.value;
 
-    if (success) {
+    try {
+        if (setting.secret) {
+            config = await invoke('set_secret', { settingName, settingValue, encrypt: encryptSecret.checked });
+            modalInput.value = '';
+            successMessage.innerText = 'Secret saved.';
+        } else {
+            pendingChanges = await invoke('stage_value', { settingName, settingValue });
+            successMessage.innerText = 'Change queued, review it before saving.';
+        }
         successMessage.style.display = 'block';
-    } else {
+    } catch (error) {
+        dangerMessage.innerText = describe_error(error);
+        dangerMessage.style.display = 'block';
+        return;
+    }
+
+    render_settings();
+    render_pending();
+});
+
+rotateApiKeyButton.addEventListener('click', async () => {
+    successMessage.style.display = 'none';
+    dangerMessage.style.display = 'none';
+
+    if (!confirm('Replace the API key with a new random one? Clients using the current key stop working.')) {
+        return;
+    }
+
+    try {
+        const apiKey = await invoke('rotate_api_key');
+        await get_configuration();
+        successMessage.innerText = `New API key: ${apiKey}\nCopy it now, it is not shown again.`;
+        successMessage.style.display = 'block';
+    } catch (error) {
+        dangerMessage.innerText = describe_error(error);
         dangerMessage.style.display = 'block';
+        return;
+    }
+
+    render_settings();
+});
+
+//PENDING CHANGES
+
+const pendingBar = document.getElementById('pending_bar');
+const pendingCount = document.getElementById('pending_count');
+const reviewModal = document.getElementById('reviewModal');
+const reviewList = document.getElementById('review_list');
+const reviewError = document.getElementById('review_error');
+
+let pendingChanges = [];
+
+async function begin_session() {
+  if (pendingChanges.length > 0) {
+    return;
+  }
+
+  try {
+    await invoke('begin_session');
+  } catch (error) {
+    show_config_error(error);
+  }
+}
+
+function ensure_no_pending() {
+  if (pendingChanges.length > 0) {
+    show_config_error('Save or discard the pending changes first.');
+    return false;
+  }
+  return true;
+}
+
+function render_pending() {
+  pendingCount.innerText = pendingChanges.length;
+  pendingBar.style.display = pendingChanges.length > 0 ? 'block' : 'none';
+}
+
+function format_change_value(value) {
+  if (value === null || value === undefined) {
+    return '(unset)';
+  }
+  return typeof value === 'object' ? JSON.stringify(value) : String(value);
+}
+
+async function open_review() {
+  reviewError.style.display = 'none';
+
+  try {
+    pendingChanges = await invoke('preview_changes');
+  } catch (error) {
+    show_config_error(error);
+    return;
+  }
+
+  reviewList.innerHTML = '';
+  pendingChanges.forEach(change => {
+    const li = document.createElement('li');
+    li.className = `change-${change.kind.toLowerCase()}`;
+    li.innerText = `${change.path}: ${format_change_value(change.old_value)} → ${format_change_value(change.new_value)}`;
+    reviewList.appendChild(li);
+  });
+
+  reviewModal.style.display = 'block';
+}
+
+async function commit_changes() {
+  try {
+    await invoke('commit_changes');
+  } catch (error) {
+    reviewError.innerText = describe_error(error);
+    reviewError.style.display = 'block';
+    if (error.kind === 'Invalid') {
+      render_problems(error.issues);
     }
+    return;
+  }
+
+  pendingChanges = [];
+  render_pending();
+  reviewModal.style.display = 'none';
+  await load_configuration();
+}
+
+async function discard_changes() {
+  await invoke('discard_changes');
+  pendingChanges = [];
+  render_pending();
+  reviewModal.style.display = 'none';
+  await load_configuration();
+}
+
+document.getElementById('review_changes').addEventListener('click', open_review);
+document.getElementById('discard_pending').addEventListener('click', discard_changes);
+document.getElementById('commit_changes').addEventListener('click', commit_changes);
+document.getElementById('discard_changes').addEventListener('click', discard_changes);
+reviewModal.querySelector('.close-button').addEventListener('click', () => {
+  reviewModal.style.display = 'none';
+});
+
+//PROXY RULES
+
+const rulesTable = document.getElementById('proxy_rules');
+const addRuleButton = document.getElementById('add_rule');
+
+const ruleModal = document.getElementById('ruleModal');
+const ruleModalTitle = document.getElementById('ruleModalTitle');
+const ruleForm = document.getElementById('ruleForm');
+const ruleInput = document.getElementById('ruleInput');
+const ruleError = document.getElementById('ruleError');
+
+// Domain of the rule being edited, null when adding a new one
+let editingDomain = null;
+
+function forward_target(rule) {
+  if (rule.routing_rules) {
+    return `${rule.routing_rules.routing_method} (${rule.routing_rules.routing_locations.length} locations)`;
+  }
+  const addr = rule.forward_addr ?? rule.forward_ipv4 ?? rule.forward_ipv6 ?? '';
+  const port = rule.forward_port_https ?? rule.forward_port_http ?? '';
+  return port === '' ? addr : `${addr}:${port}`;
+}
+
+function rule_button(text, handler) {
+  const button = document.createElement('button');
+  button.innerText = text;
+  button.addEventListener('click', handler);
+  return button;
+}
+
+function render_rules(rules) {
+  rulesTable.innerHTML = '';
+
+  rules.forEach((rule, index) => {
+    const tr = document.createElement('tr');
+
+    const domainTd = document.createElement('td');
+    domainTd.innerHTML = `<b>${rule.domain}</b>`;
+    tr.appendChild(domainTd);
+
+    const targetTd = document.createElement('td');
+    targetTd.innerText = forward_target(rule);
+    tr.appendChild(targetTd);
+
+    const actionsTd = document.createElement('td');
+    actionsTd.appendChild(rule_button('Edit', () => open_rule_modal(rule)));
+    actionsTd.appendChild(rule_button('Paths', () => open_path_rule_modal(rule.domain)));
+    actionsTd.appendChild(rule_button('Routing', () => open_routing_modal(rule.domain)));
+    actionsTd.appendChild(rule_button('Flags', () => open_flags_modal(rule.domain)));
+    actionsTd.appendChild(rule_button('Duplicate', () => duplicate_rule(rule.domain)));
+    actionsTd.appendChild(rule_button('Up', () => move_rule(index, -1)));
+    actionsTd.appendChild(rule_button('Down', () => move_rule(index, 1)));
+    actionsTd.appendChild(rule_button('Delete', () => delete_rule(rule.domain)));
+    tr.appendChild(actionsTd);
+
+    rulesTable.appendChild(tr);
+  });
+}
+
+async function run_rule_command(command, args) {
+  if (!ensure_no_pending()) {
+    return false;
+  }
+
+  try {
+    const rules = await invoke(command, args);
+    config.proxy_rules = rules;
+    render_rules(rules);
+    hide_config_error();
+    await load_problems();
+    await begin_session();
+    return true;
+  } catch (error) {
+    show_config_error(error);
+    return false;
+  }
+}
+
+function open_rule_modal(rule) {
+  ruleError.style.display = 'none';
+  editingDomain = rule ? rule.domain : null;
+  ruleModalTitle.innerText = rule ? `Edit ${rule.domain}` : 'Add Proxy Rule';
+  ruleInput.value = JSON.stringify(rule ?? new ProxyRuleInner(), null, 2);
+  ruleModal.style.display = 'block';
+}
+
+async function duplicate_rule(domain) {
+  const newDomain = prompt(`Duplicate ${domain} as:`);
+  if (newDomain) {
+    await run_rule_command('duplicate_proxy_rule', { domain, newDomain });
+  }
+}
+
+async function delete_rule(domain) {
+  if (confirm(`Delete the rule for ${domain}?`)) {
+    await run_rule_command('delete_proxy_rule', { domain });
+  }
+}
+
+async function move_rule(index, offset) {
+  const target = index + offset;
+  if (target < 0 || target >= config.proxy_rules.length) {
+    return;
+  }
+
+  const domains = config.proxy_rules.map(rule => rule.domain);
+  [domains[index], domains[target]] = [domains[target], domains[index]];
+  await run_rule_command('reorder_proxy_rules', { domains });
+}
+
+addRuleButton.addEventListener('click', () => open_rule_modal(null));
+
+ruleModal.querySelector('.close-button').addEventListener('click', () => {
+  ruleModal.style.display = 'none';
+});
+
+ruleForm.addEventListener('submit', async (e) => {
+  e.preventDefault();
+
+  let rule;
+  try {
+    rule = JSON.parse(ruleInput.value);
+  } catch (error) {
+    ruleError.innerText = `Invalid JSON: ${error.message}`;
+    ruleError.style.display = 'block';
+    return;
+  }
+
+  if (!ensure_no_pending()) {
+    return;
+  }
+
+  try {
+    const rules = editingDomain === null
+      ? await invoke('add_proxy_rule', { rule })
+      : await invoke('update_proxy_rule', { domain: editingDomain, rule });
+    config.proxy_rules = rules;
+    render_rules(rules);
+    ruleModal.style.display = 'none';
+    await load_problems();
+    await begin_session();
+  } catch (error) {
+    ruleError.innerText = describe_error(error);
+    ruleError.style.display = 'block';
+  }
+});
+
+//PATH RULES
+
+const pathRuleModal = document.getElementById('pathRuleModal');
+const pathRuleModalTitle = document.getElementById('pathRuleModalTitle');
+const pathRulesTable = document.getElementById('path_rules');
+const pathRuleConflicts = document.getElementById('path_rule_conflicts');
+const pathRuleForm = document.getElementById('pathRuleForm');
+const pathRulePath = document.getElementById('pathRulePath');
+const pathRuleMatchType = document.getElementById('pathRuleMatchType');
+const pathRuleRuleType = document.getElementById('pathRuleRuleType');
+const pathRuleMaxAge = document.getElementById('pathRuleMaxAge');
+const pathRuleSubmit = document.getElementById('pathRuleSubmit');
+const pathRuleCancel = document.getElementById('pathRuleCancel');
+const pathRuleError = document.getElementById('pathRuleError');
+
+// Domain whose path rules are shown, and the index being edited or null when adding
+let pathRuleDomain = null;
+let editingPathRule = null;
+let pathRules = [];
+
+function reset_path_rule_form() {
+  editingPathRule = null;
+  pathRulePath.value = '';
+  pathRuleMatchType.value = 'StartsWith';
+  pathRuleRuleType.value = 'Whitelist';
+  pathRuleMaxAge.value = 0;
+  pathRuleSubmit.innerText = 'Add';
+  pathRuleCancel.style.display = 'none';
+}
+
+function edit_path_rule(index) {
+  const pathRule = pathRules[index];
+  editingPathRule = index;
+  pathRulePath.value = pathRule.path;
+  pathRuleMatchType.value = pathRule.match_type;
+  pathRuleRuleType.value = pathRule.rule_type;
+  pathRuleMaxAge.value = pathRule.max_age_seconds;
+  pathRuleSubmit.innerText = 'Update';
+  pathRuleCancel.style.display = 'inline';
+}
+
+async function render_path_rules(rules) {
+  pathRules = rules;
+  pathRulesTable.innerHTML = '';
+
+  rules.forEach((pathRule, index) => {
+    const tr = document.createElement('tr');
+
+    const ruleTd = document.createElement('td');
+    ruleTd.innerText = `#${index + 1} ${pathRule.rule_type} ${pathRule.match_type} "${pathRule.path}" (max age ${pathRule.max_age_seconds}s)`;
+    tr.appendChild(ruleTd);
+
+    const actionsTd = document.createElement('td');
+    actionsTd.appendChild(rule_button('Edit', () => edit_path_rule(index)));
+    actionsTd.appendChild(rule_button('Up', () => move_path_rule(index, -1)));
+    actionsTd.appendChild(rule_button('Down', () => move_path_rule(index, 1)));
+    actionsTd.appendChild(rule_button('Delete', () => run_path_rule_command('delete_path_rule', { index })));
+    tr.appendChild(actionsTd);
+
+    pathRulesTable.appendChild(tr);
+  });
+
+  const conflicts = await invoke('analyze_path_rules', { domain: pathRuleDomain });
+  pathRuleConflicts.innerHTML = '';
+  conflicts.forEach(conflict => {
+    const li = document.createElement('li');
+    li.className = 'problem-warning';
+    li.innerText = conflict.message;
+    pathRuleConflicts.appendChild(li);
+  });
+  pathRuleConflicts.style.display = conflicts.length > 0 ? 'block' : 'none';
+}
+
+async function open_path_rule_modal(domain) {
+  pathRuleDomain = domain;
+  pathRuleModalTitle.innerText = `Path Rules for ${domain}`;
+  pathRuleError.style.display = 'none';
+  reset_path_rule_form();
+
+  try {
+    await render_path_rules(await invoke('get_path_rules', { domain }));
+    pathRuleModal.style.display = 'block';
+  } catch (error) {
+    show_config_error(error);
+  }
+}
+
+async function run_path_rule_command(command, args) {
+  if (!ensure_no_pending()) {
+    return;
+  }
+
+  try {
+    await render_path_rules(await invoke(command, { domain: pathRuleDomain, ...args }));
+    pathRuleError.style.display = 'none';
+    reset_path_rule_form();
+    await get_configuration();
+    await load_problems();
+    await begin_session();
+  } catch (error) {
+    pathRuleError.innerText = describe_error(error);
+    pathRuleError.style.display = 'block';
+  }
+}
+
+async function move_path_rule(index, offset) {
+  const target = index + offset;
+  if (target < 0 || target >= pathRules.length) {
+    return;
+  }
+
+  const order = pathRules.map((_, i) => i);
+  [order[index], order[target]] = [order[target], order[index]];
+  await run_path_rule_command('reorder_path_rules', { order });
+}
+
+pathRuleForm.addEventListener('submit', async (e) => {
+  e.preventDefault();
+
+  const pathRule = {
+    path: pathRulePath.value,
+    match_type: pathRuleMatchType.value,
+    rule_type: pathRuleRuleType.value,
+    max_age_seconds: parseInt(pathRuleMaxAge.value, 10) || 0,
+  };
+
+  if (editingPathRule === null) {
+    await run_path_rule_command('add_path_rule', { pathRule });
+  } else {
+    await run_path_rule_command('update_path_rule', { index: editingPathRule, pathRule });
+  }
+});
+
+pathRuleCancel.addEventListener('click', reset_path_rule_form);
+
+pathRuleModal.querySelector('.close-button').addEventListener('click', () => {
+  pathRuleModal.style.display = 'none';
+});
+
+//ROUTING
+
+const routingModal = document.getElementById('routingModal');
+const routingModalTitle = document.getElementById('routingModalTitle');
+const routingOptionsForm = document.getElementById('routingOptionsForm');
+const routingMethod = document.getElementById('routingMethod');
+const routingHttpsOnly = document.getElementById('routingHttpsOnly');
+const routingHealthChecks = document.getElementById('routingHealthChecks');
+const routingHealthInterval = document.getElementById('routingHealthInterval');
+const routingHealthPath = document.getElementById('routingHealthPath');
+const routingLocationsTable = document.getElementById('routing_locations');
+const routingLocationForm = document.getElementById('routingLocationForm');
+const locationSubmit = document.getElementById('locationSubmit');
+const locationCancel = document.getElementById('locationCancel');
+const routingError = document.getElementById('routingError');
+const routingProbe = document.getElementById('routing_probe');
+
+const locationInputs = {
+  priority: document.getElementById('locationPriority'),
+  forward_addr: document.getElementById('locationAddr'),
+  forward_ipv4: document.getElementById('locationIpv4'),
+  forward_ipv6: document.getElementById('locationIpv6'),
+  forward_port_http: document.getElementById('locationPortHttp'),
+  forward_port_https: document.getElementById('locationPortHttps'),
+};
+const locationPrimary = document.getElementById('locationPrimary');
+
+// Domain whose routing is shown, and the location being edited or null when adding
+let routingDomain = null;
+let editingLocation = null;
+let routingRule = null;
+
+function describe_location(location) {
+  const addr = location.forward_addr ?? location.forward_ipv4 ?? location.forward_ipv6 ?? '?';
+  const port = location.forward_port_https ?? location.forward_port_http ?? '';
+  let text = port === '' ? addr : `${addr}:${port}`;
+  if (location.primary) {
+    text += ' (primary)';
+  }
+  if (location.priority != null) {
+    text += ` priority ${location.priority}`;
+  }
+  return text;
+}
+
+function reset_location_form() {
+  editingLocation = null;
+  locationPrimary.checked = false;
+  Object.values(locationInputs).forEach(input => input.value = '');
+  locationSubmit.innerText = 'Add';
+  locationCancel.style.display = 'none';
+}
+
+function edit_location(index) {
+  const location = routingRule.routing_locations[index];
+  editingLocation = index;
+  locationPrimary.checked = location.primary === true;
+  Object.entries(locationInputs).forEach(([key, input]) => input.value = location[key] ?? '');
+  locationSubmit.innerText = 'Update';
+  locationCancel.style.display = 'inline';
+}
+
+function render_routing(rule) {
+  routingRule = rule;
+  routingLocationsTable.innerHTML = '';
+  routingOptionsForm.style.display = rule ? 'block' : 'none';
+
+  if (!rule) {
+    return;
+  }
+
+  routingMethod.value = rule.routing_method;
+  routingHttpsOnly.checked = rule.https_only;
+  routingHealthChecks.checked = rule.enable_health_checks;
+  routingHealthInterval.value = rule.health_check_interval;
+  routingHealthPath.value = rule.health_check_path ?? '';
+
+  rule.routing_locations.forEach((location, index) => {
+    const tr = document.createElement('tr');
+
+    const locationTd = document.createElement('td');
+    locationTd.innerText = `#${index + 1} ${describe_location(location)}`;
+    tr.appendChild(locationTd);
+
+    const actionsTd = document.createElement('td');
+    actionsTd.appendChild(rule_button('Edit', () => edit_location(index)));
+    actionsTd.appendChild(rule_button('Up', () => move_location(index, -1)));
+    actionsTd.appendChild(rule_button('Down', () => move_location(index, 1)));
+    actionsTd.appendChild(rule_button('Delete', () => run_routing_command('delete_routing_location', { index })));
+    tr.appendChild(actionsTd);
+
+    routingLocationsTable.appendChild(tr);
+  });
+}
+
+async function open_routing_modal(domain) {
+  routingDomain = domain;
+  routingProbe.innerHTML = '';
+  routingModalTitle.innerText = `Routing for ${domain}`;
+  routingError.style.display = 'none';
+  reset_location_form();
+
+  try {
+    render_routing(await invoke('get_routing_rule', { domain }));
+    routingModal.style.display = 'block';
+  } catch (error) {
+    show_config_error(error);
+  }
+}
+
+async function run_routing_command(command, args) {
+  if (!ensure_no_pending()) {
+    return;
+  }
 
+  try {
+    render_routing(await invoke(command, { domain: routingDomain, ...args }));
+    routingError.style.display = 'none';
+    reset_location_form();
+    await get_configuration();
+    render_rules(config.proxy_rules);
+    await load_problems();
+    await begin_session();
+  } catch (error) {
+    routingError.innerText = describe_error(error);
+    routingError.style.display = 'block';
+  }
+}
+
+async function move_location(index, offset) {
+  const target = index + offset;
+  if (target < 0 || target >= routingRule.routing_locations.length) {
+    return;
+  }
+
+  const order = routingRule.routing_locations.map((_, i) => i);
+  [order[index], order[target]] = [order[target], order[index]];
+  await run_routing_command('reorder_routing_locations', { order });
+}
+
+function optional_number(input) {
+  return input.value === '' ? null : parseInt(input.value, 10);
+}
+
+routingLocationForm.addEventListener('submit', async (e) => {
+  e.preventDefault();
+
+  const location = {
+    primary: locationPrimary.checked,
+    priority: optional_number(locationInputs.priority),
+    forward_addr: locationInputs.forward_addr.value || null,
+    forward_ipv4: locationInputs.forward_ipv4.value || null,
+    forward_ipv6: locationInputs.forward_ipv6.value || null,
+    forward_port_http: optional_number(locationInputs.forward_port_http),
+    forward_port_https: optional_number(locationInputs.forward_port_https),
+  };
+
+  if (editingLocation === null) {
+    await run_routing_command('add_routing_location', { location });
+  } else {
+    await run_routing_command('update_routing_location', { index: editingLocation, location });
+  }
+});
+
+routingOptionsForm.addEventListener('submit', async (e) => {
+  e.preventDefault();
+
+  await run_routing_command('update_routing_options', {
+    options: {
+      routing_method: routingMethod.value,
+      https_only: routingHttpsOnly.checked,
+      enable_health_checks: routingHealthChecks.checked,
+      health_check_interval: parseInt(routingHealthInterval.value, 10) || 0,
+      health_check_path: routingHealthPath.value || null,
+    }
+  });
+});
+
+locationCancel.addEventListener('click', reset_location_form);
+
+function describe_probe(probe) {
+  let text = `#${probe.index + 1} ${probe.url}: `;
+  if (probe.status != null) {
+    text += `${probe.status} in ${probe.latency_ms} ms`;
+  } else {
+    text += probe.error;
+  }
+  if (probe.tls) {
+    text += probe.tls.ok ? ` (TLS ${probe.tls.version})` : ` (TLS failed: ${probe.tls.error})`;
+  }
+  return text;
+}
+
+document.getElementById('probe_locations').addEventListener('click', async () => {
+  routingProbe.innerHTML = '<li>Probing...</li>';
+
+  try {
+    const probes = await invoke('probe_routing_locations', { domain: routingDomain });
+    routingProbe.innerHTML = '';
+    probes.forEach(probe => {
+      const li = document.createElement('li');
+      li.className = probe.healthy ? 'change-added' : 'change-removed';
+      li.innerText = describe_probe(probe);
+      routingProbe.appendChild(li);
+    });
+  } catch (error) {
+    routingProbe.innerHTML = '';
+    routingError.innerText = describe_error(error);
+    routingError.style.display = 'block';
+  }
+});
+
+routingModal.querySelector('.close-button').addEventListener('click', () => {
+  routingModal.style.display = 'none';
+});
+
+//FLAGS
+
+const flagsModal = document.getElementById('flagsModal');
+const flagsModalTitle = document.getElementById('flagsModalTitle');
+const flagsForm = document.getElementById('flagsForm');
+const flagsEnableCompression = document.getElementById('flagsEnableCompression');
+const flagsEnableCompressionLabel = document.getElementById('flagsEnableCompressionLabel');
+const flagsAlgorithms = document.getElementById('flags_algorithms');
+const flagsMimeTypes = document.getElementById('flags_mime_types');
+const flagsOtherMimeTypes = document.getElementById('flagsOtherMimeTypes');
+const flagsMinificationOptions = document.getElementById('flagsMinificationOptions');
+const flagsEnableMinification = document.getElementById('flagsEnableMinification');
+const flagsMinification = document.getElementById('flags_minification');
+const flagsError = document.getElementById('flagsError');
+
+let flagOptions;
+// Domain of the rule whose flags are edited, null for the global compression_flags setting
+let flagsDomain = null;
+
+function flag_checkbox(value, checked) {
+  const label = document.createElement('label');
+  const checkbox = document.createElement('input');
+  checkbox.type = 'checkbox';
+  checkbox.value = value;
+  checkbox.checked = checked;
+  label.appendChild(checkbox);
+  label.append(` ${value}`);
+  return label;
+}
+
+function render_flags(flags) {
+  flagsEnableCompression.checked = flags.enable_compression;
+  flagsEnableMinification.checked = flags.enable_minification;
+
+  flagsAlgorithms.innerHTML = '';
+  flagOptions.algorithms.forEach(option => {
+    const flag = flags.compression.algorithms.find(flag => flag.algorithm === option.name);
+    const tr = document.createElement('tr');
+
+    const nameTd = document.createElement('td');
+    nameTd.appendChild(flag_checkbox(option.name, flag !== undefined));
+    tr.appendChild(nameTd);
+
+    const levelTd = document.createElement('td');
+    const level = document.createElement('input');
+    level.type = 'number';
+    level.min = option.min_level;
+    level.max = option.max_level;
+    level.placeholder = `Level ${option.min_level}-${option.max_level}`;
+    level.style.width = '110px';
+    level.value = flag?.level ?? '';
+    levelTd.appendChild(level);
+    tr.appendChild(levelTd);
+
+    flagsAlgorithms.appendChild(tr);
+  });
+
+  flagsMimeTypes.innerHTML = '';
+  flagOptions.mime_types.forEach(mimeType => {
+    flagsMimeTypes.appendChild(flag_checkbox(mimeType, flags.compression.mime_types.includes(mimeType)));
+  });
+  flagsOtherMimeTypes.value = flags.compression.mime_types
+    .filter(mimeType => !flagOptions.mime_types.includes(mimeType))
+    .join(', ');
+
+  flagsMinification.innerHTML = '';
+  flagOptions.minification.forEach(target => {
+    flagsMinification.appendChild(flag_checkbox(target, flags.minification[target]));
+  });
+}
+
+function read_flags() {
+  const algorithms = [...flagsAlgorithms.querySelectorAll('tr')]
+    .filter(tr => tr.querySelector('input[type=checkbox]').checked)
+    .map(tr => ({
+      algorithm: tr.querySelector('input[type=checkbox]').value,
+      level: optional_number(tr.querySelector('input[type=number]'))
+    }));
+
+  const mimeTypes = [...flagsMimeTypes.querySelectorAll('input:checked')].map(checkbox => checkbox.value)
+    .concat(flagsOtherMimeTypes.value.split(',').map(mimeType => mimeType.trim()).filter(mimeType => mimeType !== ''));
+
+  const minification = {};
+  flagsMinification.querySelectorAll('input').forEach(checkbox => {
+    minification[checkbox.value] = checkbox.checked;
+  });
+
+  return {
+    enable_compression: flagsEnableCompression.checked,
+    compression: { algorithms, mime_types: mimeTypes },
+    enable_minification: flagsEnableMinification.checked,
+    minification
+  };
+}
+
+async function open_flags_modal(domain) {
+  flagsDomain = domain;
+  flagsError.style.display = 'none';
+
+  // The global setting only has compression flags, enable_compression is a setting of its own.
+  flagsModalTitle.innerText = domain ? `Flags for ${domain}` : 'Compression Flags';
+  flagsEnableCompressionLabel.style.display = domain ? 'inline' : 'none';
+  flagsMinificationOptions.style.display = domain ? 'block' : 'none';
+
+  try {
+    if (flagOptions === undefined) {
+      flagOptions = await invoke('get_flag_options');
+    }
+
+    const flags = domain
+      ? await invoke('get_rule_flags', { domain })
+      : {
+          enable_compression: false,
+          compression: await invoke('parse_compression_flags', { flags: config.compression_flags ?? null }),
+          enable_minification: false,
+          minification: {}
+        };
+
+    render_flags(flags);
+    flagsModal.style.display = 'block';
+  } catch (error) {
+    show_config_error(error);
+  }
+}
+
+flagsForm.addEventListener('submit', async (e) => {
+  e.preventDefault();
+  const flags = read_flags();
+
+  try {
+    if (flagsDomain === null) {
+      const settingValue = await invoke('format_compression_flags', { flags: flags.compression });
+      pendingChanges = await invoke('stage_value', { settingName: 'compression_flags', settingValue: settingValue ?? '' });
+      render_settings();
+      render_pending();
+    } else {
+      if (!ensure_no_pending()) {
+        return;
+      }
+      await invoke('set_rule_flags', { domain: flagsDomain, flags });
+      await get_configuration();
+      render_rules(config.proxy_rules);
+      await load_problems();
+      await begin_session();
+    }
+  } catch (error) {
+    flagsError.innerText = describe_error(error);
+    flagsError.style.display = 'block';
+    return;
+  }
+
+  flagsModal.style.display = 'none';
+});
+
+flagsModal.querySelector('.close-button').addEventListener('click', () => {
+  flagsModal.style.display = 'none';
+});
+
+//URL SIMULATOR
+
+const simulateForm = document.getElementById('simulate_form');
+const simulateResult = document.getElementById('simulate_result');
+const simulateOutcome = document.getElementById('simulate_outcome');
+const simulateTrace = document.getElementById('simulate_trace');
+
+simulateForm.addEventListener('submit', async (e) => {
+  e.preventDefault();
+
+  const request = {
+    url: document.getElementById('simulate_url').value,
+    user_agent: document.getElementById('simulate_user_agent').value || null,
+    method: document.getElementById('simulate_method').value,
+  };
+
+  try {
+    const simulation = await invoke('simulate_request', { request });
+
+    let outcome = simulation.allowed ? 'Allowed' : 'Blocked';
+    if (simulation.max_age_seconds != null) {
+      outcome += `, cached for ${simulation.max_age_seconds} seconds`;
+    }
+    simulateOutcome.innerText = outcome;
+    simulateOutcome.className = simulation.allowed ? 'change-added' : 'change-removed';
+
+    simulateTrace.innerHTML = '';
+    simulation.trace.forEach(step => {
+      const li = document.createElement('li');
+      li.innerText = step;
+      simulateTrace.appendChild(li);
+    });
+
+    simulateResult.style.display = 'block';
+  } catch (error) {
+    show_config_error(error);
+  }
+});
+
+//NGINX IMPORT
+
+const nginxText = document.getElementById('nginx_text');
+const nginxResult = document.getElementById('nginx_result');
+const nginxRulesTable = document.getElementById('nginx_rules');
+const nginxUntranslatedTitle = document.getElementById('nginx_untranslated_title');
+const nginxUntranslated = document.getElementById('nginx_untranslated');
+
+// Rules from the last preview, in the order of the checkboxes
+let nginxRules = [];
+
+function render_nginx_import(result) {
+  nginxRules = result.rules;
+  nginxRulesTable.innerHTML = '';
+
+  result.rules.forEach((rule, index) => {
+    const tr = document.createElement('tr');
+
+    const checkTd = document.createElement('td');
+    const checkbox = document.createElement('input');
+    checkbox.type = 'checkbox';
+    checkbox.dataset.index = index;
+    // Domains that already have a rule would be rejected
+    checkbox.checked = !config.proxy_rules.some(existing => existing.domain.toLowerCase() === rule.domain);
+    checkTd.appendChild(checkbox);
+    tr.appendChild(checkTd);
+
+    const domainTd = document.createElement('td');
+    domainTd.innerHTML = `<b>${rule.domain}</b>`;
+    tr.appendChild(domainTd);
+
+    const targetTd = document.createElement('td');
+    targetTd.innerText = forward_target(rule);
+    tr.appendChild(targetTd);
+
+    const pathsTd = document.createElement('td');
+    pathsTd.innerText = `${(rule.path_rules ?? []).length} path rule(s)`;
+    tr.appendChild(pathsTd);
+
+    nginxRulesTable.appendChild(tr);
+  });
+
+  nginxUntranslated.innerHTML = '';
+  result.untranslated.forEach(item => {
+    const li = document.createElement('li');
+    li.innerText = `Line ${item.line} (${item.context}): ${item.directive} - ${item.reason}`;
+    nginxUntranslated.appendChild(li);
+  });
+  nginxUntranslatedTitle.style.display = result.untranslated.length ? 'block' : 'none';
+
+  nginxResult.style.display = 'block';
+}
+
+document.getElementById('preview_nginx').addEventListener('click', async () => {
+  try {
+    render_nginx_import(await invoke('import_nginx', { text: nginxText.value }));
+    hide_config_error();
+  } catch (error) {
+    nginxResult.style.display = 'none';
+    show_config_error(error);
+  }
+});
+
+document.getElementById('import_nginx').addEventListener('click', async () => {
+  const rules = [...nginxRulesTable.querySelectorAll('input:checked')].map(checkbox => nginxRules[checkbox.dataset.index]);
+  if (rules.length === 0) {
+    return;
+  }
+
+  if (await run_rule_command('add_proxy_rules', { rules })) {
+    nginxResult.style.display = 'none';
+    nginxText.value = '';
+  }
+});
+
+//EXPORT
+
+const exportResult = document.getElementById('export_result');
+const exportWarnings = document.getElementById('export_warnings');
+const exportText = document.getElementById('export_text');
+const copyExportButton = document.getElementById('copy_export');
+
+document.getElementById('export_configuration').addEventListener('click', async () => {
+  try {
+    const result = await invoke('export_configuration', { format: document.getElementById('export_format').value });
+
+    exportWarnings.innerHTML = '';
+    result.warnings.forEach(warning => {
+      const li = document.createElement('li');
+      li.className = 'change-removed';
+      li.innerText = warning.domain ? `${warning.domain}: ${warning.message}` : warning.message;
+      exportWarnings.appendChild(li);
+    });
+
+    exportText.innerText = result.text;
+    exportResult.style.display = 'block';
+    copyExportButton.style.display = 'inline-block';
+    hide_config_error();
+  } catch (error) {
+    show_config_error(error);
+  }
+});
+
+copyExportButton.addEventListener('click', async () => {
+  await navigator.clipboard.writeText(exportText.innerText);
+});
+
+//CERTIFICATES
+
+const certificateResult = document.getElementById('certificate_result');
+const certificateDir = document.getElementById('certificate_dir');
+const certificatesTable = document.getElementById('certificates');
+const certificateUnreadable = document.getElementById('certificate_unreadable');
+const certificateDaysInput = document.getElementById('certificate_days');
+const certificateDomain = document.getElementById('certificate_domain');
+const certificateChain = document.getElementById('certificate_chain');
+const certificateKey = document.getElementById('certificate_key');
+
+const certificateStatusNames = {
+  Valid: 'Valid',
+  ExpiringSoon: 'Expiring soon',
+  Expired: 'Expired',
+  Missing: 'No certificate',
+};
+
+async function load_certificates() {
+  const warningDays = certificateDaysInput.value === '' ? null : Number(certificateDaysInput.value);
+
+  try {
+    render_certificates(await invoke('get_certificates', { warningDays }));
+  } catch (error) {
+    show_config_error(error);
+  }
+}
+
+function render_certificates(inventory) {
+  certificateDir.innerText = inventory.directory
+    ? `Certificates in ${inventory.directory}`
+    : 'No certificate directory found next to the configuration, choose one.';
+
+  certificatesTable.innerHTML = '';
+  const header = document.createElement('tr');
+  ['Domain', 'Source', 'Status', 'Covered by', 'Expires', 'Days left', 'Key', 'Issuer', ''].forEach(title => {
+    const th = document.createElement('th');
+    th.innerText = title;
+    header.appendChild(th);
+  });
+  certificatesTable.appendChild(header);
+
+  inventory.domains.forEach(domain => {
+    const tr = document.createElement('tr');
+    if (domain.status === 'Expired' || domain.status === 'Missing') {
+      tr.className = 'problem-error';
+    } else if (domain.status === 'ExpiringSoon') {
+      tr.className = 'problem-warning';
+    }
+
+    const certificate = domain.certificate;
+    [
+      domain.domain,
+      domain.manual ? 'Manual' : "Let's Encrypt",
+      certificateStatusNames[domain.status],
+      domain.covered_by ?? '',
+      certificate?.not_after ?? '',
+      certificate?.days_left ?? '',
+      certificate?.key_type ?? '',
+      certificate?.issuer ?? '',
+    ].forEach(text => {
+      const td = document.createElement('td');
+      td.innerText = text;
+      tr.appendChild(td);
+    });
+
+    const actionsTd = document.createElement('td');
+    if (domain.manual) {
+      actionsTd.appendChild(rule_button("Use Let's Encrypt", () => remove_certificate(domain.domain)));
+    }
+    tr.appendChild(actionsTd);
+
+    certificatesTable.appendChild(tr);
+  });
+
+  certificateDomain.innerHTML = '';
+  inventory.domains.forEach(domain => {
+    const option = document.createElement('option');
+    option.value = domain.domain;
+    option.innerText = domain.domain;
+    certificateDomain.appendChild(option);
+  });
+
+  certificateUnreadable.innerHTML = '';
+  inventory.unreadable.forEach(file => {
+    const li = document.createElement('li');
+    li.className = 'change-removed';
+    li.innerText = `Could not read ${file.path}: ${file.message}`;
+    certificateUnreadable.appendChild(li);
+  });
+
+  certificateResult.style.display = 'block';
+}
+
+document.getElementById('load_certificates').addEventListener('click', load_certificates);
+
+document.getElementById('import_certificate').addEventListener('click', async () => {
+  if (!ensure_no_pending()) {
+    return;
+  }
+
+  try {
+    render_certificates(await invoke('import_certificate', {
+      domain: certificateDomain.value,
+      chain: certificateChain.value,
+      privateKey: certificateKey.value,
+    }));
+    certificateChain.value = '';
+    certificateKey.value = '';
+    hide_config_error();
+  } catch (error) {
+    show_config_error(error);
+    if (error.kind === 'Invalid') {
+      render_problems(error.issues);
+    }
+  }
+  await begin_session();
+});
+
+async function remove_certificate(domain) {
+  if (!ensure_no_pending() || !confirm(`Delete the imported certificate of ${domain} and use Let's Encrypt again?`)) {
+    return;
+  }
+
+  try {
+    render_certificates(await invoke('remove_certificate', { domain }));
+  } catch (error) {
+    show_config_error(error);
+  }
+  await begin_session();
+}
+
+document.getElementById('choose_certificate_dir').addEventListener('click', async () => {
+  const path = prompt('Directory the proxy keeps its certificates in (leave empty to search next to the configuration):');
+  if (path === null) {
+    return;
+  }
+
+  try {
+    await invoke('set_certificate_dir', { path: path === '' ? null : path });
+  } catch (error) {
+    show_config_error(error);
+    return;
+  }
+  await load_certificates();
+});
+
+//BACKEND TLS
+
+const backendTlsTable = document.getElementById('backend_tls');
+const checkBackendTlsButton = document.getElementById('check_backend_tls');
+
+checkBackendTlsButton.addEventListener('click', async () => {
+  checkBackendTlsButton.disabled = true;
+  try {
+    render_backend_tls(await invoke('check_backend_tls'));
+    hide_config_error();
+  } catch (error) {
+    show_config_error(error);
+  }
+  checkBackendTlsButton.disabled = false;
+});
+
+function render_backend_tls(checks) {
+  backendTlsTable.innerHTML = '';
+
+  if (checks.length === 0) {
+    const tr = document.createElement('tr');
+    const td = document.createElement('td');
+    td.innerText = 'No backend has an HTTPS port.';
+    tr.appendChild(td);
+    backendTlsTable.appendChild(tr);
+    return;
+  }
+
+  const header = document.createElement('tr');
+  ['Domain', 'Backend', 'Version', 'Cipher', 'Certificate', 'Result'].forEach(title => {
+    const th = document.createElement('th');
+    th.innerText = title;
+    header.appendChild(th);
+  });
+  backendTlsTable.appendChild(header);
+
+  checks.forEach(check => {
+    const tr = document.createElement('tr');
+    if (check.error || check.meets_minimum === false) {
+      tr.className = 'problem-error';
+    }
+
+    [
+      check.domain,
+      check.location === null ? check.target : `#${check.location + 1} ${check.target}`,
+      check.version ?? '',
+      check.cipher ?? '',
+      check.certificate_error ?? (check.version ? 'Trusted' : ''),
+      check.error ?? check.warning ?? 'Meets the minimum',
+    ].forEach(text => {
+      const td = document.createElement('td');
+      td.innerText = text;
+      tr.appendChild(td);
+    });
+
+    backendTlsTable.appendChild(tr);
+  });
+}
+
+//BACKUPS
+
+const backupsTable = document.getElementById('backups');
+const backupDiff = document.getElementById('backup_diff');
+const maxBackupsInput = document.getElementById('max_backups');
+
+async function load_backups() {
+  try {
+    const preferences = await invoke('get_preferences');
+    maxBackupsInput.value = preferences.max_backups ?? 20;
+    render_backups(await invoke('list_backups'));
+  } catch (error) {
+    show_config_error(error);
+  }
+}
+
+function render_backups(backups) {
+  backupsTable.innerHTML = '';
+  backupDiff.style.display = 'none';
+
+  backups.forEach(backup => {
+    const tr = document.createElement('tr');
+
+    const createdTd = document.createElement('td');
+    createdTd.innerText = backup.created;
+    tr.appendChild(createdTd);
+
+    const actionsTd = document.createElement('td');
+    actionsTd.appendChild(rule_button('Diff', () => show_backup_diff(backup.id)));
+    actionsTd.appendChild(rule_button('Restore', () => restore_backup(backup)));
+    tr.appendChild(actionsTd);
+
+    backupsTable.appendChild(tr);
+  });
+}
+
+async function show_backup_diff(id) {
+  try {
+    const lines = await invoke('diff_backup', { id });
+    backupDiff.innerHTML = '';
+    lines.forEach(line => {
+      const div = document.createElement('div');
+      const marker = line.kind === 'Added' ? '+ ' : line.kind === 'Removed' ? '- ' : '  ';
+      div.className = `diff-${line.kind.toLowerCase()}`;
+      div.innerText = marker + line.text;
+      backupDiff.appendChild(div);
+    });
+    backupDiff.style.display = 'block';
+  } catch (error) {
+    show_config_error(error);
+  }
+}
+
+async function restore_backup(backup) {
+  if (!ensure_no_pending()) {
+    return;
+  }
+
+  if (!confirm(`Restore the configuration from ${backup.created}? The current file is backed up first.`)) {
+    return;
+  }
+
+  try {
+    await invoke('restore_backup', { id: backup.id });
     await load_configuration();
-});
\ No newline at end of file
+  } catch (error) {
+    show_config_error(error);
+  }
+}
+
+maxBackupsInput.addEventListener('change', async () => {
+  try {
+    await invoke('set_max_backups', { maxBackups: parseInt(maxBackupsInput.value, 10) || 0 });
+    render_backups(await invoke('list_backups'));
+  } catch (error) {
+    show_config_error(error);
+  }
+});
+
+//PROXY PROCESS
+
+const { listen } = window.__TAURI__.event;
+
+const proxyState = document.getElementById('proxy_state');
+const proxyMessage = document.getElementById('proxy_message');
+const startProxyButton = document.getElementById('start_proxy');
+const stopProxyButton = document.getElementById('stop_proxy');
+const restartProxyButton = document.getElementById('restart_proxy');
+
+function render_proxy_status(status) {
+  proxyState.innerText = status.pid ? `${status.state} (pid ${status.pid})` : status.state;
+  proxyState.className = `proxy-state proxy-${status.state.toLowerCase()}`;
+  proxyMessage.innerText = status.message ?? '';
+
+  const running = status.state === 'Running';
+  startProxyButton.disabled = running || status.state === 'Stopping';
+  stopProxyButton.disabled = !running;
+  restartProxyButton.disabled = status.state === 'Stopping';
+}
+
+async function load_proxy_status() {
+  render_proxy_status(await invoke('get_proxy_status'));
+}
+
+async function run_proxy_command(command) {
+  try {
+    render_proxy_status(await invoke(command));
+  } catch (error) {
+    proxyMessage.innerText = describe_error(error);
+  }
+}
+
+startProxyButton.addEventListener('click', () => run_proxy_command('start_proxy'));
+stopProxyButton.addEventListener('click', () => run_proxy_command('stop_proxy'));
+restartProxyButton.addEventListener('click', () => run_proxy_command('restart_proxy'));
+
+listen('proxy-status', (event) => render_proxy_status(event.payload));
+
+//LOGS
+
+// Older lines are removed from the page so a long running tail stays responsive.
+const MAX_LOG_LINES = 2000;
+
+const logView = document.getElementById('log_view');
+const logError = document.getElementById('log_error');
+const logLevel = document.getElementById('log_level');
+const logDomain = document.getElementById('log_domain');
+const logPattern = document.getElementById('log_pattern');
+const logFile = document.getElementById('log_file');
+
+function show_log_error(error) {
+  logError.innerText = describe_error(error);
+  logError.style.display = 'block';
+}
+
+function append_log_lines(batch) {
+  const fragment = document.createDocumentFragment();
+
+  if (batch.dropped > 0) {
+    const div = document.createElement('div');
+    div.className = 'log-dropped';
+    div.innerText = `... ${batch.dropped} line(s) skipped ...`;
+    fragment.appendChild(div);
+  }
+
+  batch.lines.forEach(line => {
+    const div = document.createElement('div');
+    div.className = line.level ? `log-${line.level.toLowerCase()}` : '';
+    div.innerText = line.text;
+    fragment.appendChild(div);
+  });
+
+  const atBottom = logView.scrollTop + logView.clientHeight >= logView.scrollHeight - 5;
+  logView.appendChild(fragment);
+
+  while (logView.childElementCount > MAX_LOG_LINES) {
+    logView.removeChild(logView.firstChild);
+  }
+
+  if (atBottom) {
+    logView.scrollTop = logView.scrollHeight;
+  }
+}
+
+async function load_log_file() {
+  logFile.value = await invoke('get_log_file') ?? '';
+}
+
+document.getElementById('apply_log_filter').addEventListener('click', async () => {
+  try {
+    await invoke('set_log_filter', {
+      filter: {
+        min_level: logLevel.value || null,
+        domain: logDomain.value || null,
+        pattern: logPattern.value || null,
+      }
+    });
+    logError.style.display = 'none';
+  } catch (error) {
+    show_log_error(error);
+  }
+});
+
+document.getElementById('clear_logs').addEventListener('click', () => {
+  logView.innerHTML = '';
+});
+
+document.getElementById('tail_log_file').addEventListener('click', async () => {
+  try {
+    if (logFile.value) {
+      await invoke('tail_log_file', { path: logFile.value });
+    } else {
+      await invoke('stop_log_file');
+    }
+    logError.style.display = 'none';
+  } catch (error) {
+    show_log_error(error);
+  }
+});
+
+document.getElementById('stop_log_file').addEventListener('click', async () => {
+  await invoke('stop_log_file');
+  logFile.value = '';
+});
+
+// The backend sends the next batch only after this one is acknowledged, so acknowledge it
+// once it has been painted.
+listen('log-batch', (event) => {
+  append_log_lines(event.payload);
+  requestAnimationFrame(() => invoke('ack_log_batch', { sequence: event.payload.sequence }));
+});